@@ -0,0 +1,20 @@
+-- TOTP enrolment per user. A row with enabled = false is a pending enrolment.
+CREATE TABLE user_mfa (
+    user_id TEXT PRIMARY KEY,
+    secret TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    last_used_step BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    enabled_at TIMESTAMPTZ
+);
+
+-- One-time recovery codes, stored as Argon2 hashes
+CREATE TABLE user_mfa_recovery_codes (
+    id UUID PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES user_mfa (user_id) ON DELETE CASCADE,
+    code_hash TEXT NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX user_mfa_recovery_codes_user_idx ON user_mfa_recovery_codes (user_id);
@@ -0,0 +1,16 @@
+-- Machine identities owned by a workspace, authenticated with the OAuth2
+-- client credentials grant
+CREATE TABLE service_accounts (
+    id UUID PRIMARY KEY,
+    workspace_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    client_id TEXT NOT NULL UNIQUE,
+    client_secret_hash TEXT NOT NULL,
+    scopes TEXT[] NOT NULL DEFAULT '{}',
+    created_by TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMPTZ,
+    disabled_at TIMESTAMPTZ
+);
+
+CREATE INDEX service_accounts_workspace_idx ON service_accounts (workspace_id);
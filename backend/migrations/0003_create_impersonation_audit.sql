@@ -0,0 +1,30 @@
+-- Every impersonation grant issued by an admin
+CREATE TABLE impersonation_sessions (
+    id TEXT PRIMARY KEY, -- JWT ID of the impersonation token
+    actor_id TEXT NOT NULL,
+    actor_email TEXT NOT NULL,
+    subject_id TEXT NOT NULL,
+    subject_email TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    ended_at TIMESTAMPTZ
+);
+
+CREATE INDEX impersonation_sessions_actor_idx ON impersonation_sessions (actor_id);
+CREATE INDEX impersonation_sessions_subject_idx ON impersonation_sessions (subject_id);
+
+-- Every request made with an impersonation token, including blocked ones
+CREATE TABLE impersonation_audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    session_id TEXT NOT NULL REFERENCES impersonation_sessions (id),
+    actor_id TEXT NOT NULL,
+    subject_id TEXT NOT NULL,
+    method TEXT NOT NULL,
+    path TEXT NOT NULL,
+    status SMALLINT NOT NULL,
+    outcome TEXT NOT NULL, -- allowed | confirmed | blocked | unconfirmed
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX impersonation_audit_log_session_idx ON impersonation_audit_log (session_id, created_at);
@@ -0,0 +1,21 @@
+-- Execution history, recorded when the BFF submits an execution and kept in
+-- sync as status is observed from the control plane
+CREATE TABLE executions (
+    id TEXT PRIMARY KEY,
+    owner_id TEXT NOT NULL,
+    workspace_id TEXT,
+    language TEXT NOT NULL,
+    status TEXT NOT NULL,
+    code_hash TEXT NOT NULL,
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ
+);
+
+-- Listing is keyset-paginated on (created_at, id), newest first
+CREATE INDEX executions_owner_idx ON executions (owner_id, created_at DESC, id DESC);
+CREATE INDEX executions_workspace_idx ON executions (workspace_id, created_at DESC, id DESC)
+    WHERE workspace_id IS NOT NULL;
+CREATE INDEX executions_created_idx ON executions (created_at DESC, id DESC);
+CREATE INDEX executions_tags_idx ON executions USING GIN (tags);
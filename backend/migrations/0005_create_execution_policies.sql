@@ -0,0 +1,13 @@
+-- Per-workspace execution limits. NULL columns fall back to the global
+-- defaults; overrides can only tighten them
+CREATE TABLE workspace_execution_policies (
+    workspace_id TEXT PRIMARY KEY,
+    allowed_languages TEXT[],
+    max_code_bytes BIGINT,
+    max_env_vars INTEGER,
+    max_env_value_bytes BIGINT,
+    updated_by TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE executions ADD COLUMN language_version TEXT;
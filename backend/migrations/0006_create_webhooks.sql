@@ -0,0 +1,51 @@
+-- Completion callbacks, registered either for one execution or for every
+-- execution in a workspace
+CREATE TABLE webhooks (
+    id UUID PRIMARY KEY,
+    workspace_id TEXT,
+    execution_id TEXT REFERENCES executions (id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    created_by TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    disabled_at TIMESTAMPTZ,
+    CHECK ((workspace_id IS NULL) <> (execution_id IS NULL))
+);
+
+CREATE INDEX webhooks_workspace_idx ON webhooks (workspace_id) WHERE workspace_id IS NOT NULL;
+CREATE INDEX webhooks_execution_idx ON webhooks (execution_id) WHERE execution_id IS NOT NULL;
+
+-- The retry queue. A delivery is 'pending' until it succeeds ('delivered')
+-- or runs out of attempts ('dead', the dead-letter record)
+CREATE TABLE webhook_deliveries (
+    id UUID PRIMARY KEY,
+    webhook_id UUID NOT NULL REFERENCES webhooks (id) ON DELETE CASCADE,
+    execution_id TEXT NOT NULL,
+    event TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_status_code INTEGER,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    delivered_at TIMESTAMPTZ,
+    dead_at TIMESTAMPTZ,
+    UNIQUE (webhook_id, execution_id, event)
+);
+
+CREATE INDEX webhook_deliveries_due_idx ON webhook_deliveries (next_attempt_at) WHERE status = 'pending';
+CREATE INDEX webhook_deliveries_webhook_idx ON webhook_deliveries (webhook_id, created_at DESC);
+
+-- One row per HTTP attempt
+CREATE TABLE webhook_delivery_attempts (
+    id BIGSERIAL PRIMARY KEY,
+    delivery_id UUID NOT NULL REFERENCES webhook_deliveries (id) ON DELETE CASCADE,
+    attempt INTEGER NOT NULL,
+    status_code INTEGER,
+    error TEXT,
+    duration_ms INTEGER NOT NULL,
+    attempted_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX webhook_delivery_attempts_delivery_idx ON webhook_delivery_attempts (delivery_id, attempt);
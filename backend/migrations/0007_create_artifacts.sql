@@ -0,0 +1,15 @@
+-- Input files uploaded ahead of an execution. Rows start unattached and are
+-- claimed by the execution they are passed to
+CREATE TABLE artifacts (
+    id UUID PRIMARY KEY,
+    owner_id TEXT NOT NULL,
+    execution_id TEXT REFERENCES executions (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    storage_key TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX artifacts_owner_idx ON artifacts (owner_id, created_at DESC);
+CREATE INDEX artifacts_execution_idx ON artifacts (execution_id) WHERE execution_id IS NOT NULL;
@@ -0,0 +1,8 @@
+-- Re-runs point at the execution they were forked from, and every execution
+-- keeps the request it was submitted with so it can be replayed
+ALTER TABLE executions
+    ADD COLUMN parent_execution_id TEXT REFERENCES executions (id) ON DELETE SET NULL,
+    ADD COLUMN request JSONB;
+
+CREATE INDEX executions_parent_idx ON executions (parent_execution_id)
+    WHERE parent_execution_id IS NOT NULL;
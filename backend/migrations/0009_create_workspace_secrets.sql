@@ -0,0 +1,13 @@
+-- Per-workspace secrets, encrypted with AES-256-GCM under SECRETS_MASTER_KEY.
+-- Values are only ever decrypted to start an execution
+CREATE TABLE workspace_secrets (
+    workspace_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    nonce BYTEA NOT NULL,
+    ciphertext BYTEA NOT NULL,
+    created_by TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_by TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (workspace_id, name)
+);
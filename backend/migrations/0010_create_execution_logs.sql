@@ -0,0 +1,21 @@
+-- Execution output, split into lines and redacted before it is stored
+CREATE TABLE execution_logs (
+    execution_id TEXT NOT NULL REFERENCES executions (id) ON DELETE CASCADE,
+    seq BIGINT NOT NULL,
+    stream TEXT NOT NULL,
+    message TEXT NOT NULL,
+    logged_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (execution_id, seq)
+);
+
+-- Extra patterns a workspace wants masked in its executions' output
+CREATE TABLE workspace_redaction_rules (
+    id UUID PRIMARY KEY,
+    workspace_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    pattern TEXT NOT NULL,
+    created_by TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX workspace_redaction_rules_workspace_idx ON workspace_redaction_rules (workspace_id, created_at);
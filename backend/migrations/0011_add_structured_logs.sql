@@ -0,0 +1,10 @@
+-- Lines printed as JSON or logfmt are also stored parsed; `message` keeps
+-- the raw line
+ALTER TABLE execution_logs
+    ADD COLUMN format TEXT,
+    ADD COLUMN level TEXT,
+    ADD COLUMN parsed_message TEXT,
+    ADD COLUMN attributes JSONB;
+
+-- Per-execution parsing rules; NULL uses the defaults
+ALTER TABLE executions ADD COLUMN log_parsing JSONB;
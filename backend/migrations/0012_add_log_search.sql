@@ -0,0 +1,7 @@
+-- Full-text index over stored output. The `simple` configuration keeps
+-- identifiers and error codes intact instead of stemming them
+ALTER TABLE execution_logs
+    ADD COLUMN search TSVECTOR GENERATED ALWAYS AS (to_tsvector('simple', message)) STORED;
+
+CREATE INDEX execution_logs_search_idx ON execution_logs USING GIN (search);
+CREATE INDEX execution_logs_logged_at_idx ON execution_logs (logged_at DESC);
@@ -0,0 +1,39 @@
+-- Per-workspace alert rules, evaluated against output lines as they are
+-- ingested and against executions that end in failure
+CREATE TABLE alert_rules (
+    id UUID PRIMARY KEY,
+    workspace_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    pattern TEXT,
+    conditions JSONB,
+    min_level TEXT,
+    on_failure BOOLEAN NOT NULL DEFAULT FALSE,
+    cooldown_secs INTEGER NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_by TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX alert_rules_workspace_idx ON alert_rules (workspace_id, created_at);
+
+CREATE TABLE notifications (
+    id UUID PRIMARY KEY,
+    workspace_id TEXT NOT NULL,
+    rule_id UUID REFERENCES alert_rules (id) ON DELETE SET NULL,
+    rule_name TEXT NOT NULL,
+    execution_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    level TEXT,
+    message TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX notifications_workspace_idx ON notifications (workspace_id, created_at DESC, id DESC);
+
+-- Notifications are shared by a workspace; read state is per user
+CREATE TABLE notification_reads (
+    notification_id UUID NOT NULL REFERENCES notifications (id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL,
+    read_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (notification_id, user_id)
+);
@@ -0,0 +1,45 @@
+-- Notebooks are ordered cells run one at a time in a long-lived control-plane
+-- session, so each cell sees the state the previous ones left behind
+CREATE TABLE notebooks (
+    id UUID PRIMARY KEY,
+    owner_id TEXT NOT NULL,
+    workspace_id TEXT,
+    name TEXT NOT NULL,
+    language TEXT NOT NULL,
+    version TEXT,
+    -- Unresolved; secret references are resolved when a session starts
+    environment JSONB NOT NULL DEFAULT '{}',
+    session_id TEXT,
+    session_started_at TIMESTAMPTZ,
+    last_activity_at TIMESTAMPTZ,
+    -- Counts cell runs since the session started, like a kernel's prompt numbers
+    execution_count INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX notebooks_owner_idx ON notebooks (owner_id, updated_at DESC);
+CREATE INDEX notebooks_workspace_idx ON notebooks (workspace_id, updated_at DESC)
+    WHERE workspace_id IS NOT NULL;
+CREATE INDEX notebooks_session_idx ON notebooks (last_activity_at)
+    WHERE session_id IS NOT NULL;
+
+-- Each cell keeps the results of its last run, so a reopened notebook shows them
+CREATE TABLE notebook_cells (
+    id UUID PRIMARY KEY,
+    notebook_id UUID NOT NULL REFERENCES notebooks (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    source TEXT NOT NULL DEFAULT '',
+    execution_id TEXT REFERENCES executions (id) ON DELETE SET NULL,
+    execution_count INTEGER,
+    status TEXT,
+    outputs JSONB,
+    error TEXT,
+    ran_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX notebook_cells_notebook_idx ON notebook_cells (notebook_id, position);
+CREATE INDEX notebook_cells_execution_idx ON notebook_cells (execution_id)
+    WHERE execution_id IS NOT NULL;
@@ -0,0 +1,42 @@
+-- Recurring executions owned by a workspace, fired by the scheduler on the
+-- replica holding the scheduler lock
+CREATE TABLE schedules (
+    id UUID PRIMARY KEY,
+    workspace_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    cron TEXT NOT NULL,
+    timezone TEXT NOT NULL,
+    -- Unresolved, like executions.request
+    request JSONB NOT NULL,
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    log_parsing JSONB,
+    catch_up TEXT NOT NULL,
+    overlap TEXT NOT NULL,
+    paused BOOLEAN NOT NULL DEFAULT FALSE,
+    -- NULL while paused
+    next_run_at TIMESTAMPTZ,
+    last_run_at TIMESTAMPTZ,
+    -- Runs execute as this user
+    owner_id TEXT NOT NULL,
+    owner_is_service_account BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX schedules_workspace_idx ON schedules (workspace_id, created_at);
+CREATE INDEX schedules_due_idx ON schedules (next_run_at) WHERE NOT paused;
+
+-- One row per occurrence; the unique key keeps an occurrence from firing twice
+CREATE TABLE schedule_runs (
+    id UUID PRIMARY KEY,
+    schedule_id UUID NOT NULL REFERENCES schedules (id) ON DELETE CASCADE,
+    scheduled_for TIMESTAMPTZ NOT NULL,
+    triggered_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- pending while being fired, then started, skipped or failed
+    status TEXT NOT NULL,
+    execution_id TEXT REFERENCES executions (id) ON DELETE SET NULL,
+    reason TEXT,
+    UNIQUE (schedule_id, scheduled_for)
+);
+
+CREATE INDEX schedule_runs_schedule_idx ON schedule_runs (schedule_id, scheduled_for DESC);
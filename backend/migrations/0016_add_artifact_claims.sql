@@ -0,0 +1,5 @@
+-- A submission claims its uploads before the execution starts, so no two
+-- executions can take the same file and the sweeper leaves it alone
+ALTER TABLE artifacts ADD COLUMN claimed_at TIMESTAMPTZ;
+
+CREATE INDEX artifacts_unattached_idx ON artifacts (created_at) WHERE execution_id IS NULL;
@@ -0,0 +1,3 @@
+-- A cell run claims the notebook before it starts, so two requests cannot
+-- both pass the one-cell-at-a-time check and run in the same session
+ALTER TABLE notebooks ADD COLUMN run_claimed_at TIMESTAMPTZ;
@@ -0,0 +1,3 @@
+-- Literal environment values are sealed under the secrets master key;
+-- `environment` keeps secret references (and values written before this)
+ALTER TABLE notebooks ADD COLUMN sealed_environment JSONB;
@@ -0,0 +1,3 @@
+-- Literal environment values are sealed under the secrets master key;
+-- `request` keeps secret references (and values written before this)
+ALTER TABLE schedules ADD COLUMN sealed_environment JSONB;
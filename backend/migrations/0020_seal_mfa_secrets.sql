@@ -0,0 +1,3 @@
+-- TOTP secrets are sealed under the secrets master key. Rows written before
+-- this hold the plain secret and are sealed the next time they are used.
+ALTER TABLE user_mfa ADD COLUMN secret_sealed BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,620 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::types::Json;
+use tokio::sync::watch;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    executions::Execution,
+    logs::{parse, LogLine},
+    AppState,
+};
+
+const MAX_RULES_PER_WORKSPACE: i64 = 50;
+const MAX_PATTERN_LEN: usize = 1024;
+const MAX_COMPILED_PATTERN_BYTES: usize = 1 << 20;
+const MAX_NAME_LEN: usize = 64;
+const MAX_CONDITIONS: usize = 10;
+const MAX_COOLDOWN_SECS: i32 = 7 * 24 * 3600;
+const DEFAULT_COOLDOWN_SECS: i32 = 300;
+/// How long one execution stays deduplicated for a rule.
+const DEDUP_TTL_SECS: u64 = 24 * 3600;
+/// Stored notification messages are cut to this length.
+const MAX_MESSAGE_LEN: usize = 1024;
+/// Rules changed while an execution runs are picked up after this long.
+const RULES_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+/// Statuses that fire `on_failure` rules.
+const FAILED_STATUSES: &[&str] = &["failed", "timeout", "lost"];
+
+pub const KIND_LOG_MATCH: &str = "log_match";
+pub const KIND_EXECUTION_FAILED: &str = "execution_failed";
+
+/// Fires at most once per execution (deduplication) and at most once per
+/// cooldown window across the whole rule.
+const CLAIM_SCRIPT: &str = r"
+if redis.call('EXISTS', KEYS[1]) == 1 or redis.call('EXISTS', KEYS[2]) == 1 then
+    return 0
+end
+redis.call('SET', KEYS[1], 1, 'EX', ARGV[1])
+if tonumber(ARGV[2]) > 0 then
+    redis.call('SET', KEYS[2], 1, 'EX', ARGV[2])
+end
+return 1
+";
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct AlertRule {
+    pub id: Uuid,
+    pub workspace_id: String,
+    pub name: String,
+    /// Regular expression matched against the raw line
+    pub pattern: Option<String>,
+    /// Parsed attributes that must equal the given values
+    pub conditions: Option<Json<Map<String, Value>>>,
+    /// Lowest parsed level that matches
+    pub min_level: Option<String>,
+    pub on_failure: bool,
+    pub cooldown_secs: i32,
+    pub enabled: bool,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+const RULE_COLUMNS: &str =
+    "id, workspace_id, name, pattern, conditions, min_level, on_failure, cooldown_secs, enabled, created_by, created_at";
+
+#[derive(Debug, Deserialize)]
+pub struct NewRule {
+    pub name: String,
+    pub pattern: Option<String>,
+    pub conditions: Option<Map<String, Value>>,
+    pub min_level: Option<String>,
+    #[serde(default)]
+    pub on_failure: bool,
+    pub cooldown_secs: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Notification {
+    pub id: Uuid,
+    pub workspace_id: String,
+    pub rule_id: Option<Uuid>,
+    pub rule_name: String,
+    pub execution_id: String,
+    /// `log_match` or `execution_failed`
+    pub kind: String,
+    pub level: Option<String>,
+    pub message: String,
+    pub created_at: DateTime<Utc>,
+    pub read: bool,
+}
+
+const NOTIFICATION_COLUMNS: &str =
+    "n.id, n.workspace_id, n.rule_id, n.rule_name, n.execution_id, n.kind, n.level, n.message, n.created_at";
+
+/// Wakes WebSocket subscribers in this process when a notification is
+/// stored here; subscribers on other instances find it by polling.
+#[derive(Debug)]
+pub struct NotificationHub {
+    latest: watch::Sender<u64>,
+}
+
+impl Default for NotificationHub {
+    fn default() -> Self {
+        Self {
+            latest: watch::channel(0).0,
+        }
+    }
+}
+
+impl NotificationHub {
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.latest.subscribe()
+    }
+
+    fn notify(&self) {
+        self.latest.send_modify(|generation| *generation += 1);
+    }
+}
+
+fn compile(pattern: &str) -> Result<Regex> {
+    if pattern.is_empty() || pattern.len() > MAX_PATTERN_LEN {
+        return Err(AppError::BadRequest(format!(
+            "Patterns must be between 1 and {} characters",
+            MAX_PATTERN_LEN
+        )));
+    }
+    RegexBuilder::new(pattern)
+        .size_limit(MAX_COMPILED_PATTERN_BYTES)
+        .build()
+        .map_err(|e| AppError::BadRequest(format!("Invalid pattern: {}", e)))
+}
+
+fn validate(rule: &NewRule) -> Result<()> {
+    let name = rule.name.trim();
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(AppError::BadRequest(format!(
+            "Rule names must be between 1 and {} characters",
+            MAX_NAME_LEN
+        )));
+    }
+    if let Some(pattern) = &rule.pattern {
+        compile(pattern)?;
+    }
+    if rule.conditions.as_ref().is_some_and(|conditions| conditions.len() > MAX_CONDITIONS) {
+        return Err(AppError::BadRequest(format!("At most {} conditions are allowed", MAX_CONDITIONS)));
+    }
+    if let Some(level) = &rule.min_level {
+        if parse::level_rank(level).is_none() {
+            return Err(AppError::BadRequest(
+                "min_level must be trace, debug, info, warn, error or fatal".to_string(),
+            ));
+        }
+    }
+    let matches_lines = rule.pattern.is_some()
+        || rule.conditions.as_ref().is_some_and(|conditions| !conditions.is_empty())
+        || rule.min_level.is_some();
+    if !matches_lines && !rule.on_failure {
+        return Err(AppError::BadRequest(
+            "A rule needs a pattern, conditions, min_level or on_failure".to_string(),
+        ));
+    }
+    if rule
+        .cooldown_secs
+        .is_some_and(|secs| !(0..=MAX_COOLDOWN_SECS).contains(&secs))
+    {
+        return Err(AppError::BadRequest(format!(
+            "cooldown_secs must be between 0 and {}",
+            MAX_COOLDOWN_SECS
+        )));
+    }
+    Ok(())
+}
+
+pub async fn rules(state: &AppState, workspace_id: &str) -> Result<Vec<AlertRule>> {
+    Ok(sqlx::query_as::<_, AlertRule>(&format!(
+        "SELECT {} FROM alert_rules WHERE workspace_id = $1 ORDER BY created_at",
+        RULE_COLUMNS
+    ))
+    .bind(workspace_id)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+async fn enabled_rules(state: &AppState, workspace_id: &str) -> Result<Vec<AlertRule>> {
+    Ok(sqlx::query_as::<_, AlertRule>(&format!(
+        "SELECT {} FROM alert_rules WHERE workspace_id = $1 AND enabled",
+        RULE_COLUMNS
+    ))
+    .bind(workspace_id)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+pub async fn create_rule(state: &AppState, workspace_id: &str, rule: &NewRule, user_id: &str) -> Result<AlertRule> {
+    validate(rule)?;
+
+    let existing: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM alert_rules WHERE workspace_id = $1")
+        .bind(workspace_id)
+        .fetch_one(&state.db)
+        .await?;
+    if existing >= MAX_RULES_PER_WORKSPACE {
+        return Err(AppError::Conflict(format!(
+            "A workspace can have at most {} alert rules",
+            MAX_RULES_PER_WORKSPACE
+        )));
+    }
+
+    Ok(sqlx::query_as::<_, AlertRule>(&format!(
+        "INSERT INTO alert_rules \
+         (id, workspace_id, name, pattern, conditions, min_level, on_failure, cooldown_secs, created_by) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING {}",
+        RULE_COLUMNS
+    ))
+    .bind(Uuid::new_v4())
+    .bind(workspace_id)
+    .bind(rule.name.trim())
+    .bind(&rule.pattern)
+    .bind(rule.conditions.as_ref().filter(|conditions| !conditions.is_empty()).map(Json))
+    .bind(&rule.min_level)
+    .bind(rule.on_failure)
+    .bind(rule.cooldown_secs.unwrap_or(DEFAULT_COOLDOWN_SECS))
+    .bind(user_id)
+    .fetch_one(&state.db)
+    .await?)
+}
+
+pub async fn set_enabled(state: &AppState, workspace_id: &str, id: Uuid, enabled: bool) -> Result<Option<AlertRule>> {
+    Ok(sqlx::query_as::<_, AlertRule>(&format!(
+        "UPDATE alert_rules SET enabled = $3 WHERE id = $1 AND workspace_id = $2 RETURNING {}",
+        RULE_COLUMNS
+    ))
+    .bind(id)
+    .bind(workspace_id)
+    .bind(enabled)
+    .fetch_optional(&state.db)
+    .await?)
+}
+
+pub async fn delete_rule(state: &AppState, workspace_id: &str, id: Uuid) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM alert_rules WHERE id = $1 AND workspace_id = $2")
+        .bind(id)
+        .bind(workspace_id)
+        .execute(&state.db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+struct CompiledRule {
+    rule: AlertRule,
+    regex: Option<Regex>,
+    min_rank: Option<u8>,
+}
+
+impl CompiledRule {
+    fn new(rule: AlertRule) -> Option<Self> {
+        let regex = match rule.pattern.as_deref().map(compile).transpose() {
+            Ok(regex) => regex,
+            Err(e) => {
+                warn!("Skipping alert rule {}: {}", rule.id, e);
+                return None;
+            }
+        };
+        let min_rank = rule.min_level.as_deref().and_then(parse::level_rank);
+        Some(Self { rule, regex, min_rank })
+    }
+
+    fn matches_lines(&self) -> bool {
+        self.regex.is_some() || self.rule.conditions.is_some() || self.min_rank.is_some()
+    }
+
+    /// Every condition the rule sets must hold.
+    fn matches(&self, line: &LogLine) -> bool {
+        if !self.matches_lines() {
+            return false;
+        }
+        if let Some(min_rank) = self.min_rank {
+            let rank = line.level.as_deref().and_then(parse::level_rank);
+            if rank.is_none_or(|rank| rank < min_rank) {
+                return false;
+            }
+        }
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(&line.message) {
+                return false;
+            }
+        }
+        if let Some(Json(conditions)) = &self.rule.conditions {
+            let attributes = line.attributes.as_ref().and_then(Value::as_object);
+            let holds = conditions.iter().all(|(key, expected)| {
+                attributes
+                    .and_then(|attributes| attributes.get(key))
+                    .is_some_and(|actual| values_equal(actual, expected))
+            });
+            if !holds {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Compares loosely, so a condition of `"500"` matches a logged `500`.
+fn values_equal(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::String(actual), Value::String(expected)) => actual == expected,
+        (Value::String(text), other) | (other, Value::String(text)) => {
+            serde_json::from_str::<Value>(text).is_ok_and(|parsed| parsed == *other)
+        }
+        (actual, expected) => actual == expected,
+    }
+}
+
+/// Evaluates a workspace's rules against one execution's lines as they are
+/// ingested.
+pub struct Evaluator {
+    workspace_id: String,
+    rules: Vec<CompiledRule>,
+    loaded_at: Instant,
+    /// Rules that already fired for this execution; the Redis claim would
+    /// refuse them anyway, this just saves the round trip.
+    fired: HashSet<Uuid>,
+}
+
+impl Evaluator {
+    pub async fn load(state: &AppState, execution: &Execution) -> Result<Option<Self>> {
+        let Some(workspace_id) = execution.workspace_id.clone() else {
+            return Ok(None);
+        };
+        let rules = enabled_rules(state, &workspace_id).await?;
+
+        Ok(Some(Self {
+            workspace_id,
+            rules: rules.into_iter().filter_map(CompiledRule::new).collect(),
+            loaded_at: Instant::now(),
+            fired: HashSet::new(),
+        }))
+    }
+
+    async fn refresh(&mut self, state: &AppState) -> Result<()> {
+        if self.loaded_at.elapsed() < RULES_REFRESH_INTERVAL {
+            return Ok(());
+        }
+        let rules = enabled_rules(state, &self.workspace_id).await?;
+        self.rules = rules.into_iter().filter_map(CompiledRule::new).collect();
+        self.loaded_at = Instant::now();
+        Ok(())
+    }
+
+    pub async fn evaluate(&mut self, state: &AppState, execution_id: &str, lines: &[LogLine]) -> Result<()> {
+        self.refresh(state).await?;
+
+        for compiled in &self.rules {
+            if self.fired.contains(&compiled.rule.id) {
+                continue;
+            }
+            let Some(line) = lines.iter().find(|line| compiled.matches(line)) else {
+                continue;
+            };
+
+            self.fired.insert(compiled.rule.id);
+            let message = line.parsed_message.as_deref().unwrap_or(&line.message);
+            fire(state, &compiled.rule, execution_id, KIND_LOG_MATCH, line.level.as_deref(), message).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Fires the workspace's `on_failure` rules for an execution that ended in
+/// failure.
+pub async fn execution_finished(state: &AppState, execution: &Execution) -> Result<()> {
+    let Some(workspace_id) = execution.workspace_id.as_deref() else {
+        return Ok(());
+    };
+    if !FAILED_STATUSES.contains(&execution.status.as_str()) {
+        return Ok(());
+    }
+
+    let message = format!("Execution {} ended with status {}", execution.id, execution.status);
+    for rule in enabled_rules(state, workspace_id).await? {
+        if rule.on_failure {
+            fire(state, &rule, &execution.id, KIND_EXECUTION_FAILED, None, &message).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn claim(state: &AppState, rule: &AlertRule, execution_id: &str) -> Result<bool> {
+    let mut conn = state.redis_client.as_ref().clone();
+    let claimed: i64 = redis::Script::new(CLAIM_SCRIPT)
+        .key(format!("alert_dedup:{}:{}", rule.id, execution_id))
+        .key(format!("alert_cooldown:{}", rule.id))
+        .arg(DEDUP_TTL_SECS)
+        .arg(rule.cooldown_secs)
+        .invoke_async(&mut conn)
+        .await?;
+    Ok(claimed == 1)
+}
+
+async fn fire(
+    state: &AppState,
+    rule: &AlertRule,
+    execution_id: &str,
+    kind: &str,
+    level: Option<&str>,
+    message: &str,
+) -> Result<()> {
+    if !claim(state, rule, execution_id).await? {
+        return Ok(());
+    }
+
+    let message: String = message.chars().take(MAX_MESSAGE_LEN).collect();
+    sqlx::query(
+        "INSERT INTO notifications (id, workspace_id, rule_id, rule_name, execution_id, kind, level, message) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+    )
+    .bind(Uuid::new_v4())
+    .bind(&rule.workspace_id)
+    .bind(rule.id)
+    .bind(&rule.name)
+    .bind(execution_id)
+    .bind(kind)
+    .bind(level)
+    .bind(message)
+    .execute(&state.db)
+    .await?;
+
+    state.notifications.notify();
+    Ok(())
+}
+
+/// A workspace's notifications, newest first, with the caller's read state.
+/// `before` is the id of the last notification of the previous page.
+pub async fn notifications(
+    state: &AppState,
+    workspace_id: &str,
+    user_id: &str,
+    unread_only: bool,
+    before: Option<Uuid>,
+    limit: i64,
+) -> Result<Vec<Notification>> {
+    Ok(sqlx::query_as::<_, Notification>(&format!(
+        "SELECT {}, r.user_id IS NOT NULL AS read FROM notifications n \
+         LEFT JOIN notification_reads r ON r.notification_id = n.id AND r.user_id = $2 \
+         WHERE n.workspace_id = $1 AND ($3 = FALSE OR r.user_id IS NULL) \
+         AND ($4::UUID IS NULL OR (n.created_at, n.id) < (SELECT created_at, id FROM notifications WHERE id = $4)) \
+         ORDER BY n.created_at DESC, n.id DESC LIMIT $5",
+        NOTIFICATION_COLUMNS
+    ))
+    .bind(workspace_id)
+    .bind(user_id)
+    .bind(unread_only)
+    .bind(before)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+/// The newest notification of a workspace, where live delivery starts from.
+pub async fn latest(state: &AppState, workspace_id: &str) -> Result<Option<Uuid>> {
+    Ok(sqlx::query_scalar(
+        "SELECT id FROM notifications WHERE workspace_id = $1 ORDER BY created_at DESC, id DESC LIMIT 1",
+    )
+    .bind(workspace_id)
+    .fetch_optional(&state.db)
+    .await?)
+}
+
+/// Notifications newer than `after`, oldest first, for live delivery.
+pub async fn notifications_after(state: &AppState, workspace_id: &str, after: Option<Uuid>) -> Result<Vec<Notification>> {
+    Ok(sqlx::query_as::<_, Notification>(&format!(
+        "SELECT {}, FALSE AS read FROM notifications n \
+         WHERE n.workspace_id = $1 \
+         AND ($2::UUID IS NULL OR (n.created_at, n.id) > (SELECT created_at, id FROM notifications WHERE id = $2)) \
+         ORDER BY n.created_at, n.id LIMIT 100",
+        NOTIFICATION_COLUMNS
+    ))
+    .bind(workspace_id)
+    .bind(after)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+/// The workspace a notification belongs to.
+pub async fn notification_workspace(state: &AppState, id: Uuid) -> Result<Option<String>> {
+    Ok(sqlx::query_scalar("SELECT workspace_id FROM notifications WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?)
+}
+
+pub async fn mark_read(state: &AppState, id: Uuid, user_id: &str) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO notification_reads (notification_id, user_id) VALUES ($1, $2) \
+         ON CONFLICT (notification_id, user_id) DO NOTHING",
+    )
+    .bind(id)
+    .bind(user_id)
+    .execute(&state.db)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn new_rule(pattern: Option<&str>, conditions: Option<Value>, min_level: Option<&str>) -> NewRule {
+        NewRule {
+            name: "errors".to_string(),
+            pattern: pattern.map(str::to_string),
+            conditions: conditions.and_then(|conditions| conditions.as_object().cloned()),
+            min_level: min_level.map(str::to_string),
+            on_failure: false,
+            cooldown_secs: None,
+        }
+    }
+
+    fn compiled(pattern: Option<&str>, conditions: Option<Value>, min_level: Option<&str>) -> CompiledRule {
+        let rule = new_rule(pattern, conditions, min_level);
+        CompiledRule::new(AlertRule {
+            id: Uuid::new_v4(),
+            workspace_id: "ws-1".to_string(),
+            name: rule.name,
+            pattern: rule.pattern,
+            conditions: rule.conditions.map(Json),
+            min_level: rule.min_level,
+            on_failure: false,
+            cooldown_secs: DEFAULT_COOLDOWN_SECS,
+            enabled: true,
+            created_by: "user-1".to_string(),
+            created_at: Utc::now(),
+        })
+        .unwrap()
+    }
+
+    fn line(message: &str, level: Option<&str>, attributes: Option<Value>) -> LogLine {
+        LogLine {
+            seq: 1,
+            stream: "stdout".to_string(),
+            message: message.to_string(),
+            timestamp: Utc::now(),
+            format: None,
+            level: level.map(str::to_string),
+            parsed_message: None,
+            attributes,
+        }
+    }
+
+    #[test]
+    fn level_threshold_matches_that_level_and_above() {
+        let rule = compiled(None, None, Some("warn"));
+        assert!(!rule.matches(&line("x", Some("info"), None)));
+        assert!(rule.matches(&line("x", Some("warn"), None)));
+        assert!(rule.matches(&line("x", Some("fatal"), None)));
+        assert!(!rule.matches(&line("x", None, None)));
+    }
+
+    #[test]
+    fn every_set_condition_must_hold() {
+        let rule = compiled(Some("timed out"), Some(json!({"service": "billing"})), Some("error"));
+        let attributes = Some(json!({"service": "billing", "attempt": 3}));
+        assert!(rule.matches(&line("request timed out", Some("error"), attributes.clone())));
+        assert!(!rule.matches(&line("request failed", Some("error"), attributes.clone())));
+        assert!(!rule.matches(&line("request timed out", Some("info"), attributes)));
+        assert!(!rule.matches(&line("request timed out", Some("error"), Some(json!({"service": "search"})))));
+        assert!(!rule.matches(&line("request timed out", Some("error"), None)));
+    }
+
+    #[test]
+    fn rules_without_line_conditions_match_nothing() {
+        let rule = compiled(None, None, None);
+        assert!(!rule.matches_lines());
+        assert!(!rule.matches(&line("anything", Some("fatal"), Some(json!({})))));
+    }
+
+    #[test]
+    fn values_compare_loosely_across_strings() {
+        assert!(values_equal(&json!(500), &json!("500")));
+        assert!(values_equal(&json!("500"), &json!(500)));
+        assert!(values_equal(&json!(true), &json!("true")));
+        assert!(values_equal(&json!("abc"), &json!("abc")));
+        assert!(!values_equal(&json!("500"), &json!(" 500x")));
+        assert!(!values_equal(&json!(500), &json!("501")));
+        assert!(!values_equal(&json!("500"), &json!("500.0")));
+        assert!(!values_equal(&json!(500), &json!(501)));
+    }
+
+    #[test]
+    fn validate_rejects_rules_that_match_nothing() {
+        assert!(matches!(validate(&new_rule(None, None, None)), Err(AppError::BadRequest(_))));
+        assert!(matches!(validate(&new_rule(None, Some(json!({})), None)), Err(AppError::BadRequest(_))));
+        assert!(validate(&NewRule { on_failure: true, ..new_rule(None, None, None) }).is_ok());
+        assert!(validate(&new_rule(Some("panic"), None, None)).is_ok());
+        assert!(validate(&new_rule(None, Some(json!({"status": 500})), None)).is_ok());
+        assert!(validate(&new_rule(None, None, Some("error"))).is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_bad_fields() {
+        for rule in [
+            NewRule { name: "  ".to_string(), ..new_rule(Some("x"), None, None) },
+            new_rule(Some(""), None, None),
+            new_rule(Some("(unclosed"), None, None),
+            new_rule(None, None, Some("loud")),
+            NewRule { cooldown_secs: Some(-1), ..new_rule(Some("x"), None, None) },
+            new_rule(None, Some(Value::Object((0..=MAX_CONDITIONS).map(|i| (i.to_string(), json!(i))).collect())), None),
+        ] {
+            assert!(matches!(validate(&rule), Err(AppError::BadRequest(_))), "{:?}", rule);
+        }
+    }
+}
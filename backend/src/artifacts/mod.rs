@@ -0,0 +1,369 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt, TryStreamExt};
+use object_store::{
+    aws::AmazonS3Builder, local::LocalFileSystem, path::Path, GetOptions, GetRange, ObjectStore, WriteMultipart,
+};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    config::{ArtifactStorage, Config},
+    error::{AppError, Result},
+    AppState,
+};
+
+pub mod reaper;
+
+const UPLOADS_PREFIX: &str = "uploads";
+const EXECUTIONS_PREFIX: &str = "executions";
+const OUTPUTS_DIR: &str = "outputs";
+const MAX_NAME_LEN: usize = 255;
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// An uploaded input file.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Artifact {
+    pub id: Uuid,
+    pub owner_id: String,
+    pub execution_id: Option<String>,
+    pub name: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    #[serde(skip)]
+    pub storage_key: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A file the sandbox wrote under the execution's output prefix.
+#[derive(Debug, Serialize)]
+pub struct OutputArtifact {
+    pub path: String,
+    pub content_type: String,
+    pub size_bytes: u64,
+    pub last_modified: DateTime<Utc>,
+}
+
+/// An object opened for download, possibly restricted to a byte range.
+pub struct Download {
+    pub size: u64,
+    pub range: Option<Range<u64>>,
+    pub stream: std::pin::Pin<Box<dyn Stream<Item = std::result::Result<Bytes, object_store::Error>> + Send>>,
+}
+
+const SELECT_COLUMNS: &str = "id, owner_id, execution_id, name, content_type, size_bytes, storage_key, created_at";
+
+pub fn build_store(config: &Config) -> Result<Arc<dyn ObjectStore>> {
+    Ok(match config.artifact_storage {
+        ArtifactStorage::Local => {
+            std::fs::create_dir_all(&config.artifact_local_dir).map_err(|_| AppError::InternalServerError)?;
+            Arc::new(LocalFileSystem::new_with_prefix(&config.artifact_local_dir)?)
+        }
+        ArtifactStorage::S3 => {
+            let bucket = config
+                .artifact_s3_bucket
+                .as_deref()
+                .expect("ARTIFACT_S3_BUCKET is required when ARTIFACT_STORAGE=s3");
+            let mut builder = AmazonS3Builder::from_env().with_bucket_name(bucket);
+            if let Some(endpoint) = &config.artifact_s3_endpoint {
+                builder = builder.with_endpoint(endpoint).with_allow_http(endpoint.starts_with("http://"));
+            }
+            Arc::new(builder.build()?)
+        }
+    })
+}
+
+/// Keeps only the last path component and rejects names that cannot be
+/// stored or served safely.
+pub fn sanitize_name(name: &str) -> Result<String> {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    if name.is_empty() || name == "." || name == ".." || name.len() > MAX_NAME_LEN || name.chars().any(char::is_control) {
+        return Err(AppError::BadRequest(format!("Invalid file name: {:?}", name)));
+    }
+    Ok(name.to_string())
+}
+
+/// Sniffs magic bytes first and only falls back to the file extension, so a
+/// client cannot choose how its upload is served back.
+pub fn detect_content_type(name: &str, head: &[u8]) -> String {
+    infer::get(head)
+        .map(|kind| kind.mime_type().to_string())
+        .or_else(|| mime_guess::from_path(name).first().map(|mime| mime.essence_str().to_string()))
+        .unwrap_or_else(|| DEFAULT_CONTENT_TYPE.to_string())
+}
+
+/// Where the sandbox writes an execution's output files.
+fn outputs_prefix(execution_id: &str) -> Path {
+    Path::from_iter([EXECUTIONS_PREFIX, execution_id, OUTPUTS_DIR])
+}
+
+/// Streams one uploaded file into the store, enforcing the size limit as
+/// bytes arrive. At most one part is held in memory at a time.
+pub async fn upload<S, E>(state: &AppState, owner_id: &str, name: &str, chunks: S) -> Result<Artifact>
+where
+    S: Stream<Item = std::result::Result<Bytes, E>>,
+    E: std::fmt::Display,
+{
+    let mut chunks = std::pin::pin!(chunks);
+    let name = sanitize_name(name)?;
+    let id = Uuid::new_v4();
+    let storage_key = Path::from_iter([UPLOADS_PREFIX, &id.to_string(), &name]);
+    let max_bytes = state.config.artifact_max_file_bytes;
+
+    let mut writer = WriteMultipart::new(state.artifact_store.put_multipart(&storage_key).await?);
+    let mut size: u64 = 0;
+    let mut content_type = None;
+
+    let outcome: Result<()> = async {
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk.map_err(|e| AppError::BadRequest(format!("Upload interrupted: {}", e)))?;
+            size += chunk.len() as u64;
+            if size > max_bytes {
+                return Err(AppError::PayloadTooLarge(format!("{} exceeds {} bytes", name, max_bytes)));
+            }
+            if content_type.is_none() {
+                content_type = Some(detect_content_type(&name, &chunk));
+            }
+            writer.wait_for_capacity(1).await?;
+            writer.write(&chunk);
+        }
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = outcome {
+        let _ = writer.abort().await;
+        return Err(e);
+    }
+    writer.finish().await?;
+
+    Ok(sqlx::query_as::<_, Artifact>(&format!(
+        "INSERT INTO artifacts (id, owner_id, name, content_type, size_bytes, storage_key) \
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING {}",
+        SELECT_COLUMNS
+    ))
+    .bind(id)
+    .bind(owner_id)
+    .bind(&name)
+    .bind(content_type.unwrap_or_else(|| detect_content_type(&name, &[])))
+    .bind(size as i64)
+    .bind(storage_key.as_ref())
+    .fetch_one(&state.db)
+    .await?)
+}
+
+/// Claims the caller's unattached uploads for one submission, all or none.
+/// A claimed upload cannot be passed to another execution or swept.
+pub async fn claim(state: &AppState, owner_id: &str, ids: &[Uuid]) -> Result<Vec<Artifact>> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut tx = state.db.begin().await?;
+    let artifacts = sqlx::query_as::<_, Artifact>(&format!(
+        "UPDATE artifacts SET claimed_at = NOW() \
+         WHERE id = ANY($1) AND owner_id = $2 AND execution_id IS NULL AND claimed_at IS NULL RETURNING {}",
+        SELECT_COLUMNS
+    ))
+    .bind(ids)
+    .bind(owner_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    // Dropping the transaction rolls back the uploads that were claimed
+    if let Some(missing) = ids.iter().find(|id| !artifacts.iter().any(|artifact| artifact.id == **id)) {
+        return Err(AppError::BadRequest(format!("Unknown or already used input file: {}", missing)));
+    }
+    tx.commit().await?;
+    Ok(artifacts)
+}
+
+/// Hands claimed uploads back after the execution could not be started.
+pub async fn unclaim(state: &AppState, ids: &[Uuid]) -> Result<()> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query("UPDATE artifacts SET claimed_at = NULL WHERE id = ANY($1) AND execution_id IS NULL")
+        .bind(ids)
+        .execute(&state.db)
+        .await?;
+    Ok(())
+}
+
+pub async fn attach(state: &AppState, ids: &[Uuid], execution_id: &str) -> Result<()> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    let attached: Vec<Uuid> = sqlx::query_scalar(
+        "UPDATE artifacts SET execution_id = $2 WHERE id = ANY($1) AND execution_id IS NULL RETURNING id",
+    )
+    .bind(ids)
+    .bind(execution_id)
+    .fetch_all(&state.db)
+    .await?;
+    if attached.len() != ids.len() {
+        return Err(AppError::Conflict("Input files are already attached to another execution".to_string()));
+    }
+    Ok(())
+}
+
+/// Deletes up to `limit` uploads that were never attached and have been
+/// neither created nor claimed since `cutoff`. Returns their storage keys.
+pub async fn delete_expired_uploads(state: &AppState, cutoff: DateTime<Utc>, limit: i64) -> Result<Vec<String>> {
+    Ok(sqlx::query_scalar(
+        "DELETE FROM artifacts WHERE id IN ( \
+             SELECT id FROM artifacts WHERE execution_id IS NULL AND created_at < $1 \
+             AND (claimed_at IS NULL OR claimed_at < $1) LIMIT $2 FOR UPDATE SKIP LOCKED \
+         ) RETURNING storage_key",
+    )
+    .bind(cutoff)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+pub async fn delete_object(state: &AppState, storage_key: &str) -> Result<()> {
+    let location = Path::parse(storage_key).map_err(|_| AppError::InternalServerError)?;
+    match state.artifact_store.delete(&location).await {
+        Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Gives a re-run the same input files as its parent. The stored objects are
+/// shared; only the rows are copied.
+pub async fn copy_inputs(state: &AppState, from_execution_id: &str, to_execution_id: &str, owner_id: &str) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO artifacts (id, owner_id, execution_id, name, content_type, size_bytes, storage_key) \
+         SELECT gen_random_uuid(), $3, $2, name, content_type, size_bytes, storage_key \
+         FROM artifacts WHERE execution_id = $1",
+    )
+    .bind(from_execution_id)
+    .bind(to_execution_id)
+    .bind(owner_id)
+    .execute(&state.db)
+    .await?;
+    Ok(())
+}
+
+pub async fn inputs(state: &AppState, execution_id: &str) -> Result<Vec<Artifact>> {
+    Ok(sqlx::query_as::<_, Artifact>(&format!(
+        "SELECT {} FROM artifacts WHERE execution_id = $1 ORDER BY created_at",
+        SELECT_COLUMNS
+    ))
+    .bind(execution_id)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+pub async fn find_input(state: &AppState, execution_id: &str, id: Uuid) -> Result<Option<Artifact>> {
+    Ok(sqlx::query_as::<_, Artifact>(&format!(
+        "SELECT {} FROM artifacts WHERE id = $1 AND execution_id = $2",
+        SELECT_COLUMNS
+    ))
+    .bind(id)
+    .bind(execution_id)
+    .fetch_optional(&state.db)
+    .await?)
+}
+
+pub async fn outputs(state: &AppState, execution_id: &str) -> Result<Vec<OutputArtifact>> {
+    let prefix = outputs_prefix(execution_id);
+    let objects: Vec<_> = state.artifact_store.list(Some(&prefix)).try_collect().await?;
+
+    let mut outputs: Vec<OutputArtifact> = objects
+        .into_iter()
+        .filter_map(|meta| {
+            let path = meta
+                .location
+                .prefix_match(&prefix)?
+                .map(|part| part.as_ref().to_string())
+                .collect::<Vec<_>>()
+                .join("/");
+            Some(OutputArtifact {
+                content_type: detect_content_type(&path, &[]),
+                path,
+                size_bytes: meta.size as u64,
+                last_modified: meta.last_modified,
+            })
+        })
+        .collect();
+    outputs.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(outputs)
+}
+
+/// Resolves an output path below the execution's prefix, refusing anything
+/// that would escape it.
+pub fn output_location(execution_id: &str, path: &str) -> Result<Path> {
+    let prefix = outputs_prefix(execution_id);
+    let parts: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+    if parts.is_empty() || parts.iter().any(|part| *part == "." || *part == "..") {
+        return Err(AppError::NotFound);
+    }
+
+    let relative = Path::parse(parts.join("/")).map_err(|_| AppError::NotFound)?;
+    Ok(prefix.parts().chain(relative.parts()).collect())
+}
+
+/// Parses a single `bytes=` range against an object of `size` bytes.
+/// Multiple ranges are not supported and fall back to the whole object.
+pub fn parse_range(header: &str, size: u64) -> Result<Option<Range<u64>>> {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+
+    let unsatisfiable = || AppError::RangeNotSatisfiable { size };
+    let (start, end) = spec.split_once('-').ok_or_else(unsatisfiable)?;
+    let range = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().map_err(|_| unsatisfiable())?;
+            if suffix == 0 {
+                return Err(unsatisfiable());
+            }
+            size.saturating_sub(suffix)..size
+        }
+        (start, "") => start.parse().map_err(|_| unsatisfiable())?..size,
+        (start, end) => {
+            let start: u64 = start.parse().map_err(|_| unsatisfiable())?;
+            let end: u64 = end.parse().map_err(|_| unsatisfiable())?;
+            if end < start {
+                return Err(unsatisfiable());
+            }
+            start..(end + 1).min(size)
+        }
+    };
+
+    if range.start >= size {
+        return Err(unsatisfiable());
+    }
+    Ok(Some(range))
+}
+
+/// Opens an object for streaming, honouring an optional `Range` header.
+pub async fn open(state: &AppState, location: &Path, range_header: Option<&str>) -> Result<Download> {
+    let meta = state.artifact_store.head(location).await?;
+    let size = meta.size as u64;
+    let range = range_header.map(|header| parse_range(header, size)).transpose()?.flatten();
+
+    let options = GetOptions {
+        range: range
+            .clone()
+            .map(|range| GetRange::Bounded(range.start as usize..range.end as usize)),
+        ..Default::default()
+    };
+    let result = state.artifact_store.get_opts(location, options).await?;
+
+    Ok(Download {
+        size,
+        range,
+        stream: result.into_stream().boxed(),
+    })
+}
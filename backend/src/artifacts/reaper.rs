@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use tracing::{error, info, warn};
+
+use crate::{artifacts, AppState};
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(300);
+const BATCH_SIZE: i64 = 100;
+
+/// Deletes uploads that were never passed to an execution within
+/// `ARTIFACT_UPLOAD_TTL_SECS`, along with their stored objects. Every replica
+/// runs one; a row is only deleted once, so only one of them removes the
+/// object.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+    info!("Upload reaper started");
+
+    loop {
+        interval.tick().await;
+
+        let cutoff = Utc::now() - chrono::Duration::seconds(state.config.artifact_upload_ttl_secs as i64);
+        loop {
+            let expired = match artifacts::delete_expired_uploads(&state, cutoff, BATCH_SIZE).await {
+                Ok(expired) => expired,
+                Err(e) => {
+                    error!("Failed to delete expired uploads: {}", e);
+                    break;
+                }
+            };
+
+            for storage_key in &expired {
+                if let Err(e) = artifacts::delete_object(&state, storage_key).await {
+                    warn!("Failed to delete expired upload {}: {}", storage_key, e);
+                }
+            }
+            if (expired.len() as i64) < BATCH_SIZE {
+                break;
+            }
+        }
+    }
+}
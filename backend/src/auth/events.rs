@@ -0,0 +1,68 @@
+use chrono::Utc;
+use serde::Serialize;
+use tracing::{error, warn};
+
+use crate::AppState;
+
+const SECURITY_EVENTS_STREAM: &str = "security_events";
+const SECURITY_EVENTS_MAX_LEN: usize = 10_000;
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SecurityEvent {
+    LoginLockout {
+        scope: String,
+        identifier: String,
+        failures: u64,
+        lockout_secs: u64,
+        ip: String,
+    },
+    LoginUnlock {
+        email: Option<String>,
+        ip: Option<String>,
+        unlocked_by: String,
+    },
+    ImpersonationStarted {
+        session_id: String,
+        actor_id: String,
+        subject_id: String,
+        reason: String,
+        expires_at: String,
+    },
+    ImpersonationEnded {
+        session_id: String,
+        ended_by: String,
+    },
+}
+
+/// Logs the event and appends it to the `security_events` Redis stream.
+/// Failures are logged rather than returned so they never block the request.
+pub async fn emit(state: &AppState, event: SecurityEvent) {
+    let payload = match serde_json::to_string(&event) {
+        Ok(payload) => payload,
+        Err(e) => {
+            error!("Failed to serialize security event: {}", e);
+            return;
+        }
+    };
+
+    warn!(target: "security", event = %payload, "Security event");
+
+    let mut conn = state.redis_client.as_ref().clone();
+    let result = redis::cmd("XADD")
+        .arg(SECURITY_EVENTS_STREAM)
+        .arg("MAXLEN")
+        .arg("~")
+        .arg(SECURITY_EVENTS_MAX_LEN)
+        .arg("*")
+        .arg("event")
+        .arg(&payload)
+        .arg("timestamp")
+        .arg(Utc::now().to_rfc3339())
+        .query_async::<_, String>(&mut conn)
+        .await;
+
+    if let Err(e) = result {
+        error!("Failed to record security event: {}", e);
+    }
+}
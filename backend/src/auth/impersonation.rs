@@ -0,0 +1,369 @@
+use axum::http::Method;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        events::{self, SecurityEvent},
+        generate_impersonation_token, Actor, Claims,
+    },
+    clients::iam::User,
+    error::{AppError, Result},
+    AppState,
+};
+
+/// Header an impersonating admin must send to go ahead with a destructive action.
+pub const CONFIRM_HEADER: &str = "x-impersonation-confirm";
+
+/// What the impersonation guard does with a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Guard {
+    Allow,
+    Confirm,
+    Block,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Outcome {
+    Allowed,
+    Confirmed,
+    Blocked,
+    Unconfirmed,
+}
+
+impl Outcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Allowed => "allowed",
+            Outcome::Confirmed => "confirmed",
+            Outcome::Blocked => "blocked",
+            Outcome::Unconfirmed => "unconfirmed",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Grant {
+    pub session_id: String,
+    pub access_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub method: String,
+    pub path: String,
+    pub status: i16,
+    pub outcome: String,
+    pub created_at: DateTime<Utc>,
+}
+
+fn grant_key(session_id: &str) -> String {
+    format!("impersonation:{}", session_id)
+}
+
+/// Credentials, security settings, secrets and webhooks (which send data to
+/// any URL) are never touched while impersonating. Deletes, edits,
+/// cancellations and changes to workspace configuration or schedules, which
+/// outlast the impersonation, need explicit confirmation.
+pub fn classify(method: &Method, path: &str) -> Guard {
+    if *method == Method::GET || *method == Method::HEAD || *method == Method::OPTIONS {
+        return Guard::Allow;
+    }
+
+    let segments: Vec<&str> = path.split('/').collect();
+    let touches = |segment: &str| segments.contains(&segment);
+    if path.starts_with("/api/auth/mfa") || touches("service-accounts") || touches("secrets") || touches("webhooks") {
+        return Guard::Block;
+    }
+
+    if *method == Method::DELETE || *method == Method::PUT || *method == Method::PATCH || path.ends_with("/cancel") {
+        return Guard::Confirm;
+    }
+    if *method == Method::POST && (path.starts_with("/api/workspaces") || path.starts_with("/api/schedules")) {
+        return Guard::Confirm;
+    }
+
+    Guard::Allow
+}
+
+pub async fn start(
+    state: &AppState,
+    actor: &Claims,
+    subject: &User,
+    reason: &str,
+    duration: Duration,
+) -> Result<Grant> {
+    let session_id = Uuid::new_v4().to_string();
+    let expires_at = Utc::now() + duration;
+
+    let access_token = generate_impersonation_token(
+        &subject.id,
+        &subject.email,
+        &subject.role,
+        Actor {
+            sub: actor.sub.clone(),
+            email: actor.email.clone(),
+        },
+        &session_id,
+        &state.config.jwt_secret,
+        duration.num_seconds(),
+    )?;
+
+    sqlx::query(
+        "INSERT INTO impersonation_sessions \
+         (id, actor_id, actor_email, subject_id, subject_email, reason, expires_at) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(&session_id)
+    .bind(&actor.sub)
+    .bind(&actor.email)
+    .bind(&subject.id)
+    .bind(&subject.email)
+    .bind(reason)
+    .bind(expires_at)
+    .execute(&state.db)
+    .await?;
+
+    let mut conn = state.redis_client.as_ref().clone();
+    redis::cmd("SETEX")
+        .arg(grant_key(&session_id))
+        .arg(duration.num_seconds())
+        .arg(&actor.sub)
+        .query_async::<_, ()>(&mut conn)
+        .await?;
+
+    events::emit(
+        state,
+        SecurityEvent::ImpersonationStarted {
+            session_id: session_id.clone(),
+            actor_id: actor.sub.clone(),
+            subject_id: subject.id.clone(),
+            reason: reason.to_string(),
+            expires_at: expires_at.to_rfc3339(),
+        },
+    )
+    .await;
+
+    Ok(Grant {
+        session_id,
+        access_token,
+        expires_at,
+    })
+}
+
+/// An impersonation token is only honoured while its grant exists, so ending
+/// a session revokes the token before it expires.
+pub async fn is_active(state: &AppState, session_id: &str) -> Result<bool> {
+    let mut conn = state.redis_client.as_ref().clone();
+    Ok(redis::cmd("EXISTS")
+        .arg(grant_key(session_id))
+        .query_async(&mut conn)
+        .await?)
+}
+
+pub async fn end(state: &AppState, session_id: &str, ended_by: &str) -> Result<()> {
+    let updated = sqlx::query(
+        "UPDATE impersonation_sessions SET ended_at = NOW() WHERE id = $1 AND ended_at IS NULL",
+    )
+    .bind(session_id)
+    .execute(&state.db)
+    .await?;
+
+    if updated.rows_affected() == 0 {
+        return Err(AppError::NotFound);
+    }
+
+    let mut conn = state.redis_client.as_ref().clone();
+    redis::cmd("DEL")
+        .arg(grant_key(session_id))
+        .query_async::<_, ()>(&mut conn)
+        .await?;
+
+    events::emit(
+        state,
+        SecurityEvent::ImpersonationEnded {
+            session_id: session_id.to_string(),
+            ended_by: ended_by.to_string(),
+        },
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Writes one audit row. Failures are logged; the response has already been
+/// produced by the time this runs.
+pub async fn record(state: &AppState, claims: &Claims, method: &Method, path: &str, status: u16, outcome: Outcome) {
+    let Some(actor) = claims.act.as_ref() else {
+        return;
+    };
+
+    let result = sqlx::query(
+        "INSERT INTO impersonation_audit_log \
+         (session_id, actor_id, subject_id, method, path, status, outcome) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(&claims.jti)
+    .bind(&actor.sub)
+    .bind(&claims.sub)
+    .bind(method.as_str())
+    .bind(path)
+    .bind(status as i16)
+    .bind(outcome.as_str())
+    .execute(&state.db)
+    .await;
+
+    if let Err(e) = result {
+        error!("Failed to write impersonation audit entry for {}: {}", claims.jti, e);
+    }
+}
+
+pub async fn audit_log(state: &AppState, session_id: &str) -> Result<Vec<AuditEntry>> {
+    Ok(sqlx::query_as::<_, AuditEntry>(
+        "SELECT id, method, path, status, outcome, created_at FROM impersonation_audit_log \
+         WHERE session_id = $1 ORDER BY created_at, id",
+    )
+    .bind(session_id)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every route the router in main.rs serves under `/api`, read from its
+    /// source so a new route cannot be left out of the table below.
+    fn routes() -> Vec<(Method, String)> {
+        let main = include_str!("../main.rs");
+        let mut routes = Vec::new();
+        for nest in main.split(".nest(\"").skip(1) {
+            let (prefix, rest) = nest.split_once("\", ").unwrap();
+            let builder = rest.split('(').next().unwrap();
+            let body = main.split(&format!("fn {}(", builder)).nth(1).unwrap();
+            let body = &body[..body.find("\n}").unwrap()];
+
+            for route in body.split(".route(").skip(1) {
+                let path = route.split('"').nth(1).unwrap();
+                let path = if path == "/" { prefix.to_string() } else { format!("{}{}", prefix, path) };
+                let handlers = route.split(".layer(").next().unwrap();
+                for method in [Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE] {
+                    let call = format!("{}(", method.as_str().to_lowercase());
+                    let calls = handlers.match_indices(&call).filter(|(index, _)| {
+                        !handlers[..*index].ends_with(|c: char| c.is_alphanumeric() || c == '_')
+                    });
+                    for _ in calls {
+                        routes.push((method.clone(), path.clone()));
+                    }
+                }
+            }
+        }
+        routes
+    }
+
+    #[test]
+    fn classifies_every_route() {
+        use Guard::*;
+        let expected = [
+            ("POST", "/api/auth/logout", Allow),
+            ("GET", "/api/auth/sessions", Allow),
+            ("DELETE", "/api/auth/sessions/:id", Confirm),
+            ("POST", "/api/auth/mfa/enroll", Block),
+            ("POST", "/api/auth/mfa/enroll/confirm", Block),
+            ("POST", "/api/auth/mfa/recovery-codes", Block),
+            ("POST", "/api/auth/mfa/disable", Block),
+            ("POST", "/api/auth/login", Allow),
+            ("POST", "/api/auth/refresh", Allow),
+            ("GET", "/api/auth/me", Allow),
+            ("POST", "/api/auth/mfa/verify", Block),
+            ("POST", "/api/oauth/token", Allow),
+            ("POST", "/api/admin/unlock", Allow),
+            ("POST", "/api/admin/impersonate", Allow),
+            ("DELETE", "/api/admin/impersonations/:session_id", Confirm),
+            ("GET", "/api/admin/impersonations/:session_id/audit", Allow),
+            ("GET", "/api/executions", Allow),
+            ("POST", "/api/executions", Allow),
+            ("GET", "/api/executions/:id", Allow),
+            ("GET", "/api/executions/:id/logs", Allow),
+            ("GET", "/api/executions/:id/logs/export", Allow),
+            ("POST", "/api/executions/:id/cancel", Confirm),
+            ("POST", "/api/executions/:id/rerun", Allow),
+            ("GET", "/api/executions/:id/lineage", Allow),
+            ("GET", "/api/executions/:id/artifacts", Allow),
+            ("GET", "/api/executions/:id/artifacts/inputs/:artifact_id", Allow),
+            ("GET", "/api/executions/:id/artifacts/outputs/*path", Allow),
+            ("GET", "/api/executions/:id/webhooks", Allow),
+            ("POST", "/api/executions/:id/webhooks", Block),
+            ("POST", "/api/artifacts", Allow),
+            ("GET", "/api/notebooks", Allow),
+            ("POST", "/api/notebooks", Allow),
+            ("GET", "/api/notebooks/:id", Allow),
+            ("PATCH", "/api/notebooks/:id", Confirm),
+            ("DELETE", "/api/notebooks/:id", Confirm),
+            ("POST", "/api/notebooks/:id/cells", Allow),
+            ("PATCH", "/api/notebooks/:id/cells/:cell_id", Confirm),
+            ("DELETE", "/api/notebooks/:id/cells/:cell_id", Confirm),
+            ("POST", "/api/notebooks/:id/cells/:cell_id/run", Allow),
+            ("POST", "/api/notebooks/:id/restart", Allow),
+            ("DELETE", "/api/notebooks/:id/session", Confirm),
+            ("GET", "/api/schedules", Allow),
+            ("POST", "/api/schedules", Confirm),
+            ("GET", "/api/schedules/:id", Allow),
+            ("PATCH", "/api/schedules/:id", Confirm),
+            ("DELETE", "/api/schedules/:id", Confirm),
+            ("POST", "/api/schedules/:id/pause", Confirm),
+            ("POST", "/api/schedules/:id/resume", Confirm),
+            ("GET", "/api/schedules/:id/runs", Allow),
+            ("GET", "/api/logs/search", Allow),
+            ("GET", "/api/notifications", Allow),
+            ("POST", "/api/notifications/:id/read", Allow),
+            ("GET", "/api/usage", Allow),
+            ("DELETE", "/api/webhooks/:id", Block),
+            ("GET", "/api/webhooks/:id/deliveries", Allow),
+            ("GET", "/api/webhooks/:id/deliveries/:delivery_id", Allow),
+            ("POST", "/api/webhooks/:id/deliveries/:delivery_id/redeliver", Block),
+            ("POST", "/api/memory/search", Allow),
+            ("POST", "/api/memory/query", Allow),
+            ("POST", "/api/memory/store", Allow),
+            ("GET", "/api/workspaces", Allow),
+            ("POST", "/api/workspaces", Confirm),
+            ("GET", "/api/workspaces/:id", Allow),
+            ("PUT", "/api/workspaces/:id", Confirm),
+            ("DELETE", "/api/workspaces/:id", Confirm),
+            ("GET", "/api/workspaces/:id/execution-policy", Allow),
+            ("PUT", "/api/workspaces/:id/execution-policy", Confirm),
+            ("GET", "/api/workspaces/:id/webhooks", Allow),
+            ("POST", "/api/workspaces/:id/webhooks", Block),
+            ("GET", "/api/workspaces/:id/secrets", Allow),
+            ("PUT", "/api/workspaces/:id/secrets/:name", Block),
+            ("DELETE", "/api/workspaces/:id/secrets/:name", Block),
+            ("GET", "/api/workspaces/:id/redaction-rules", Allow),
+            ("POST", "/api/workspaces/:id/redaction-rules", Confirm),
+            ("DELETE", "/api/workspaces/:id/redaction-rules/:rule_id", Confirm),
+            ("GET", "/api/workspaces/:id/alert-rules", Allow),
+            ("POST", "/api/workspaces/:id/alert-rules", Confirm),
+            ("PATCH", "/api/workspaces/:id/alert-rules/:rule_id", Confirm),
+            ("DELETE", "/api/workspaces/:id/alert-rules/:rule_id", Confirm),
+            ("GET", "/api/workspaces/:id/service-accounts", Allow),
+            ("POST", "/api/workspaces/:id/service-accounts", Block),
+            ("DELETE", "/api/workspaces/:id/service-accounts/:account_id", Block),
+            ("POST", "/api/workspaces/:id/service-accounts/:account_id/rotate-secret", Block),
+            ("POST", "/api/ws/ticket", Allow),
+        ];
+
+        let routes = routes();
+        assert_eq!(routes.len(), expected.len(), "every route needs an expected guard");
+        for (method, path) in routes {
+            let guard = expected
+                .iter()
+                .find(|(m, p, _)| *m == method.as_str() && *p == path)
+                .map(|(_, _, guard)| *guard)
+                .unwrap_or_else(|| panic!("no expected guard for {} {}", method, path));
+            assert_eq!(classify(&method, &path), guard, "{} {}", method, path);
+        }
+    }
+}
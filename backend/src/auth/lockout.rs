@@ -0,0 +1,199 @@
+use redis::Script;
+
+use crate::{
+    auth::events::{self, SecurityEvent},
+    error::{AppError, Result},
+    AppState,
+};
+
+// Counts a failed attempt and either arms the progressive delay or, once the
+// threshold is reached, replaces the counter with a lockout key.
+//
+// KEYS: failures, lockout, delay
+// ARGV: window_secs, max_attempts, lockout_secs, then the `delay_schedule`
+const RECORD_FAILURE_SCRIPT: &str = r#"
+local failures = redis.call('INCR', KEYS[1])
+if failures == 1 then
+    redis.call('EXPIRE', KEYS[1], ARGV[1])
+end
+if failures >= tonumber(ARGV[2]) then
+    redis.call('SET', KEYS[2], failures, 'EX', ARGV[3])
+    redis.call('DEL', KEYS[1], KEYS[3])
+    return {failures, 1}
+end
+local delay = tonumber(ARGV[3 + math.min(failures, #ARGV - 3)])
+if delay > 0 then
+    redis.call('SET', KEYS[3], 1, 'PX', delay)
+end
+return {failures, 0}
+"#;
+
+/// Milliseconds a login waits after its `failures`th failure: the base
+/// doubled per earlier failure, capped at `max_ms`.
+fn delay_ms(base_ms: u64, max_ms: u64, failures: u32) -> u64 {
+    let exponent = failures.saturating_sub(1).min(63);
+    base_ms.saturating_mul(1 << exponent).min(max_ms)
+}
+
+/// The delay after each failure in turn, up to the first one at the cap;
+/// later failures reuse the last entry.
+fn delay_schedule(base_ms: u64, max_ms: u64) -> Vec<u64> {
+    let mut schedule = Vec::new();
+    for failures in 1..=64 {
+        let delay = delay_ms(base_ms, max_ms, failures);
+        schedule.push(delay);
+        if delay >= max_ms || delay == 0 {
+            break;
+        }
+    }
+    schedule
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Scope {
+    Email,
+    Ip,
+}
+
+impl Scope {
+    fn as_str(self) -> &'static str {
+        match self {
+            Scope::Email => "email",
+            Scope::Ip => "ip",
+        }
+    }
+}
+
+fn failures_key(scope: Scope, identifier: &str) -> String {
+    format!("login_failures:{}:{}", scope.as_str(), identifier)
+}
+
+fn lockout_key(scope: Scope, identifier: &str) -> String {
+    format!("login_lockout:{}:{}", scope.as_str(), identifier)
+}
+
+fn delay_key(scope: Scope, identifier: &str) -> String {
+    format!("login_delay:{}:{}", scope.as_str(), identifier)
+}
+
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Rejects the attempt if either the email or the IP is locked out or still
+/// inside its progressive delay.
+pub async fn check(state: &AppState, email: &str, ip: &str) -> Result<()> {
+    let mut conn = state.redis_client.as_ref().clone();
+    let ttls: Vec<i64> = redis::pipe()
+        .cmd("PTTL")
+        .arg(lockout_key(Scope::Email, email))
+        .cmd("PTTL")
+        .arg(delay_key(Scope::Email, email))
+        .cmd("PTTL")
+        .arg(lockout_key(Scope::Ip, ip))
+        .cmd("PTTL")
+        .arg(delay_key(Scope::Ip, ip))
+        .query_async(&mut conn)
+        .await?;
+
+    match ttls.into_iter().max() {
+        Some(ms) if ms > 0 => Err(AppError::TooManyRequests {
+            retry_after: (ms as u64).div_ceil(1000),
+        }),
+        _ => Ok(()),
+    }
+}
+
+pub async fn record_failure(state: &AppState, email: &str, ip: &str) -> Result<()> {
+    let config = &state.config;
+    let scopes = [
+        (Scope::Email, email, config.login_max_attempts_per_email),
+        (Scope::Ip, ip, config.login_max_attempts_per_ip),
+    ];
+
+    let delays = delay_schedule(config.login_delay_base_ms, config.login_delay_max_ms);
+
+    let mut conn = state.redis_client.as_ref().clone();
+    for (scope, identifier, max_attempts) in scopes {
+        let (failures, locked): (u64, u8) = Script::new(RECORD_FAILURE_SCRIPT)
+            .key(failures_key(scope, identifier))
+            .key(lockout_key(scope, identifier))
+            .key(delay_key(scope, identifier))
+            .arg(config.login_attempt_window_secs)
+            .arg(max_attempts)
+            .arg(config.login_lockout_secs)
+            .arg(&delays)
+            .invoke_async(&mut conn)
+            .await?;
+
+        if locked == 1 {
+            events::emit(
+                state,
+                SecurityEvent::LoginLockout {
+                    scope: scope.as_str().to_string(),
+                    identifier: identifier.to_string(),
+                    failures,
+                    lockout_secs: config.login_lockout_secs,
+                    ip: ip.to_string(),
+                },
+            )
+            .await;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn reset(state: &AppState, email: &str, ip: &str) -> Result<()> {
+    let mut conn = state.redis_client.as_ref().clone();
+    redis::cmd("DEL")
+        .arg(failures_key(Scope::Email, email))
+        .arg(delay_key(Scope::Email, email))
+        .arg(failures_key(Scope::Ip, ip))
+        .arg(delay_key(Scope::Ip, ip))
+        .query_async::<_, ()>(&mut conn)
+        .await?;
+    Ok(())
+}
+
+/// Clears counters, delays and lockouts. Returns the number of keys removed.
+pub async fn unlock(state: &AppState, email: Option<&str>, ip: Option<&str>) -> Result<u64> {
+    let mut cmd = redis::cmd("DEL");
+    for (scope, identifier) in [(Scope::Email, email), (Scope::Ip, ip)] {
+        if let Some(identifier) = identifier {
+            cmd.arg(failures_key(scope, identifier))
+                .arg(lockout_key(scope, identifier))
+                .arg(delay_key(scope, identifier));
+        }
+    }
+
+    let mut conn = state.redis_client.as_ref().clone();
+    Ok(cmd.query_async(&mut conn).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_double_up_to_the_cap() {
+        let delays: Vec<u64> = (1..=6).map(|failures| delay_ms(250, 3000, failures)).collect();
+        assert_eq!(delays, vec![250, 500, 1000, 2000, 3000, 3000]);
+        assert_eq!(delay_ms(250, 3000, 0), 250);
+        assert_eq!(delay_ms(250, 3000, u32::MAX), 3000);
+        assert_eq!(delay_ms(u64::MAX, u64::MAX, 40), u64::MAX);
+    }
+
+    #[test]
+    fn schedule_stops_at_the_cap() {
+        assert_eq!(delay_schedule(250, 3000), vec![250, 500, 1000, 2000, 3000]);
+        assert_eq!(delay_schedule(1000, 1000), vec![1000]);
+        assert_eq!(delay_schedule(500, 100), vec![100]);
+    }
+
+    #[test]
+    fn zero_disables_the_delay() {
+        assert_eq!(delay_schedule(0, 3000), vec![0]);
+        assert_eq!(delay_schedule(250, 0), vec![0]);
+    }
+}
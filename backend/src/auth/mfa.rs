@@ -0,0 +1,413 @@
+use chrono::Utc;
+use serde::{de::DeserializeOwned, Serialize};
+use totp_rs::{Algorithm, Secret, TOTP};
+use uuid::Uuid;
+
+use crate::{
+    auth::{hash_password, random_string, verify_password},
+    error::{AppError, Result},
+    secrets, AppState,
+};
+
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP: u64 = 30;
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_LEN: usize = 10;
+const CHALLENGE_TOKEN_LEN: usize = 48;
+/// Wrong codes a challenge survives; the next one invalidates it.
+const MAX_CHALLENGE_FAILURES: u64 = 5;
+
+// Counts a wrong code against a challenge and deletes the challenge once the
+// limit is reached.
+//
+// KEYS: challenge, failures
+// ARGV: max_failures, ttl_secs
+const CHALLENGE_FAILURE_SCRIPT: &str = r#"
+local failures = redis.call('INCR', KEYS[2])
+if failures == 1 then
+    redis.call('EXPIRE', KEYS[2], ARGV[2])
+end
+if failures >= tonumber(ARGV[1]) then
+    redis.call('DEL', KEYS[1], KEYS[2])
+end
+return failures
+"#;
+
+#[derive(Debug)]
+pub struct Enrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct MfaRecord {
+    /// Sealed under the secrets master key, unless `secret_sealed` is false
+    secret: String,
+    secret_sealed: bool,
+    enabled: bool,
+    last_used_step: Option<i64>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct RecoveryCodeRecord {
+    id: Uuid,
+    code_hash: String,
+}
+
+fn generate_secret() -> String {
+    match Secret::generate_secret().to_encoded() {
+        Secret::Encoded(secret) => secret,
+        Secret::Raw(_) => unreachable!("to_encoded always returns an encoded secret"),
+    }
+}
+
+fn sealing_context(user_id: &str) -> String {
+    format!("mfa-totp/{}", user_id)
+}
+
+/// TOTP secrets are never stored in the clear, so MFA is unavailable until
+/// `SECRETS_MASTER_KEY` is configured.
+fn seal_secret(state: &AppState, user_id: &str, secret: &str) -> Result<String> {
+    secrets::seal(state, &sealing_context(user_id), secret)?.ok_or(AppError::ServiceUnavailable)
+}
+
+/// The plain secret of `record`. A secret stored before sealing is sealed
+/// now; without the master key it is not used at all.
+async fn open_secret(state: &AppState, user_id: &str, record: &MfaRecord) -> Result<String> {
+    if record.secret_sealed {
+        return secrets::unseal(state, &sealing_context(user_id), &record.secret);
+    }
+
+    let sealed = seal_secret(state, user_id, &record.secret)?;
+    sqlx::query(
+        "UPDATE user_mfa SET secret = $2, secret_sealed = TRUE \
+         WHERE user_id = $1 AND secret = $3 AND NOT secret_sealed",
+    )
+    .bind(user_id)
+    .bind(sealed)
+    .bind(&record.secret)
+    .execute(&state.db)
+    .await?;
+    Ok(record.secret.clone())
+}
+
+fn build_totp(state: &AppState, secret: &str, account: &str) -> Result<TOTP> {
+    let bytes = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|_| AppError::InternalServerError)?;
+
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        0,
+        TOTP_STEP,
+        bytes,
+        Some(state.config.mfa_issuer.clone()),
+        account.to_string(),
+    )
+    .map_err(|_| AppError::InternalServerError)
+}
+
+/// Matches `code` against the previous, current and next time step, skipping
+/// steps at or before the last accepted one so a code cannot be replayed.
+fn matching_step(totp: &TOTP, code: &str, last_used_step: Option<i64>) -> Option<i64> {
+    let current = Utc::now().timestamp() as u64 / TOTP_STEP;
+    (current.saturating_sub(1)..=current + 1)
+        .filter(|step| last_used_step.is_none_or(|last| *step as i64 > last))
+        .find(|step| totp.check(code, step * TOTP_STEP))
+        .map(|step| step as i64)
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+async fn fetch_record(state: &AppState, user_id: &str) -> Result<Option<MfaRecord>> {
+    Ok(sqlx::query_as::<_, MfaRecord>(
+        "SELECT secret, secret_sealed, enabled, last_used_step FROM user_mfa WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(&state.db)
+    .await?)
+}
+
+async fn fetch_enabled_record(state: &AppState, user_id: &str) -> Result<MfaRecord> {
+    match fetch_record(state, user_id).await? {
+        Some(record) if record.enabled => Ok(record),
+        _ => Err(AppError::BadRequest("MFA is not enabled".to_string())),
+    }
+}
+
+/// Accepts a TOTP code and records its time step. Returns false on mismatch
+/// or if a concurrent request already consumed the same step.
+async fn accept_totp(state: &AppState, user_id: &str, email: &str, record: &MfaRecord, code: &str) -> Result<bool> {
+    let secret = open_secret(state, user_id, record).await?;
+    let totp = build_totp(state, &secret, email)?;
+    let Some(step) = matching_step(&totp, code.trim(), record.last_used_step) else {
+        return Ok(false);
+    };
+
+    let updated = sqlx::query(
+        "UPDATE user_mfa SET last_used_step = $2 \
+         WHERE user_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)",
+    )
+    .bind(user_id)
+    .bind(step)
+    .execute(&state.db)
+    .await?;
+
+    Ok(updated.rows_affected() == 1)
+}
+
+async fn accept_recovery_code(state: &AppState, user_id: &str, code: &str) -> Result<bool> {
+    let code = normalize_recovery_code(code);
+    if code.len() != RECOVERY_CODE_LEN {
+        return Ok(false);
+    }
+
+    let candidates = sqlx::query_as::<_, RecoveryCodeRecord>(
+        "SELECT id, code_hash FROM user_mfa_recovery_codes WHERE user_id = $1 AND used_at IS NULL",
+    )
+    .bind(user_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    for candidate in candidates {
+        if verify_password(&code, &candidate.code_hash)? {
+            let updated = sqlx::query(
+                "UPDATE user_mfa_recovery_codes SET used_at = NOW() WHERE id = $1 AND used_at IS NULL",
+            )
+            .bind(candidate.id)
+            .execute(&state.db)
+            .await?;
+            return Ok(updated.rows_affected() == 1);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Replaces all recovery codes for the user and returns the new plaintext codes.
+async fn replace_recovery_codes(state: &AppState, user_id: &str) -> Result<Vec<String>> {
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| random_string(RECOVERY_CODE_LEN).to_lowercase())
+        .collect();
+
+    let mut tx = state.db.begin().await?;
+    sqlx::query("DELETE FROM user_mfa_recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    for code in &codes {
+        sqlx::query("INSERT INTO user_mfa_recovery_codes (id, user_id, code_hash) VALUES ($1, $2, $3)")
+            .bind(Uuid::new_v4())
+            .bind(user_id)
+            .bind(hash_password(code)?)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(codes
+        .into_iter()
+        .map(|code| format!("{}-{}", &code[..5], &code[5..]))
+        .collect())
+}
+
+pub async fn is_enabled(state: &AppState, user_id: &str) -> Result<bool> {
+    Ok(fetch_record(state, user_id)
+        .await?
+        .is_some_and(|record| record.enabled))
+}
+
+/// Starts (or restarts) a pending enrolment with a fresh secret.
+pub async fn begin_enrollment(state: &AppState, user_id: &str, email: &str) -> Result<Enrollment> {
+    let secret = generate_secret();
+    let totp = build_totp(state, &secret, email)?;
+    let sealed = seal_secret(state, user_id, &secret)?;
+
+    let updated = sqlx::query(
+        "INSERT INTO user_mfa (user_id, secret, secret_sealed) VALUES ($1, $2, TRUE) \
+         ON CONFLICT (user_id) DO UPDATE \
+         SET secret = EXCLUDED.secret, secret_sealed = TRUE, last_used_step = NULL, created_at = NOW() \
+         WHERE user_mfa.enabled = FALSE",
+    )
+    .bind(user_id)
+    .bind(&sealed)
+    .execute(&state.db)
+    .await?;
+
+    if updated.rows_affected() == 0 {
+        return Err(AppError::BadRequest("MFA is already enabled".to_string()));
+    }
+
+    Ok(Enrollment {
+        otpauth_uri: totp.get_url(),
+        secret,
+    })
+}
+
+/// Enables MFA once the user proves possession of the pending secret.
+/// Returns the initial set of recovery codes.
+pub async fn confirm_enrollment(state: &AppState, user_id: &str, email: &str, code: &str) -> Result<Vec<String>> {
+    let record = match fetch_record(state, user_id).await? {
+        Some(record) if !record.enabled => record,
+        Some(_) => return Err(AppError::BadRequest("MFA is already enabled".to_string())),
+        None => return Err(AppError::BadRequest("No pending MFA enrolment".to_string())),
+    };
+
+    if !accept_totp(state, user_id, email, &record, code).await? {
+        return Err(AppError::AuthenticationError);
+    }
+
+    sqlx::query("UPDATE user_mfa SET enabled = TRUE, enabled_at = NOW() WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&state.db)
+        .await?;
+
+    replace_recovery_codes(state, user_id).await
+}
+
+/// Verifies either a TOTP code or an unused recovery code.
+pub async fn verify(state: &AppState, user_id: &str, email: &str, code: &str) -> Result<bool> {
+    let record = fetch_enabled_record(state, user_id).await?;
+
+    let trimmed = code.trim();
+    if trimmed.len() == TOTP_DIGITS && trimmed.chars().all(|c| c.is_ascii_digit()) {
+        return accept_totp(state, user_id, email, &record, trimmed).await;
+    }
+
+    accept_recovery_code(state, user_id, code).await
+}
+
+pub async fn regenerate_recovery_codes(state: &AppState, user_id: &str, email: &str, code: &str) -> Result<Vec<String>> {
+    let record = fetch_enabled_record(state, user_id).await?;
+    if !accept_totp(state, user_id, email, &record, code).await? {
+        return Err(AppError::AuthenticationError);
+    }
+
+    replace_recovery_codes(state, user_id).await
+}
+
+pub async fn disable(state: &AppState, user_id: &str, email: &str, code: &str) -> Result<()> {
+    if !verify(state, user_id, email, code).await? {
+        return Err(AppError::AuthenticationError);
+    }
+
+    sqlx::query("DELETE FROM user_mfa WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&state.db)
+        .await?;
+    Ok(())
+}
+
+fn challenge_key(token: &str) -> String {
+    format!("mfa_challenge:{}", token)
+}
+
+fn challenge_failures_key(token: &str) -> String {
+    format!("mfa_challenge_failures:{}", token)
+}
+
+/// Stores `payload` behind a short-lived challenge token handed out after the
+/// password check succeeds.
+pub async fn create_challenge<T: Serialize>(state: &AppState, payload: &T) -> Result<String> {
+    let token = random_string(CHALLENGE_TOKEN_LEN);
+    let data = serde_json::to_string(payload).map_err(|_| AppError::InternalServerError)?;
+
+    let mut conn = state.redis_client.as_ref().clone();
+    redis::cmd("SETEX")
+        .arg(challenge_key(&token))
+        .arg(state.config.mfa_challenge_ttl_secs)
+        .arg(data)
+        .query_async::<_, ()>(&mut conn)
+        .await?;
+
+    Ok(token)
+}
+
+pub async fn load_challenge<T: DeserializeOwned>(state: &AppState, token: &str) -> Result<Option<T>> {
+    let mut conn = state.redis_client.as_ref().clone();
+    let data: Option<String> = redis::cmd("GET")
+        .arg(challenge_key(token))
+        .query_async(&mut conn)
+        .await?;
+
+    Ok(data.and_then(|data| serde_json::from_str(&data).ok()))
+}
+
+/// Records a wrong code. After `MAX_CHALLENGE_FAILURES` the challenge is
+/// gone and the user has to log in again.
+pub async fn record_challenge_failure(state: &AppState, token: &str) -> Result<()> {
+    let mut conn = state.redis_client.as_ref().clone();
+    redis::Script::new(CHALLENGE_FAILURE_SCRIPT)
+        .key(challenge_key(token))
+        .key(challenge_failures_key(token))
+        .arg(MAX_CHALLENGE_FAILURES)
+        .arg(state.config.mfa_challenge_ttl_secs)
+        .invoke_async::<_, u64>(&mut conn)
+        .await?;
+    Ok(())
+}
+
+/// Deletes the challenge. Returns false if it was already consumed.
+pub async fn consume_challenge(state: &AppState, token: &str) -> Result<bool> {
+    let mut conn = state.redis_client.as_ref().clone();
+    let (deleted, _): (u64, u64) = redis::pipe()
+        .cmd("DEL")
+        .arg(challenge_key(token))
+        .cmd("DEL")
+        .arg(challenge_failures_key(token))
+        .query_async(&mut conn)
+        .await?;
+
+    Ok(deleted == 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn totp() -> TOTP {
+        let bytes = Secret::Encoded(generate_secret()).to_bytes().unwrap();
+        TOTP::new(Algorithm::SHA1, TOTP_DIGITS, 0, TOTP_STEP, bytes, None, "user@example.com".to_string()).unwrap()
+    }
+
+    fn current_step() -> u64 {
+        Utc::now().timestamp() as u64 / TOTP_STEP
+    }
+
+    #[test]
+    fn accepts_codes_from_adjacent_steps() {
+        let totp = totp();
+        let step = current_step();
+        for candidate in [step - 1, step, step + 1] {
+            let code = totp.generate(candidate * TOTP_STEP);
+            assert!(matching_step(&totp, &code, None).is_some());
+        }
+    }
+
+    #[test]
+    fn rejects_replayed_steps() {
+        let totp = totp();
+        let step = current_step();
+        let code = totp.generate(step * TOTP_STEP);
+        assert_eq!(matching_step(&totp, &code, None), Some(step as i64));
+        assert_eq!(matching_step(&totp, &code, Some(step as i64)), None);
+    }
+
+    #[test]
+    fn normalizes_recovery_codes() {
+        assert_eq!(normalize_recovery_code(" AbCdE-12345 "), "abcde12345");
+    }
+
+    #[test]
+    fn generates_distinct_decodable_secrets() {
+        let a = generate_secret();
+        assert_ne!(a, generate_secret());
+        assert!(Secret::Encoded(a).to_bytes().is_ok());
+    }
+}
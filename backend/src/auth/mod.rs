@@ -1,11 +1,28 @@
 use chrono::{Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use rand::{distributions::Alphanumeric, Rng};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::error::Result;
 
-#[derive(Debug, Serialize, Deserialize)]
+pub mod events;
+pub mod impersonation;
+pub mod lockout;
+pub mod mfa;
+pub mod scopes;
+pub mod service_accounts;
+pub mod session;
+pub mod validation;
+pub mod workspace;
+
+/// Role carried by tokens minted for service accounts.
+pub const SERVICE_ROLE: &str = "service";
+
+/// Lifetime of refresh tokens, and therefore of an idle device session.
+pub const REFRESH_TOKEN_DAYS: i64 = 30;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,        // User ID
     pub email: String,
@@ -13,6 +30,40 @@ pub struct Claims {
     pub exp: i64,          // Expiration time
     pub iat: i64,          // Issued at
     pub jti: String,       // JWT ID for revocation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,          // Device session (user tokens)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,        // Space-delimited OAuth2 scopes (service accounts)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace_id: Option<String>, // Owning workspace (service accounts)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,           // Admin acting as `sub` (impersonation)
+}
+
+/// The party actually making requests with an impersonation token (RFC 8693).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Actor {
+    pub sub: String,
+    pub email: String,
+}
+
+impl Claims {
+    pub fn is_impersonation(&self) -> bool {
+        self.act.is_some()
+    }
+
+    pub fn is_service_account(&self) -> bool {
+        self.role == SERVICE_ROLE
+    }
+
+    /// Only service account and OAuth tokens carry scopes; user session
+    /// tokens are left to the role checks in the handlers.
+    pub fn has_scope(&self, scope: &str) -> bool {
+        match &self.scope {
+            Some(granted) => granted.split_whitespace().any(|granted| granted == scope),
+            None => !self.is_service_account(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -22,7 +73,14 @@ pub struct AuthTokens {
     pub expires_in: i64,
 }
 
-pub fn generate_tokens(user_id: &str, email: &str, role: &str, secret: &str, expiry_hours: i64) -> Result<AuthTokens> {
+pub fn generate_tokens(
+    user_id: &str,
+    email: &str,
+    role: &str,
+    session_id: &str,
+    secret: &str,
+    expiry_hours: i64,
+) -> Result<AuthTokens> {
     let now = Utc::now();
     let exp = now + Duration::hours(expiry_hours);
     
@@ -33,6 +91,10 @@ pub fn generate_tokens(user_id: &str, email: &str, role: &str, secret: &str, exp
         exp: exp.timestamp(),
         iat: now.timestamp(),
         jti: Uuid::new_v4().to_string(),
+        sid: Some(session_id.to_string()),
+        scope: None,
+        workspace_id: None,
+        act: None,
     };
     
     let access_token = encode(
@@ -46,9 +108,13 @@ pub fn generate_tokens(user_id: &str, email: &str, role: &str, secret: &str, exp
         sub: user_id.to_string(),
         email: email.to_string(),
         role: role.to_string(),
-        exp: (now + Duration::days(30)).timestamp(),
+        exp: (now + Duration::days(REFRESH_TOKEN_DAYS)).timestamp(),
         iat: now.timestamp(),
         jti: Uuid::new_v4().to_string(),
+        sid: Some(session_id.to_string()),
+        scope: None,
+        workspace_id: None,
+        act: None,
     };
     
     let refresh_token = encode(
@@ -64,6 +130,70 @@ pub fn generate_tokens(user_id: &str, email: &str, role: &str, secret: &str, exp
     })
 }
 
+/// Mints an access token for a service account. There is no refresh token;
+/// clients repeat the client credentials grant instead.
+pub fn generate_service_token(
+    account_id: &str,
+    client_id: &str,
+    workspace_id: &str,
+    scopes: &[String],
+    secret: &str,
+    expiry_secs: i64,
+) -> Result<String> {
+    let now = Utc::now();
+    
+    let claims = Claims {
+        sub: account_id.to_string(),
+        email: client_id.to_string(),
+        role: SERVICE_ROLE.to_string(),
+        exp: (now + Duration::seconds(expiry_secs)).timestamp(),
+        iat: now.timestamp(),
+        jti: Uuid::new_v4().to_string(),
+        sid: None,
+        scope: Some(scopes.join(" ")),
+        workspace_id: Some(workspace_id.to_string()),
+        act: None,
+    };
+    
+    Ok(encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret.as_ref()),
+    )?)
+}
+
+/// Mints a non-refreshable token that acts as `subject_*` on behalf of `actor`.
+pub fn generate_impersonation_token(
+    subject_id: &str,
+    subject_email: &str,
+    subject_role: &str,
+    actor: Actor,
+    jti: &str,
+    secret: &str,
+    expiry_secs: i64,
+) -> Result<String> {
+    let now = Utc::now();
+    
+    let claims = Claims {
+        sub: subject_id.to_string(),
+        email: subject_email.to_string(),
+        role: subject_role.to_string(),
+        exp: (now + Duration::seconds(expiry_secs)).timestamp(),
+        iat: now.timestamp(),
+        jti: jti.to_string(),
+        sid: None,
+        scope: None,
+        workspace_id: None,
+        act: Some(actor),
+    };
+    
+    Ok(encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret.as_ref()),
+    )?)
+}
+
 pub fn validate_token(token: &str, secret: &str) -> Result<Claims> {
     let token_data = decode::<Claims>(
         token,
@@ -74,6 +204,16 @@ pub fn validate_token(token: &str, secret: &str) -> Result<Claims> {
     Ok(token_data.claims)
 }
 
+/// A random `[A-Za-z0-9]` string, for client secrets, recovery codes and
+/// one-time tokens.
+pub fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
 pub fn hash_password(password: &str) -> Result<String> {
     use argon2::{
         password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
@@ -101,4 +241,60 @@ pub fn verify_password(password: &str, hash: &str) -> Result<bool> {
     Ok(Argon2::default()
         .verify_password(password.as_bytes(), &parsed_hash)
         .is_ok())
-}
\ No newline at end of file
+}
+/// Claims for `user-1` with `role` and nothing optional set.
+#[cfg(test)]
+pub fn test_claims(role: &str) -> Claims {
+    Claims {
+        sub: "user-1".to_string(),
+        email: "user@example.com".to_string(),
+        role: role.to_string(),
+        exp: 0,
+        iat: 0,
+        jti: "jti".to_string(),
+        sid: None,
+        scope: None,
+        workspace_id: None,
+        act: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(role: &str, scope: Option<&str>) -> Claims {
+        Claims {
+            scope: scope.map(str::to_string),
+            ..test_claims(role)
+        }
+    }
+
+    #[test]
+    fn random_strings_are_alphanumeric() {
+        let value = random_string(40);
+        assert_eq!(value.len(), 40);
+        assert!(value.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(value, random_string(40));
+    }
+
+    #[test]
+    fn explicit_scopes_are_matched_exactly() {
+        let claims = claims(SERVICE_ROLE, Some("executions:read memory:write"));
+        assert!(claims.has_scope("executions:read"));
+        assert!(claims.has_scope("memory:write"));
+        assert!(!claims.has_scope("executions:write"));
+        assert!(!claims.has_scope("executions"));
+    }
+
+    #[test]
+    fn service_accounts_without_scopes_get_none() {
+        assert!(!claims(SERVICE_ROLE, None).has_scope("executions:read"));
+    }
+
+    #[test]
+    fn user_tokens_are_not_scoped() {
+        assert!(claims("user", None).has_scope("executions:write"));
+        assert!(claims("viewer", None).has_scope("workspaces:write"));
+    }
+}
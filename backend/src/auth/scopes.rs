@@ -0,0 +1,19 @@
+pub const EXECUTIONS_READ: &str = "executions:read";
+pub const EXECUTIONS_WRITE: &str = "executions:write";
+pub const MEMORY_READ: &str = "memory:read";
+pub const MEMORY_WRITE: &str = "memory:write";
+pub const WORKSPACES_READ: &str = "workspaces:read";
+pub const WORKSPACES_WRITE: &str = "workspaces:write";
+
+pub const ALL: &[&str] = &[
+    EXECUTIONS_READ,
+    EXECUTIONS_WRITE,
+    MEMORY_READ,
+    MEMORY_WRITE,
+    WORKSPACES_READ,
+    WORKSPACES_WRITE,
+];
+
+pub fn is_known(scope: &str) -> bool {
+    ALL.contains(&scope)
+}
@@ -0,0 +1,174 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    auth::{hash_password, random_string, scopes, verify_password},
+    error::{AppError, Result},
+    AppState,
+};
+
+const CLIENT_ID_PREFIX: &str = "sa_";
+const CLIENT_ID_LEN: usize = 24;
+const CLIENT_SECRET_LEN: usize = 48;
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ServiceAccount {
+    pub id: Uuid,
+    pub workspace_id: String,
+    pub name: String,
+    pub client_id: String,
+    pub scopes: Vec<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub disabled_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct ClientCredentials {
+    id: Uuid,
+    client_secret_hash: String,
+}
+
+const SELECT_COLUMNS: &str = "id, workspace_id, name, client_id, scopes, created_by, created_at, last_used_at, disabled_at";
+
+pub fn validate_scopes(requested: &[String]) -> Result<()> {
+    match requested.iter().find(|scope| !scopes::is_known(scope)) {
+        Some(unknown) => Err(AppError::BadRequest(format!("Unknown scope: {}", unknown))),
+        None => Ok(()),
+    }
+}
+
+/// Creates a service account and returns it with its plaintext client
+/// secret, which is never retrievable again.
+pub async fn create(
+    state: &AppState,
+    workspace_id: &str,
+    name: &str,
+    scopes: &[String],
+    created_by: &str,
+) -> Result<(ServiceAccount, String)> {
+    validate_scopes(scopes)?;
+
+    let client_id = format!("{}{}", CLIENT_ID_PREFIX, random_string(CLIENT_ID_LEN).to_lowercase());
+    let client_secret = random_string(CLIENT_SECRET_LEN);
+
+    let account = sqlx::query_as::<_, ServiceAccount>(&format!(
+        "INSERT INTO service_accounts (id, workspace_id, name, client_id, client_secret_hash, scopes, created_by) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING {}",
+        SELECT_COLUMNS
+    ))
+    .bind(Uuid::new_v4())
+    .bind(workspace_id)
+    .bind(name)
+    .bind(&client_id)
+    .bind(hash_password(&client_secret)?)
+    .bind(scopes)
+    .bind(created_by)
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok((account, client_secret))
+}
+
+pub async fn list(state: &AppState, workspace_id: &str) -> Result<Vec<ServiceAccount>> {
+    Ok(sqlx::query_as::<_, ServiceAccount>(&format!(
+        "SELECT {} FROM service_accounts WHERE workspace_id = $1 ORDER BY created_at",
+        SELECT_COLUMNS
+    ))
+    .bind(workspace_id)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+pub async fn disable(state: &AppState, workspace_id: &str, id: Uuid) -> Result<()> {
+    let updated = sqlx::query(
+        "UPDATE service_accounts SET disabled_at = COALESCE(disabled_at, NOW()) \
+         WHERE id = $1 AND workspace_id = $2",
+    )
+    .bind(id)
+    .bind(workspace_id)
+    .execute(&state.db)
+    .await?;
+
+    if updated.rows_affected() == 0 {
+        return Err(AppError::NotFound);
+    }
+    Ok(())
+}
+
+/// Replaces the client secret. Tokens already issued stay valid until expiry.
+pub async fn rotate_secret(state: &AppState, workspace_id: &str, id: Uuid) -> Result<String> {
+    let client_secret = random_string(CLIENT_SECRET_LEN);
+
+    let updated = sqlx::query(
+        "UPDATE service_accounts SET client_secret_hash = $3 \
+         WHERE id = $1 AND workspace_id = $2 AND disabled_at IS NULL",
+    )
+    .bind(id)
+    .bind(workspace_id)
+    .bind(hash_password(&client_secret)?)
+    .execute(&state.db)
+    .await?;
+
+    if updated.rows_affected() == 0 {
+        return Err(AppError::NotFound);
+    }
+    Ok(client_secret)
+}
+
+/// Verifies client credentials for an enabled account.
+pub async fn authenticate_client(state: &AppState, client_id: &str, client_secret: &str) -> Result<Option<ServiceAccount>> {
+    let credentials = sqlx::query_as::<_, ClientCredentials>(
+        "SELECT id, client_secret_hash FROM service_accounts WHERE client_id = $1 AND disabled_at IS NULL",
+    )
+    .bind(client_id)
+    .fetch_optional(&state.db)
+    .await?;
+
+    let Some(credentials) = credentials else {
+        return Ok(None);
+    };
+    if !verify_password(client_secret, &credentials.client_secret_hash)? {
+        return Ok(None);
+    }
+
+    Ok(sqlx::query_as::<_, ServiceAccount>(&format!(
+        "UPDATE service_accounts SET last_used_at = NOW() WHERE id = $1 RETURNING {}",
+        SELECT_COLUMNS
+    ))
+    .bind(credentials.id)
+    .fetch_optional(&state.db)
+    .await?)
+}
+
+pub async fn is_active(state: &AppState, id: &str) -> Result<bool> {
+    let Ok(id) = Uuid::parse_str(id) else {
+        return Ok(false);
+    };
+
+    let active: Option<bool> = sqlx::query_scalar(
+        "SELECT disabled_at IS NULL FROM service_accounts WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?;
+
+    Ok(active.unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_unknown_scopes() {
+        assert!(validate_scopes(&["executions:read".to_string(), "memory:write".to_string()]).is_ok());
+        assert!(validate_scopes(&[]).is_ok());
+        assert!(matches!(
+            validate_scopes(&["executions:read".to_string(), "admin".to_string()]),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+}
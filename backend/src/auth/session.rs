@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use serde::Serialize;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    auth::REFRESH_TOKEN_DAYS,
+    error::{AppError, Result},
+    AppState,
+};
+
+/// How often a single replica may write `last_seen_at` for one session.
+const TOUCH_INTERVAL: Duration = Duration::from_secs(60);
+const TRACKER_PRUNE_THRESHOLD: usize = 10_000;
+
+/// One signed-in device. Stored as a Redis hash so individual fields can be
+/// updated without a read-modify-write.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub id: String,
+    pub user_id: String,
+    pub email: String,
+    pub role: String,
+    pub user_agent: Option<String>,
+    pub ip: String,
+    pub created_at: i64,
+    pub last_seen_at: i64,
+    /// Tokens issued by IAM at login, used for IAM-backed validation.
+    pub iam_access_token: Option<String>,
+    pub iam_refresh_token: Option<String>,
+}
+
+/// The part of a session that is safe to show its owner.
+#[derive(Debug, Serialize)]
+pub struct SessionSummary {
+    pub id: String,
+    pub user_agent: Option<String>,
+    pub ip: String,
+    pub created_at: i64,
+    pub last_seen_at: i64,
+    pub current: bool,
+}
+
+impl Session {
+    pub fn new(
+        user_id: &str,
+        email: &str,
+        role: &str,
+        user_agent: Option<String>,
+        ip: String,
+        iam_access_token: Option<String>,
+        iam_refresh_token: Option<String>,
+    ) -> Self {
+        let now = Utc::now().timestamp();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            user_id: user_id.to_string(),
+            email: email.to_string(),
+            role: role.to_string(),
+            user_agent,
+            ip,
+            created_at: now,
+            last_seen_at: now,
+            iam_access_token,
+            iam_refresh_token,
+        }
+    }
+
+    fn to_fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![
+            ("id", self.id.clone()),
+            ("user_id", self.user_id.clone()),
+            ("email", self.email.clone()),
+            ("role", self.role.clone()),
+            ("ip", self.ip.clone()),
+            ("created_at", self.created_at.to_string()),
+            ("last_seen_at", self.last_seen_at.to_string()),
+        ];
+        if let Some(user_agent) = &self.user_agent {
+            fields.push(("user_agent", user_agent.clone()));
+        }
+        if let Some(token) = &self.iam_access_token {
+            fields.push(("iam_access_token", token.clone()));
+        }
+        if let Some(token) = &self.iam_refresh_token {
+            fields.push(("iam_refresh_token", token.clone()));
+        }
+        fields
+    }
+
+    fn from_fields(mut fields: HashMap<String, String>) -> Option<Self> {
+        Some(Self {
+            id: fields.remove("id")?,
+            user_id: fields.remove("user_id")?,
+            email: fields.remove("email")?,
+            role: fields.remove("role")?,
+            user_agent: fields.remove("user_agent"),
+            ip: fields.remove("ip").unwrap_or_default(),
+            created_at: fields.get("created_at")?.parse().ok()?,
+            last_seen_at: fields.get("last_seen_at")?.parse().ok()?,
+            iam_access_token: fields.remove("iam_access_token"),
+            iam_refresh_token: fields.remove("iam_refresh_token"),
+        })
+    }
+
+    fn summary(&self, current_session_id: Option<&str>) -> SessionSummary {
+        SessionSummary {
+            id: self.id.clone(),
+            user_agent: self.user_agent.clone(),
+            ip: self.ip.clone(),
+            created_at: self.created_at,
+            last_seen_at: self.last_seen_at,
+            current: current_session_id == Some(self.id.as_str()),
+        }
+    }
+}
+
+fn session_key(user_id: &str, session_id: &str) -> String {
+    format!("session:{}:{}", user_id, session_id)
+}
+
+fn index_key(user_id: &str) -> String {
+    format!("sessions:{}", user_id)
+}
+
+fn session_ttl_secs() -> i64 {
+    REFRESH_TOKEN_DAYS * 24 * 3600
+}
+
+pub async fn store(state: &AppState, session: &Session) -> Result<()> {
+    let key = session_key(&session.user_id, &session.id);
+    let index = index_key(&session.user_id);
+
+    let mut conn = state.redis_client.as_ref().clone();
+    redis::pipe()
+        .atomic()
+        .cmd("HSET")
+        .arg(&key)
+        .arg(session.to_fields())
+        .ignore()
+        .cmd("EXPIRE")
+        .arg(&key)
+        .arg(session_ttl_secs())
+        .ignore()
+        .cmd("SADD")
+        .arg(&index)
+        .arg(&session.id)
+        .ignore()
+        .cmd("EXPIRE")
+        .arg(&index)
+        .arg(session_ttl_secs())
+        .ignore()
+        .query_async::<_, ()>(&mut conn)
+        .await?;
+    Ok(())
+}
+
+/// Replaces the IAM tokens of an existing session without extending its lifetime.
+pub async fn update_iam_tokens(state: &AppState, session: &Session) -> Result<()> {
+    let (Some(access_token), Some(refresh_token)) = (&session.iam_access_token, &session.iam_refresh_token) else {
+        return Err(AppError::InternalServerError);
+    };
+
+    let mut conn = state.redis_client.as_ref().clone();
+    redis::cmd("HSET")
+        .arg(session_key(&session.user_id, &session.id))
+        .arg("iam_access_token")
+        .arg(access_token)
+        .arg("iam_refresh_token")
+        .arg(refresh_token)
+        .query_async::<_, ()>(&mut conn)
+        .await?;
+    Ok(())
+}
+
+/// Keeps a session alive for another refresh-token lifetime.
+pub async fn extend(state: &AppState, user_id: &str, session_id: &str) -> Result<bool> {
+    let mut conn = state.redis_client.as_ref().clone();
+    let (extended, _): (bool, bool) = redis::pipe()
+        .cmd("EXPIRE")
+        .arg(session_key(user_id, session_id))
+        .arg(session_ttl_secs())
+        .cmd("EXPIRE")
+        .arg(index_key(user_id))
+        .arg(session_ttl_secs())
+        .query_async(&mut conn)
+        .await?;
+    Ok(extended)
+}
+
+pub async fn exists(state: &AppState, user_id: &str, session_id: &str) -> Result<bool> {
+    let mut conn = state.redis_client.as_ref().clone();
+    Ok(redis::cmd("EXISTS")
+        .arg(session_key(user_id, session_id))
+        .query_async(&mut conn)
+        .await?)
+}
+
+pub async fn load(state: &AppState, user_id: &str, session_id: &str) -> Result<Option<Session>> {
+    let mut conn = state.redis_client.as_ref().clone();
+    let fields: HashMap<String, String> = redis::cmd("HGETALL")
+        .arg(session_key(user_id, session_id))
+        .query_async(&mut conn)
+        .await?;
+
+    Ok(Session::from_fields(fields))
+}
+
+/// Lists the user's live sessions, most recently active first, and drops
+/// index entries whose session has expired.
+pub async fn list(state: &AppState, user_id: &str, current_session_id: Option<&str>) -> Result<Vec<SessionSummary>> {
+    let mut conn = state.redis_client.as_ref().clone();
+    let session_ids: Vec<String> = redis::cmd("SMEMBERS")
+        .arg(index_key(user_id))
+        .query_async(&mut conn)
+        .await?;
+    if session_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut pipe = redis::pipe();
+    for session_id in &session_ids {
+        pipe.cmd("HGETALL").arg(session_key(user_id, session_id));
+    }
+    let records: Vec<HashMap<String, String>> = pipe.query_async(&mut conn).await?;
+
+    let mut sessions = Vec::new();
+    let mut expired = Vec::new();
+    for (session_id, fields) in session_ids.into_iter().zip(records) {
+        match Session::from_fields(fields) {
+            Some(session) => sessions.push(session.summary(current_session_id)),
+            None => expired.push(session_id),
+        }
+    }
+
+    if !expired.is_empty() {
+        redis::cmd("SREM")
+            .arg(index_key(user_id))
+            .arg(expired)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+    }
+
+    sessions.sort_by_key(|session| std::cmp::Reverse(session.last_seen_at));
+    Ok(sessions)
+}
+
+/// Revokes one session. Returns false if it did not exist.
+pub async fn delete(state: &AppState, user_id: &str, session_id: &str) -> Result<bool> {
+    let mut conn = state.redis_client.as_ref().clone();
+    let (deleted, _): (u64, u64) = redis::pipe()
+        .atomic()
+        .cmd("DEL")
+        .arg(session_key(user_id, session_id))
+        .cmd("SREM")
+        .arg(index_key(user_id))
+        .arg(session_id)
+        .query_async(&mut conn)
+        .await?;
+    Ok(deleted == 1)
+}
+
+/// Remembers when this replica last wrote `last_seen_at` for each session so
+/// authenticated requests only write to Redis once per `TOUCH_INTERVAL`.
+#[derive(Debug, Default)]
+pub struct ActivityTracker {
+    last_written: Mutex<HashMap<String, Instant>>,
+}
+
+impl ActivityTracker {
+    fn should_write(&self, session_id: &str) -> bool {
+        let now = Instant::now();
+        let mut last_written = self.last_written.lock().unwrap_or_else(|e| e.into_inner());
+
+        if last_written.len() > TRACKER_PRUNE_THRESHOLD {
+            last_written.retain(|_, written| now.duration_since(*written) < TOUCH_INTERVAL);
+        }
+
+        match last_written.get(session_id) {
+            Some(written) if now.duration_since(*written) < TOUCH_INTERVAL => false,
+            _ => {
+                last_written.insert(session_id.to_string(), now);
+                true
+            }
+        }
+    }
+}
+
+/// Records activity on a session without holding up the request.
+pub fn touch(state: &AppState, user_id: &str, session_id: &str) {
+    if !state.session_activity.should_write(session_id) {
+        return;
+    }
+
+    let key = session_key(user_id, session_id);
+    let mut conn = state.redis_client.as_ref().clone();
+    tokio::spawn(async move {
+        // HSET on a missing key would resurrect a revoked session, so only
+        // write while the hash still exists
+        let result = redis::Script::new(
+            "if redis.call('EXISTS', KEYS[1]) == 1 then \
+                 return redis.call('HSET', KEYS[1], 'last_seen_at', ARGV[1]) \
+             end \
+             return 0",
+        )
+        .key(&key)
+        .arg(Utc::now().timestamp())
+        .invoke_async::<_, i64>(&mut conn)
+        .await;
+
+        if let Err(e) = result {
+            error!("Failed to update session activity for {}: {}", key, e);
+        }
+    });
+}
@@ -0,0 +1,178 @@
+use tracing::warn;
+
+use crate::{
+    auth::{impersonation, service_accounts, session, validate_token, Claims},
+    config::TokenValidationStrategy,
+    error::{AppError, Result},
+    AppState,
+};
+
+fn iam_check_key(claims: &Claims) -> String {
+    format!("iam_check:{}:{}", claims.sub, claims.sid.as_deref().unwrap_or("-"))
+}
+
+fn is_unavailable(error: &AppError) -> bool {
+    matches!(
+        error,
+        AppError::GrpcError(status)
+            if matches!(status.code(), tonic::Code::Unavailable | tonic::Code::DeadlineExceeded)
+    )
+}
+
+/// Validates a bearer token according to the configured strategy.
+pub async fn authenticate(state: &AppState, token: &str) -> Result<Claims> {
+    let claims = validate_token(token, &state.config.jwt_secret)?;
+    verify_claims(state, &claims).await?;
+    Ok(claims)
+}
+
+/// Checks that already-decoded claims are still good: their session or
+/// grant is live and, depending on the strategy, IAM still agrees.
+pub async fn verify_claims(state: &AppState, claims: &Claims) -> Result<()> {
+    // Impersonation tokens live and die with their grant, not the subject's session
+    if claims.is_impersonation() {
+        if !impersonation::is_active(state, &claims.jti).await? {
+            return Err(AppError::Unauthorized);
+        }
+        return Ok(());
+    }
+
+    // User tokens are only as good as the device session they belong to
+    if !claims.is_service_account() {
+        let Some(session_id) = claims.sid.as_deref() else {
+            return Err(AppError::Unauthorized);
+        };
+        if !session::exists(state, &claims.sub, session_id).await? {
+            return Err(AppError::Unauthorized);
+        }
+        session::touch(state, &claims.sub, session_id);
+    }
+
+    match state.config.token_validation_strategy {
+        TokenValidationStrategy::Local => {}
+        TokenValidationStrategy::Iam => check_remote(state, claims).await?,
+        TokenValidationStrategy::Hybrid => {
+            let mut conn = state.redis_client.as_ref().clone();
+            let cached: bool = redis::cmd("EXISTS")
+                .arg(iam_check_key(claims))
+                .query_async(&mut conn)
+                .await?;
+
+            if !cached {
+                check_remote(state, claims).await?;
+                redis::cmd("SETEX")
+                    .arg(iam_check_key(claims))
+                    .arg(state.config.iam_check_ttl_secs)
+                    .arg(1)
+                    .query_async::<_, ()>(&mut conn)
+                    .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Service accounts are not IAM users, so their liveness check is whether
+/// the account is still enabled.
+async fn check_remote(state: &AppState, claims: &Claims) -> Result<()> {
+    if !claims.is_service_account() {
+        return check_with_iam(state, claims).await;
+    }
+
+    if service_accounts::is_active(state, &claims.sub).await? {
+        Ok(())
+    } else {
+        Err(AppError::Unauthorized)
+    }
+}
+
+/// Confirms with IAM that the session's user is still active and still holds
+/// the role baked into the claims. Ends the session otherwise.
+pub async fn check_with_iam(state: &AppState, claims: &Claims) -> Result<()> {
+    let Some(session_id) = claims.sid.as_deref() else {
+        return Err(AppError::Unauthorized);
+    };
+    let Some(mut session) = session::load(state, &claims.sub, session_id).await? else {
+        return Err(AppError::Unauthorized);
+    };
+    let Some(access_token) = session.iam_access_token.clone() else {
+        return Err(AppError::Unauthorized);
+    };
+
+    let role = match state.iam_client.validate_token(&access_token).await {
+        Ok(response) if response.valid => response.user.map(|user| user.role),
+        Ok(_) => refresh_iam_tokens(state, &mut session).await?,
+        Err(e) if is_unavailable(&e) => return Err(AppError::ServiceUnavailable),
+        Err(_) => None,
+    };
+
+    if role.as_deref() == Some(claims.role.as_str()) {
+        return Ok(());
+    }
+
+    warn!("IAM rejected session for user {}", claims.sub);
+    session::delete(state, &claims.sub, session_id).await?;
+    Err(AppError::Unauthorized)
+}
+
+/// Swaps an expired IAM access token for a fresh pair and returns the user's
+/// current role, or `None` if IAM refuses the refresh.
+async fn refresh_iam_tokens(state: &AppState, session: &mut session::Session) -> Result<Option<String>> {
+    let Some(refresh_token) = session.iam_refresh_token.clone() else {
+        return Ok(None);
+    };
+
+    let refreshed = match state.iam_client.refresh_token(&refresh_token).await {
+        Ok(refreshed) => refreshed,
+        Err(e) if is_unavailable(&e) => return Err(AppError::ServiceUnavailable),
+        Err(_) => return Ok(None),
+    };
+
+    let response = match state.iam_client.validate_token(&refreshed.access_token).await {
+        Ok(response) if response.valid => response,
+        Ok(_) => return Ok(None),
+        Err(e) if is_unavailable(&e) => return Err(AppError::ServiceUnavailable),
+        Err(_) => return Ok(None),
+    };
+
+    session.iam_access_token = Some(refreshed.access_token);
+    session.iam_refresh_token = Some(refreshed.refresh_token);
+    session::update_iam_tokens(state, session).await?;
+
+    Ok(response.user.map(|user| user.role))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::test_claims;
+
+    fn claims(sid: Option<&str>) -> Claims {
+        Claims {
+            sid: sid.map(str::to_string),
+            ..test_claims("user")
+        }
+    }
+
+    #[test]
+    fn caches_iam_checks_per_session() {
+        assert_eq!(iam_check_key(&claims(Some("s1"))), "iam_check:user-1:s1");
+        assert_eq!(iam_check_key(&claims(None)), "iam_check:user-1:-");
+    }
+
+    #[test]
+    fn only_transport_failures_count_as_unavailable() {
+        assert!(is_unavailable(&AppError::GrpcError(tonic::Status::unavailable("down"))));
+        assert!(is_unavailable(&AppError::GrpcError(tonic::Status::deadline_exceeded("slow"))));
+        assert!(!is_unavailable(&AppError::GrpcError(tonic::Status::unauthenticated("no"))));
+        assert!(!is_unavailable(&AppError::Unauthorized));
+    }
+
+    #[test]
+    fn parses_strategies_case_insensitively() {
+        assert_eq!("Hybrid".parse::<TokenValidationStrategy>(), Ok(TokenValidationStrategy::Hybrid));
+        assert_eq!("iam".parse::<TokenValidationStrategy>(), Ok(TokenValidationStrategy::Iam));
+        assert!("remote".parse::<TokenValidationStrategy>().is_err());
+    }
+}
@@ -0,0 +1,80 @@
+use crate::{
+    auth::Claims,
+    error::{AppError, Result},
+    AppState,
+};
+
+const MEMBERSHIP_CACHE_TTL_SECS: u64 = 60;
+const NOT_A_MEMBER: &str = "none";
+
+/// Workspace roles, ordered from least to most privileged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WorkspaceRole {
+    Viewer,
+    User,
+    Admin,
+}
+
+impl WorkspaceRole {
+    fn parse(role: &str) -> Option<Self> {
+        match role {
+            "viewer" => Some(Self::Viewer),
+            "user" => Some(Self::User),
+            "admin" => Some(Self::Admin),
+            _ => None,
+        }
+    }
+}
+
+fn membership_key(workspace_id: &str, user_id: &str) -> String {
+    format!("workspace_role:{}:{}", workspace_id, user_id)
+}
+
+/// Looks up the user's role in a workspace via IAM, caching the answer briefly.
+async fn membership(state: &AppState, workspace_id: &str, user_id: &str) -> Result<Option<WorkspaceRole>> {
+    let key = membership_key(workspace_id, user_id);
+    let mut conn = state.redis_client.as_ref().clone();
+
+    let cached: Option<String> = redis::cmd("GET").arg(&key).query_async(&mut conn).await?;
+    if let Some(cached) = cached {
+        return Ok(WorkspaceRole::parse(&cached));
+    }
+
+    let response = state.iam_client
+        .get_workspace_membership(workspace_id, user_id)
+        .await?;
+
+    redis::cmd("SETEX")
+        .arg(&key)
+        .arg(MEMBERSHIP_CACHE_TTL_SECS)
+        .arg(response.role.as_deref().unwrap_or(NOT_A_MEMBER))
+        .query_async::<_, ()>(&mut conn)
+        .await?;
+
+    Ok(response.role.as_deref().and_then(WorkspaceRole::parse))
+}
+
+/// Ensures the caller holds at least `required` in the workspace and returns
+/// the effective role. Platform admins pass everywhere; service accounts act
+/// as plain users inside their own workspace only.
+pub async fn require_role(
+    state: &AppState,
+    claims: &Claims,
+    workspace_id: &str,
+    required: WorkspaceRole,
+) -> Result<WorkspaceRole> {
+    if claims.role == "admin" {
+        return Ok(WorkspaceRole::Admin);
+    }
+
+    let role = if claims.is_service_account() {
+        (claims.workspace_id.as_deref() == Some(workspace_id)).then_some(WorkspaceRole::User)
+    } else {
+        membership(state, workspace_id, &claims.sub).await?
+    };
+
+    match role {
+        Some(role) if role >= required => Ok(role),
+        _ => Err(AppError::Forbidden),
+    }
+}
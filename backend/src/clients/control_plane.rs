@@ -1,93 +1,242 @@
-use tonic::transport::Channel;
-use crate::error::Result;
-
-// TODO: These will be generated from proto files
-pub mod control_plane_proto {
-    tonic::include_proto!("hermes.control_plane.v1");
-}
+use serde::{Deserialize, Serialize};
 
-use control_plane_proto::control_plane_service_client::ControlPlaneServiceClient;
+use super::grpc::RpcClient;
+use crate::error::Result;
 
 pub struct ControlPlaneClient {
-    client: ControlPlaneServiceClient<Channel>,
+    client: RpcClient,
 }
 
 impl ControlPlaneClient {
-    pub async fn new(url: &str) -> Result<Self> {
-        let channel = Channel::from_shared(url.to_string())
-            .expect("Invalid URL")
-            .connect()
-            .await?;
-        
-        let client = ControlPlaneServiceClient::new(channel);
-        Ok(Self { client })
+    pub async fn new(url: &str, json_codec: bool) -> Result<Self> {
+        Ok(Self {
+            client: RpcClient::connect(url, json_codec).await?,
+        })
     }
 
     pub async fn create_execution(&self, request: CreateExecutionRequest) -> Result<CreateExecutionResponse> {
-        let response = self.client
-            .clone()
-            .create_execution(request)
-            .await?;
-        Ok(response.into_inner())
+        let session_id = request.session_id.clone();
+        let request = CreateExecutionMessage { request, session_id };
+        self.client.unary("/hermes.control_plane.v1.ControlPlaneService/CreateExecution", request).await
     }
 
     pub async fn get_execution(&self, execution_id: &str) -> Result<GetExecutionResponse> {
         let request = GetExecutionRequest {
             execution_id: execution_id.to_string(),
         };
-        
-        let response = self.client
-            .clone()
-            .get_execution(request)
-            .await?;
-        Ok(response.into_inner())
+        self.client.unary("/hermes.control_plane.v1.ControlPlaneService/GetExecution", request).await
+    }
+
+    pub async fn write_stdin(&self, execution_id: &str, data: Vec<u8>, eof: bool) -> Result<WriteStdinResponse> {
+        let request = WriteStdinRequest {
+            execution_id: execution_id.to_string(),
+            data,
+            eof,
+        };
+        self.client.unary("/hermes.control_plane.v1.ControlPlaneService/WriteStdin", request).await
+    }
+
+    pub async fn send_signal(&self, execution_id: &str, signal: &str) -> Result<SendSignalResponse> {
+        let request = SendSignalRequest {
+            execution_id: execution_id.to_string(),
+            signal: signal.to_string(),
+        };
+        self.client.unary("/hermes.control_plane.v1.ControlPlaneService/SendSignal", request).await
+    }
+
+    /// Streams stdout and stderr from a running execution as it is produced.
+    pub async fn stream_output(&self, execution_id: &str) -> Result<tonic::Streaming<OutputChunk>> {
+        let request = StreamOutputRequest {
+            execution_id: execution_id.to_string(),
+        };
+        self.client.server_streaming("/hermes.control_plane.v1.ControlPlaneService/StreamOutput", request).await
+    }
+
+    /// Starts a long-lived session whose state carries over between the
+    /// executions run in it.
+    pub async fn create_session(&self, request: CreateSessionRequest) -> Result<CreateSessionResponse> {
+        self.client.unary("/hermes.control_plane.v1.ControlPlaneService/CreateSession", request).await
+    }
+
+    /// Clears a session's state, keeping the session itself.
+    pub async fn restart_session(&self, session_id: &str) -> Result<RestartSessionResponse> {
+        let request = RestartSessionRequest {
+            session_id: session_id.to_string(),
+        };
+        self.client.unary("/hermes.control_plane.v1.ControlPlaneService/RestartSession", request).await
+    }
+
+    pub async fn close_session(&self, session_id: &str) -> Result<CloseSessionResponse> {
+        let request = CloseSessionRequest {
+            session_id: session_id.to_string(),
+        };
+        self.client.unary("/hermes.control_plane.v1.ControlPlaneService/CloseSession", request).await
     }
 
     pub async fn cancel_execution(&self, execution_id: &str) -> Result<CancelExecutionResponse> {
         let request = CancelExecutionRequest {
             execution_id: execution_id.to_string(),
         };
-        
-        let response = self.client
-            .clone()
-            .cancel_execution(request)
-            .await?;
-        Ok(response.into_inner())
+        self.client.unary("/hermes.control_plane.v1.ControlPlaneService/CancelExecution", request).await
     }
 }
 
 // Placeholder types until proto generation
-#[derive(Debug, Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CreateExecutionRequest {
     pub code: String,
     pub language: String,
+    pub version: Option<String>,
     pub environment: std::collections::HashMap<String, String>,
+    pub input_files: Vec<InputFile>,
+    /// Runs the code in an existing session instead of a fresh sandbox.
+    /// Sessions do not outlive their notebook, so this is never stored.
+    #[serde(default, skip_serializing)]
+    pub session_id: Option<String>,
+}
+
+/// `session_id` is left out of stored requests but must reach the control
+/// plane.
+#[derive(Serialize)]
+struct CreateExecutionMessage {
+    #[serde(flatten)]
+    request: CreateExecutionRequest,
+    session_id: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+// Environment values may hold resolved secrets, so only the names are shown
+impl std::fmt::Debug for CreateExecutionRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CreateExecutionRequest")
+            .field("language", &self.language)
+            .field("version", &self.version)
+            .field("environment", &self.environment.keys().collect::<Vec<_>>())
+            .field("input_files", &self.input_files)
+            .field("session_id", &self.session_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A file staged in the artifact store for the sandbox to mount.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputFile {
+    pub name: String,
+    pub storage_key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateExecutionResponse {
     pub execution_id: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetExecutionRequest {
     pub execution_id: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetExecutionResponse {
     pub execution_id: String,
     pub status: String,
     pub output: Option<String>,
     pub error: Option<String>,
+    /// Display data the execution produced, in the order it was emitted
+    pub outputs: Vec<OutputItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputItem {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+    /// JSON object, e.g. image dimensions
+    pub metadata: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CreateSessionRequest {
+    pub language: String,
+    pub version: Option<String>,
+    pub environment: std::collections::HashMap<String, String>,
+}
+
+// Environment values hold resolved secrets, so only the names are shown
+impl std::fmt::Debug for CreateSessionRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CreateSessionRequest")
+            .field("language", &self.language)
+            .field("version", &self.version)
+            .field("environment", &self.environment.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateSessionResponse {
+    pub session_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestartSessionRequest {
+    pub session_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestartSessionResponse {
+    pub success: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloseSessionRequest {
+    pub session_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloseSessionResponse {
+    pub success: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CancelExecutionRequest {
     pub execution_id: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CancelExecutionResponse {
     pub success: bool,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WriteStdinRequest {
+    pub execution_id: String,
+    pub data: Vec<u8>,
+    pub eof: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WriteStdinResponse {
+    pub accepted: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SendSignalRequest {
+    pub execution_id: String,
+    pub signal: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SendSignalResponse {
+    pub delivered: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamOutputRequest {
+    pub execution_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputChunk {
+    /// `stdout` or `stderr`
+    pub stream: String,
+    pub data: Vec<u8>,
+    pub timestamp: Option<String>,
+}
@@ -0,0 +1,131 @@
+use std::marker::PhantomData;
+
+use prost::bytes::{Buf, BufMut};
+use serde::{de::DeserializeOwned, Serialize};
+use tonic::{
+    client::Grpc,
+    codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder},
+    codegen::http::uri::PathAndQuery,
+    transport::Channel,
+    Status, Streaming,
+};
+
+use crate::error::Result;
+
+// TODO: Replace with the generated clients once the proto files are available.
+// Until then messages are the placeholder types, sent as JSON over gRPC, which
+// only development stand-ins understand; connecting is refused unless
+// `GRPC_JSON_CODEC` opts in.
+
+/// A gRPC channel to one backend service.
+#[derive(Clone)]
+pub struct RpcClient {
+    inner: Grpc<Channel>,
+}
+
+impl RpcClient {
+    pub async fn connect(url: &str, json_codec: bool) -> Result<Self> {
+        if !json_codec {
+            return Err(Status::unimplemented(format!(
+                "Cannot connect to {}: the protobuf clients are not generated yet. Set GRPC_JSON_CODEC=true \
+                 to send JSON to development services",
+                url
+            ))
+            .into());
+        }
+        let channel = Channel::from_shared(url.to_string())
+            .map_err(|e| Status::invalid_argument(format!("Invalid service URL {}: {}", url, e)))?
+            .connect()
+            .await
+            .map_err(|e| Status::unavailable(format!("Failed to connect to {}: {}", url, e)))?;
+        Ok(Self { inner: Grpc::new(channel) })
+    }
+
+    async fn ready(&self) -> Result<Grpc<Channel>> {
+        let mut grpc = self.inner.clone();
+        grpc.ready()
+            .await
+            .map_err(|e| Status::unavailable(format!("Service was not ready: {}", e)))?;
+        Ok(grpc)
+    }
+
+    pub async fn unary<Req, Res>(&self, path: &'static str, request: Req) -> Result<Res>
+    where
+        Req: Serialize + Send + Sync + 'static,
+        Res: DeserializeOwned + Send + Sync + 'static,
+    {
+        let response = self
+            .ready()
+            .await?
+            .unary(tonic::Request::new(request), PathAndQuery::from_static(path), JsonCodec::default())
+            .await?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn server_streaming<Req, Res>(&self, path: &'static str, request: Req) -> Result<Streaming<Res>>
+    where
+        Req: Serialize + Send + Sync + 'static,
+        Res: DeserializeOwned + Send + Sync + 'static,
+    {
+        let response = self
+            .ready()
+            .await?
+            .server_streaming(tonic::Request::new(request), PathAndQuery::from_static(path), JsonCodec::default())
+            .await?;
+        Ok(response.into_inner())
+    }
+}
+
+struct JsonCodec<Req, Res>(PhantomData<(Req, Res)>);
+
+impl<Req, Res> Default for JsonCodec<Req, Res> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<Req, Res> Codec for JsonCodec<Req, Res>
+where
+    Req: Serialize + Send + 'static,
+    Res: DeserializeOwned + Send + 'static,
+{
+    type Encode = Req;
+    type Decode = Res;
+    type Encoder = JsonEncoder<Req>;
+    type Decoder = JsonDecoder<Res>;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        JsonEncoder(PhantomData)
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        JsonDecoder(PhantomData)
+    }
+}
+
+struct JsonEncoder<T>(PhantomData<T>);
+
+impl<T: Serialize> Encoder for JsonEncoder<T> {
+    type Item = T;
+    type Error = Status;
+
+    fn encode(&mut self, item: T, dst: &mut EncodeBuf<'_>) -> std::result::Result<(), Status> {
+        serde_json::to_writer(dst.writer(), &item).map_err(|e| Status::internal(e.to_string()))
+    }
+}
+
+struct JsonDecoder<T>(PhantomData<T>);
+
+impl<T: DeserializeOwned> Decoder for JsonDecoder<T> {
+    type Item = T;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> std::result::Result<Option<T>, Status> {
+        if !src.has_remaining() {
+            return Ok(None);
+        }
+        serde_json::from_reader(src.reader())
+            .map(Some)
+            .map_err(|e| Status::internal(e.to_string()))
+    }
+}
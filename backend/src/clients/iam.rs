@@ -1,26 +1,17 @@
-use tonic::transport::Channel;
-use crate::error::Result;
-
-// TODO: These will be generated from proto files
-pub mod iam_proto {
-    tonic::include_proto!("hermes.iam.v1");
-}
+use serde::{Deserialize, Serialize};
 
-use iam_proto::iam_service_client::IamServiceClient;
+use super::grpc::RpcClient;
+use crate::error::Result;
 
 pub struct IamClient {
-    client: IamServiceClient<Channel>,
+    client: RpcClient,
 }
 
 impl IamClient {
-    pub async fn new(url: &str) -> Result<Self> {
-        let channel = Channel::from_shared(url.to_string())
-            .expect("Invalid URL")
-            .connect()
-            .await?;
-        
-        let client = IamServiceClient::new(channel);
-        Ok(Self { client })
+    pub async fn new(url: &str, json_codec: bool) -> Result<Self> {
+        Ok(Self {
+            client: RpcClient::connect(url, json_codec).await?,
+        })
     }
 
     pub async fn authenticate(&self, email: &str, password: &str) -> Result<AuthenticateResponse> {
@@ -28,54 +19,54 @@ impl IamClient {
             email: email.to_string(),
             password: password.to_string(),
         };
-        
-        let response = self.client
-            .clone()
-            .authenticate(request)
-            .await?;
-        Ok(response.into_inner())
+        self.client.unary("/hermes.iam.v1.IamService/Authenticate", request).await
     }
 
     pub async fn validate_token(&self, token: &str) -> Result<ValidateTokenResponse> {
         let request = ValidateTokenRequest {
             token: token.to_string(),
         };
-        
-        let response = self.client
-            .clone()
-            .validate_token(request)
-            .await?;
-        Ok(response.into_inner())
+        self.client.unary("/hermes.iam.v1.IamService/ValidateToken", request).await
     }
 
     pub async fn refresh_token(&self, refresh_token: &str) -> Result<RefreshTokenResponse> {
         let request = RefreshTokenRequest {
             refresh_token: refresh_token.to_string(),
         };
-        
-        let response = self.client
-            .clone()
-            .refresh_token(request)
-            .await?;
-        Ok(response.into_inner())
+        self.client.unary("/hermes.iam.v1.IamService/RefreshToken", request).await
+    }
+
+    pub async fn get_user(&self, user_id: &str) -> Result<GetUserResponse> {
+        let request = GetUserRequest {
+            user_id: user_id.to_string(),
+        };
+        self.client.unary("/hermes.iam.v1.IamService/GetUser", request).await
+    }
+
+    pub async fn get_workspace_membership(&self, workspace_id: &str, user_id: &str) -> Result<GetWorkspaceMembershipResponse> {
+        let request = GetWorkspaceMembershipRequest {
+            workspace_id: workspace_id.to_string(),
+            user_id: user_id.to_string(),
+        };
+        self.client.unary("/hermes.iam.v1.IamService/GetWorkspaceMembership", request).await
     }
 }
 
 // Placeholder types until proto generation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthenticateRequest {
     pub email: String,
     pub password: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthenticateResponse {
     pub access_token: String,
     pub refresh_token: String,
     pub user: User,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub id: String,
     pub email: String,
@@ -83,24 +74,46 @@ pub struct User {
     pub role: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValidateTokenRequest {
     pub token: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValidateTokenResponse {
     pub valid: bool,
     pub user: Option<User>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RefreshTokenRequest {
     pub refresh_token: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RefreshTokenResponse {
     pub access_token: String,
     pub refresh_token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetUserRequest {
+    pub user_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetUserResponse {
+    pub user: Option<User>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetWorkspaceMembershipRequest {
+    pub workspace_id: String,
+    pub user_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetWorkspaceMembershipResponse {
+    /// `None` when the user is not a member of the workspace.
+    pub role: Option<String>,
 }
\ No newline at end of file
@@ -1,80 +1,59 @@
-use tonic::transport::Channel;
-use crate::error::Result;
-
-// TODO: These will be generated from proto files
-pub mod memory_proto {
-    tonic::include_proto!("hermes.memory.v1");
-}
+use serde::{Deserialize, Serialize};
 
-use memory_proto::memory_service_client::MemoryServiceClient;
+use super::grpc::RpcClient;
+use crate::error::Result;
 
 pub struct MemoryClient {
-    client: MemoryServiceClient<Channel>,
+    client: RpcClient,
 }
 
 impl MemoryClient {
-    pub async fn new(url: &str) -> Result<Self> {
-        let channel = Channel::from_shared(url.to_string())
-            .expect("Invalid URL")
-            .connect()
-            .await?;
-        
-        let client = MemoryServiceClient::new(channel);
-        Ok(Self { client })
+    pub async fn new(url: &str, json_codec: bool) -> Result<Self> {
+        Ok(Self {
+            client: RpcClient::connect(url, json_codec).await?,
+        })
     }
 
     pub async fn store(&self, request: StoreRequest) -> Result<StoreResponse> {
-        let response = self.client
-            .clone()
-            .store(request)
-            .await?;
-        Ok(response.into_inner())
+        self.client.unary("/hermes.memory.v1.MemoryService/Store", request).await
     }
 
     pub async fn search(&self, request: SearchRequest) -> Result<SearchResponse> {
-        let response = self.client
-            .clone()
-            .search(request)
-            .await?;
-        Ok(response.into_inner())
+        self.client.unary("/hermes.memory.v1.MemoryService/Search", request).await
     }
 
     pub async fn query(&self, request: QueryRequest) -> Result<QueryResponse> {
-        let response = self.client
-            .clone()
-            .query(request)
-            .await?;
-        Ok(response.into_inner())
+        self.client.unary("/hermes.memory.v1.MemoryService/Query", request).await
     }
 }
 
 // Placeholder types until proto generation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StoreRequest {
     pub namespace: String,
     pub content: String,
     pub metadata: std::collections::HashMap<String, String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StoreResponse {
     pub id: String,
     pub success: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchRequest {
     pub namespace: String,
     pub query: String,
     pub limit: i32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResponse {
     pub results: Vec<SearchResult>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResult {
     pub id: String,
     pub content: String,
@@ -82,13 +61,13 @@ pub struct SearchResult {
     pub metadata: std::collections::HashMap<String, String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryRequest {
     pub namespace: String,
     pub omni_query: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryResponse {
     pub results: Vec<serde_json::Value>,
     pub execution_time_ms: i64,
@@ -1,6 +1,7 @@
-mod control_plane;
-mod iam;
-mod memory;
+pub mod control_plane;
+mod grpc;
+pub mod iam;
+pub mod memory;
 
 pub use control_plane::ControlPlaneClient;
 pub use iam::IamClient;
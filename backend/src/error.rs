@@ -1,11 +1,28 @@
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
+use serde::Serialize;
 use serde_json::json;
 use thiserror::Error;
 
+/// One rejected field in a request body.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Authentication failed")]
@@ -14,12 +31,42 @@ pub enum AppError {
     #[error("Unauthorized")]
     Unauthorized,
     
+    #[error("Forbidden")]
+    Forbidden,
+    
+    #[error("Too many requests, retry after {retry_after}s")]
+    TooManyRequests { retry_after: u64 },
+    
+    #[error("Quota exceeded: {quota} ({used}/{limit}) for {scope}")]
+    QuotaExceeded {
+        scope: &'static str,
+        quota: String,
+        used: u64,
+        limit: u64,
+        retry_after: u64,
+    },
+    
     #[error("Bad request: {0}")]
     BadRequest(String),
     
     #[error("Not found")]
     NotFound,
     
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    
+    #[error("Unprocessable entity: {0}")]
+    UnprocessableEntity(String),
+    
+    #[error("Validation failed")]
+    Validation(Vec<FieldError>),
+    
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+    
+    #[error("Range not satisfiable")]
+    RangeNotSatisfiable { size: u64 },
+    
     #[error("Internal server error")]
     InternalServerError,
     
@@ -29,6 +76,9 @@ pub enum AppError {
     #[error("Database error: {0}")]
     DatabaseError(#[from] sqlx::Error),
     
+    #[error("Storage error: {0}")]
+    StorageError(#[from] object_store::Error),
+    
     #[error("Redis error: {0}")]
     RedisError(#[from] redis::RedisError),
     
@@ -41,14 +91,46 @@ pub enum AppError {
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        let retry_after = match &self {
+            AppError::TooManyRequests { retry_after } | AppError::QuotaExceeded { retry_after, .. } => Some(*retry_after),
+            _ => None,
+        };
+        let unsatisfied_size = match &self {
+            AppError::RangeNotSatisfiable { size } => Some(*size),
+            _ => None,
+        };
+        let quota = match &self {
+            AppError::QuotaExceeded { scope, quota, used, limit, .. } => Some(json!({
+                "scope": scope,
+                "name": quota,
+                "used": used,
+                "limit": limit,
+            })),
+            _ => None,
+        };
+        let field_errors = match &self {
+            AppError::Validation(fields) => Some(json!(fields)),
+            _ => None,
+        };
+
         let (status, error_message) = match self {
             AppError::AuthenticationError => (StatusCode::UNAUTHORIZED, "Authentication failed"),
             AppError::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized"),
+            AppError::Forbidden => (StatusCode::FORBIDDEN, "Forbidden"),
+            AppError::TooManyRequests { .. } => (StatusCode::TOO_MANY_REQUESTS, "Too many requests"),
+            AppError::QuotaExceeded { .. } => (StatusCode::TOO_MANY_REQUESTS, "Quota exceeded"),
             AppError::BadRequest(ref msg) => (StatusCode::BAD_REQUEST, msg.as_str()),
             AppError::NotFound => (StatusCode::NOT_FOUND, "Not found"),
+            AppError::Conflict(ref msg) => (StatusCode::CONFLICT, msg.as_str()),
+            AppError::UnprocessableEntity(ref msg) => (StatusCode::UNPROCESSABLE_ENTITY, msg.as_str()),
+            AppError::Validation(_) => (StatusCode::UNPROCESSABLE_ENTITY, "Validation failed"),
+            AppError::PayloadTooLarge(ref msg) => (StatusCode::PAYLOAD_TOO_LARGE, msg.as_str()),
+            AppError::RangeNotSatisfiable { .. } => (StatusCode::RANGE_NOT_SATISFIABLE, "Range not satisfiable"),
             AppError::InternalServerError => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error"),
             AppError::ServiceUnavailable => (StatusCode::SERVICE_UNAVAILABLE, "Service unavailable"),
             AppError::DatabaseError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error"),
+            AppError::StorageError(object_store::Error::NotFound { .. }) => (StatusCode::NOT_FOUND, "Not found"),
+            AppError::StorageError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Storage error"),
             AppError::RedisError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Cache error"),
             AppError::GrpcError(ref e) => {
                 let status = match e.code() {
@@ -63,12 +145,32 @@ impl IntoResponse for AppError {
             AppError::JwtError(_) => (StatusCode::UNAUTHORIZED, "Invalid token"),
         };
 
-        let body = Json(json!({
+        let mut body = json!({
             "error": error_message,
             "status": status.as_u16(),
-        }));
+        });
+        if let Some(secs) = retry_after {
+            body["retry_after"] = json!(secs);
+        }
+        if let Some(quota) = quota {
+            body["quota"] = quota;
+        }
+        if let Some(fields) = field_errors {
+            body["fields"] = fields;
+        }
 
-        (status, body).into_response()
+        let mut response = (status, Json(body)).into_response();
+        if let Some(secs) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        if let Some(size) = unsatisfied_size {
+            if let Ok(value) = HeaderValue::from_str(&format!("bytes */{}", size)) {
+                response.headers_mut().insert(header::CONTENT_RANGE, value);
+            }
+        }
+        response
     }
 }
 
@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    AppState,
+};
+
+pub const HEADER: &str = "idempotency-key";
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+const MAX_KEY_LEN: usize = 255;
+/// Upper bound on how long a request may hold the key; covers a slow
+/// control plane without wedging the key if the holder dies.
+const LOCK_TTL_MS: u64 = 30_000;
+const LOCK_WAIT: Duration = Duration::from_secs(10);
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredResponse {
+    fingerprint: String,
+    pub status: u16,
+    pub body: serde_json::Value,
+}
+
+/// Exclusive right to process a request for one key.
+#[derive(Debug)]
+pub struct Lock {
+    record_key: String,
+    lock_key: String,
+    token: String,
+    fingerprint: String,
+}
+
+pub enum Claim {
+    /// First request for the key; the caller must `complete` or `release`.
+    Acquired(Lock),
+    /// The key has already been answered with this response.
+    Replay(StoredResponse),
+}
+
+fn record_key(scope: &str, key: &str) -> String {
+    format!("idempotency:{}:{}", scope, key)
+}
+
+fn lock_key(scope: &str, key: &str) -> String {
+    format!("idempotency_lock:{}:{}", scope, key)
+}
+
+pub fn validate_key(key: &str) -> Result<()> {
+    if key.is_empty() || key.len() > MAX_KEY_LEN || !key.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err(AppError::BadRequest(format!(
+            "Idempotency-Key must be 1-{} printable ASCII characters",
+            MAX_KEY_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// Hashes a request body so a reused key can be matched against it. Goes
+/// through `serde_json::Value` so map ordering does not change the result.
+pub fn fingerprint<T: Serialize>(payload: &T) -> Result<String> {
+    let value = serde_json::to_value(payload).map_err(|_| AppError::InternalServerError)?;
+    let bytes = serde_json::to_vec(&value).map_err(|_| AppError::InternalServerError)?;
+    Ok(format!("{:x}", Sha256::digest(&bytes)))
+}
+
+async fn load(conn: &mut redis::aio::ConnectionManager, key: &str, fingerprint: &str) -> Result<Option<StoredResponse>> {
+    let data: Option<String> = redis::cmd("GET").arg(key).query_async(conn).await?;
+    let Some(stored) = data.and_then(|data| serde_json::from_str::<StoredResponse>(&data).ok()) else {
+        return Ok(None);
+    };
+
+    if stored.fingerprint != fingerprint {
+        return Err(AppError::UnprocessableEntity(
+            "Idempotency-Key was already used with a different request body".to_string(),
+        ));
+    }
+    Ok(Some(stored))
+}
+
+/// Returns the stored response for `key`, or the lock to process it. A
+/// concurrent duplicate waits for the first request to finish instead of
+/// running alongside it.
+pub async fn begin(state: &AppState, scope: &str, key: &str, fingerprint: String) -> Result<Claim> {
+    let record_key = record_key(scope, key);
+    let lock_key = lock_key(scope, key);
+    let token = Uuid::new_v4().to_string();
+    let deadline = Instant::now() + LOCK_WAIT;
+    let mut conn = state.redis_client.as_ref().clone();
+
+    loop {
+        if let Some(stored) = load(&mut conn, &record_key, &fingerprint).await? {
+            return Ok(Claim::Replay(stored));
+        }
+
+        let acquired: Option<String> = redis::cmd("SET")
+            .arg(&lock_key)
+            .arg(&token)
+            .arg("NX")
+            .arg("PX")
+            .arg(LOCK_TTL_MS)
+            .query_async(&mut conn)
+            .await?;
+
+        if acquired.is_some() {
+            // The previous holder may have stored its response between our
+            // read and taking the lock
+            if let Some(stored) = load(&mut conn, &record_key, &fingerprint).await? {
+                release_lock(&mut conn, &lock_key, &token).await?;
+                return Ok(Claim::Replay(stored));
+            }
+            return Ok(Claim::Acquired(Lock {
+                record_key,
+                lock_key,
+                token,
+                fingerprint,
+            }));
+        }
+
+        if Instant::now() >= deadline {
+            return Err(AppError::Conflict(
+                "A request with this Idempotency-Key is still being processed".to_string(),
+            ));
+        }
+        tokio::time::sleep(LOCK_POLL_INTERVAL).await;
+    }
+}
+
+async fn release_lock(conn: &mut redis::aio::ConnectionManager, lock_key: &str, token: &str) -> Result<()> {
+    // Only delete the lock if it is still ours; it may have expired and been
+    // taken by another request
+    redis::Script::new(
+        "if redis.call('GET', KEYS[1]) == ARGV[1] then \
+             return redis.call('DEL', KEYS[1]) \
+         end \
+         return 0",
+    )
+    .key(lock_key)
+    .arg(token)
+    .invoke_async::<_, i64>(conn)
+    .await?;
+    Ok(())
+}
+
+/// Stores the response for replay and releases the lock.
+pub async fn complete(state: &AppState, lock: Lock, status: u16, body: serde_json::Value) -> Result<()> {
+    let stored = StoredResponse {
+        fingerprint: lock.fingerprint,
+        status,
+        body,
+    };
+    let data = serde_json::to_string(&stored).map_err(|_| AppError::InternalServerError)?;
+
+    let mut conn = state.redis_client.as_ref().clone();
+    redis::cmd("SETEX")
+        .arg(&lock.record_key)
+        .arg(state.config.idempotency_ttl_secs)
+        .arg(data)
+        .query_async::<_, ()>(&mut conn)
+        .await?;
+    release_lock(&mut conn, &lock.lock_key, &lock.token).await
+}
+
+/// Gives the key up without storing anything, so a retry can try again.
+pub async fn release(state: &AppState, lock: Lock) -> Result<()> {
+    let mut conn = state.redis_client.as_ref().clone();
+    release_lock(&mut conn, &lock.lock_key, &lock.token).await
+}
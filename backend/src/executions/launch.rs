@@ -0,0 +1,101 @@
+use tracing::error;
+
+use crate::{
+    auth::{
+        workspace::{self, WorkspaceRole},
+        Claims,
+    },
+    clients::control_plane::CreateExecutionRequest,
+    error::{AppError, Result},
+    executions::{
+        policy::{self, ExecutionInput},
+        quota, Execution, NewExecution,
+    },
+    logs::parse::ParsingRules,
+    secrets, AppState,
+};
+
+/// Checks the request against the caller's workspace role, the execution
+/// policy and quotas, resolves secret references, then starts it on the
+/// control plane and records it.
+pub async fn launch(
+    state: &AppState,
+    claims: &Claims,
+    workspace_id: Option<&str>,
+    tags: &[String],
+    parent_execution_id: Option<&str>,
+    log_parsing: Option<&ParsingRules>,
+    request: CreateExecutionRequest,
+) -> Result<Execution> {
+    super::validate_tags(tags)?;
+    if let Some(rules) = log_parsing {
+        rules.validate()?;
+    }
+    if let Some(workspace_id) = workspace_id {
+        workspace::require_role(state, claims, workspace_id, WorkspaceRole::User).await?;
+    }
+
+    let policy = policy::effective(state, workspace_id).await?;
+    policy::validate(
+        &policy,
+        &ExecutionInput {
+            language: &request.language,
+            version: request.version.as_deref(),
+            code: &request.code,
+            environment: &request.environment,
+        },
+    )?;
+
+    // Only the unresolved request is recorded; resolved values exist just
+    // for the call to the control plane
+    let mut resolved = request.clone();
+    let secret_values = secrets::resolve(state, workspace_id, &mut resolved.environment).await?;
+    policy::validate_resolved(&policy, &resolved.environment)?;
+
+    let reservation = quota::reserve(state, &claims.sub, workspace_id).await?;
+    let response = match state.control_plane_client.create_execution(resolved).await {
+        Ok(response) => response,
+        Err(e) => {
+            if let Err(release_error) = quota::release(state, reservation).await {
+                error!("Failed to release quota reservation: {}", release_error);
+            }
+            // The control plane may echo the environment back in its error
+            return Err(match e {
+                AppError::GrpcError(status) => AppError::GrpcError(tonic::Status::new(
+                    status.code(),
+                    secrets::redact(status.message(), &secret_values),
+                )),
+                e => e,
+            });
+        }
+    };
+
+    // The execution is running from here on. Failing the request would only
+    // invite a retry that starts it twice, so bookkeeping errors are logged.
+    if let Err(e) = quota::commit(state, reservation, &response.execution_id).await {
+        error!("Failed to commit quota reservation for execution {}: {}", response.execution_id, e);
+    }
+
+    let new = NewExecution {
+        id: &response.execution_id,
+        owner_id: &claims.sub,
+        workspace_id,
+        tags,
+        parent_execution_id,
+        request: &request,
+        log_parsing,
+    };
+    match super::record(state, &new).await {
+        Ok(execution) => {
+            state.execution_logs.ensure(state, &execution);
+            Ok(execution)
+        }
+        Err(e) => {
+            error!("Failed to record execution {}: {}", response.execution_id, e);
+            if let Err(e) = quota::abandon(state, &claims.sub, workspace_id, &response.execution_id).await {
+                error!("Failed to free the running slot of execution {}: {}", response.execution_id, e);
+            }
+            Ok(super::unrecorded(&new))
+        }
+    }
+}
@@ -0,0 +1,539 @@
+use std::collections::HashMap;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{types::Json, Postgres, QueryBuilder};
+use tracing::error;
+
+use crate::{
+    alerts,
+    auth::{
+        scopes,
+        workspace::{self, WorkspaceRole},
+        Claims,
+    },
+    clients::control_plane::CreateExecutionRequest,
+    error::{AppError, Result},
+    logs::parse::ParsingRules,
+    notebooks, secrets, webhooks, AppState,
+};
+
+pub mod idempotency;
+pub mod launch;
+pub mod outputs;
+pub mod policy;
+pub mod quota;
+pub mod status_hub;
+pub mod watcher;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 100;
+const MAX_TAGS: usize = 20;
+const MAX_TAG_LEN: usize = 64;
+/// How far a lineage walks up or down the chain of re-runs.
+const MAX_LINEAGE_DEPTH: i32 = 100;
+const MAX_LINEAGE_DESCENDANTS: i64 = 200;
+
+/// Statuses after which the control plane will not report further changes.
+pub(crate) const TERMINAL_STATUSES: &[&str] = &["completed", "failed", "cancelled", "timeout", "lost"];
+/// Recorded when the control plane no longer knows about an execution.
+pub const STATUS_LOST: &str = "lost";
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Execution {
+    pub id: String,
+    pub owner_id: String,
+    pub workspace_id: Option<String>,
+    pub language: String,
+    pub language_version: Option<String>,
+    pub status: String,
+    pub code_hash: String,
+    pub tags: Vec<String>,
+    pub parent_execution_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+const SELECT_COLUMNS: &str = "id, owner_id, workspace_id, language, language_version, status, code_hash, tags, \
+     parent_execution_id, created_at, updated_at, completed_at";
+
+/// Which executions a listing may include.
+#[derive(Debug)]
+pub enum Visibility {
+    All,
+    Owner(String),
+    Workspace(String),
+}
+
+#[derive(Debug)]
+pub struct ListFilter {
+    pub visibility: Visibility,
+    pub statuses: Vec<String>,
+    pub languages: Vec<String>,
+    pub created_by: Option<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    /// Executions must carry every one of these tags.
+    pub tags: Vec<String>,
+    pub cursor: Option<Cursor>,
+    pub limit: i64,
+}
+
+/// Keyset position of the last row on a page, opaque to clients.
+#[derive(Debug, Clone)]
+pub struct Cursor {
+    created_at: DateTime<Utc>,
+    id: String,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!("{}|{}", self.created_at.timestamp_micros(), self.id))
+    }
+
+    pub fn decode(value: &str) -> Result<Self> {
+        let invalid = || AppError::BadRequest("Invalid cursor".to_string());
+
+        let decoded = URL_SAFE_NO_PAD.decode(value).map_err(|_| invalid())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+        let (micros, id) = decoded.split_once('|').ok_or_else(invalid)?;
+        let micros = micros.parse().map_err(|_| invalid())?;
+
+        Ok(Self {
+            created_at: DateTime::from_timestamp_micros(micros).ok_or_else(invalid)?,
+            id: id.to_string(),
+        })
+    }
+}
+
+pub fn is_terminal(status: &str) -> bool {
+    TERMINAL_STATUSES.contains(&status)
+}
+
+pub fn code_hash(code: &str) -> String {
+    format!("{:x}", Sha256::digest(code.as_bytes()))
+}
+
+pub fn validate_tags(tags: &[String]) -> Result<()> {
+    if tags.len() > MAX_TAGS {
+        return Err(AppError::BadRequest(format!("At most {} tags are allowed", MAX_TAGS)));
+    }
+    if let Some(tag) = tags.iter().find(|tag| tag.trim().is_empty() || tag.len() > MAX_TAG_LEN) {
+        return Err(AppError::BadRequest(format!("Invalid tag: {:?}", tag)));
+    }
+    Ok(())
+}
+
+/// What is known about an execution when it is submitted.
+pub struct NewExecution<'a> {
+    pub id: &'a str,
+    pub owner_id: &'a str,
+    pub workspace_id: Option<&'a str>,
+    pub tags: &'a [String],
+    pub parent_execution_id: Option<&'a str>,
+    /// Kept so the execution can be re-run later.
+    pub request: &'a CreateExecutionRequest,
+    pub log_parsing: Option<&'a ParsingRules>,
+}
+
+/// A request as kept in `executions.request`. Secret references are stored
+/// as written; literal environment values are encrypted under the secrets
+/// master key, or left out (by name) when none is configured.
+#[derive(Serialize, Deserialize)]
+struct KeptRequest {
+    #[serde(flatten)]
+    request: CreateExecutionRequest,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sealed_environment: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    dropped_environment: Vec<String>,
+}
+
+/// A request read back for a re-run.
+pub struct StoredRequest {
+    pub request: CreateExecutionRequest,
+    /// Environment variables whose literal values were not kept
+    pub dropped_environment: Vec<String>,
+}
+
+/// Sealed values are bound to their execution so they cannot be moved.
+fn sealing_context(execution_id: &str) -> String {
+    format!("execution-request/{}", execution_id)
+}
+
+fn keep(state: &AppState, execution_id: &str, request: &CreateExecutionRequest) -> Result<KeptRequest> {
+    let (references, literals) = secrets::split_literals(&request.environment);
+
+    let mut kept = KeptRequest {
+        request: CreateExecutionRequest {
+            environment: references,
+            ..request.clone()
+        },
+        sealed_environment: None,
+        dropped_environment: Vec::new(),
+    };
+    if literals.is_empty() {
+        return Ok(kept);
+    }
+
+    let plaintext = serde_json::to_string(&literals).map_err(|_| AppError::InternalServerError)?;
+    match secrets::seal(state, &sealing_context(execution_id), &plaintext)? {
+        Some(sealed) => kept.sealed_environment = Some(sealed),
+        None => {
+            kept.dropped_environment = literals.into_keys().collect();
+            kept.dropped_environment.sort();
+        }
+    }
+    Ok(kept)
+}
+
+pub async fn record(state: &AppState, execution: &NewExecution<'_>) -> Result<Execution> {
+    let request = execution.request;
+    let kept = keep(state, execution.id, request)?;
+    Ok(sqlx::query_as::<_, Execution>(&format!(
+        "INSERT INTO executions \
+         (id, owner_id, workspace_id, language, language_version, status, code_hash, tags, parent_execution_id, \
+          request, log_parsing) \
+         VALUES ($1, $2, $3, $4, $5, 'pending', $6, $7, $8, $9, $10) RETURNING {}",
+        SELECT_COLUMNS
+    ))
+    .bind(execution.id)
+    .bind(execution.owner_id)
+    .bind(execution.workspace_id)
+    .bind(&request.language)
+    .bind(&request.version)
+    .bind(code_hash(&request.code))
+    .bind(execution.tags)
+    .bind(execution.parent_execution_id)
+    .bind(Json(kept))
+    .bind(execution.log_parsing.map(Json))
+    .fetch_one(&state.db)
+    .await?)
+}
+
+/// What `record` would have returned, for an execution that started but
+/// could not be stored.
+pub fn unrecorded(execution: &NewExecution<'_>) -> Execution {
+    let now = Utc::now();
+    Execution {
+        id: execution.id.to_string(),
+        owner_id: execution.owner_id.to_string(),
+        workspace_id: execution.workspace_id.map(str::to_string),
+        language: execution.request.language.clone(),
+        language_version: execution.request.version.clone(),
+        status: "pending".to_string(),
+        code_hash: code_hash(&execution.request.code),
+        tags: execution.tags.to_vec(),
+        parent_execution_id: execution.parent_execution_id.map(str::to_string),
+        created_at: now,
+        updated_at: now,
+        completed_at: None,
+    }
+}
+
+/// The request an execution was submitted with, if it was recorded.
+/// Executions from before requests were stored have none.
+pub async fn stored_request(state: &AppState, id: &str) -> Result<Option<StoredRequest>> {
+    let kept: Option<Option<Json<KeptRequest>>> = sqlx::query_scalar("SELECT request FROM executions WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?;
+    let Some(Json(kept)) = kept.flatten() else {
+        return Ok(None);
+    };
+
+    let mut request = kept.request;
+    if let Some(sealed) = &kept.sealed_environment {
+        let plaintext = secrets::unseal(state, &sealing_context(id), sealed)?;
+        let literals: HashMap<String, String> =
+            serde_json::from_str(&plaintext).map_err(|_| AppError::InternalServerError)?;
+        request.environment.extend(literals);
+    }
+    Ok(Some(StoredRequest {
+        request,
+        dropped_environment: kept.dropped_environment,
+    }))
+}
+
+/// How the execution's output lines are parsed.
+pub async fn log_parsing(state: &AppState, id: &str) -> Result<ParsingRules> {
+    let rules: Option<Option<Json<ParsingRules>>> =
+        sqlx::query_scalar("SELECT log_parsing FROM executions WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&state.db)
+            .await?;
+    Ok(rules.flatten().map(|Json(rules)| rules).unwrap_or_default())
+}
+
+/// The executions `id` was re-run from, root first.
+pub async fn ancestors(state: &AppState, id: &str) -> Result<Vec<Execution>> {
+    Ok(sqlx::query_as::<_, Execution>(&format!(
+        "WITH RECURSIVE chain (id, parent_id, depth) AS ( \
+             SELECT id, parent_execution_id, 0 FROM executions WHERE id = $1 \
+             UNION ALL \
+             SELECT e.id, e.parent_execution_id, chain.depth + 1 \
+             FROM executions e JOIN chain ON e.id = chain.parent_id \
+             WHERE chain.depth < $2 \
+         ) \
+         SELECT {} FROM executions JOIN chain USING (id) WHERE chain.depth > 0 ORDER BY chain.depth DESC",
+        SELECT_COLUMNS
+    ))
+    .bind(id)
+    .bind(MAX_LINEAGE_DEPTH)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+/// Every re-run descending from `id`, oldest first. Each row's
+/// `parent_execution_id` places it in the tree.
+pub async fn descendants(state: &AppState, id: &str) -> Result<Vec<Execution>> {
+    Ok(sqlx::query_as::<_, Execution>(&format!(
+        "WITH RECURSIVE tree (id, depth) AS ( \
+             SELECT id, 1 FROM executions WHERE parent_execution_id = $1 \
+             UNION ALL \
+             SELECT e.id, tree.depth + 1 FROM executions e JOIN tree ON e.parent_execution_id = tree.id \
+             WHERE tree.depth < $2 \
+         ) \
+         SELECT {} FROM executions JOIN tree USING (id) ORDER BY created_at, id LIMIT $3",
+        SELECT_COLUMNS
+    ))
+    .bind(id)
+    .bind(MAX_LINEAGE_DEPTH)
+    .bind(MAX_LINEAGE_DESCENDANTS)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+pub async fn find(state: &AppState, id: &str) -> Result<Option<Execution>> {
+    Ok(sqlx::query_as::<_, Execution>(&format!(
+        "SELECT {} FROM executions WHERE id = $1",
+        SELECT_COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?)
+}
+
+/// Records a status observed from the control plane. Terminal statuses are
+/// final, so a late report cannot move a finished execution backwards. The
+/// transition into a terminal status queues completion webhooks in the same
+/// transaction, so they are never lost.
+pub async fn update_status(state: &AppState, id: &str, status: &str) -> Result<()> {
+    let terminal = is_terminal(status);
+
+    let mut tx = state.db.begin().await?;
+    let updated = sqlx::query_as::<_, Execution>(&format!(
+        "UPDATE executions SET status = $2, updated_at = NOW(), \
+         completed_at = CASE WHEN $3 THEN COALESCE(completed_at, NOW()) ELSE completed_at END \
+         WHERE id = $1 AND status <> $2 AND NOT (status = ANY($4)) RETURNING {}",
+        SELECT_COLUMNS
+    ))
+    .bind(id)
+    .bind(status)
+    .bind(terminal)
+    .bind(TERMINAL_STATUSES)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(execution) = updated else {
+        return Ok(());
+    };
+    // Only the caller whose update won sees the row, so webhooks are queued once
+    if terminal {
+        webhooks::enqueue_finished(&mut tx, &execution).await?;
+    }
+    tx.commit().await?;
+    state.execution_status.publish(id, status);
+
+    // The status is already recorded, so one failing side effect must not
+    // keep the others from running
+    if terminal {
+        if let Err(e) = quota::finish(state, &execution).await {
+            error!("Failed to release quota for execution {}: {}", id, e);
+        }
+        if let Err(e) = alerts::execution_finished(state, &execution).await {
+            error!("Failed to evaluate alerts for execution {}: {}", id, e);
+        }
+        if let Err(e) = notebooks::execution_finished(state, &execution).await {
+            error!("Failed to update notebook for execution {}: {}", id, e);
+        }
+    }
+    Ok(())
+}
+
+/// One page of executions that have not finished yet, oldest first,
+/// starting after `after`.
+pub async fn unfinished(state: &AppState, after: Option<&Cursor>, limit: i64) -> Result<Vec<Execution>> {
+    Ok(sqlx::query_as::<_, Execution>(&format!(
+        "SELECT {} FROM executions WHERE NOT (status = ANY($1))          AND ($2::TIMESTAMPTZ IS NULL OR (created_at, id) > ($2, $3))          ORDER BY created_at, id LIMIT $4",
+        SELECT_COLUMNS
+    ))
+    .bind(TERMINAL_STATUSES)
+    .bind(after.map(|cursor| cursor.created_at))
+    .bind(after.map(|cursor| cursor.id.as_str()))
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+/// Allows admins, the user who created the execution, and members of the
+/// workspace it ran in.
+pub async fn authorize(state: &AppState, claims: &Claims, execution_id: &str) -> Result<Execution> {
+    let execution = find(state, execution_id).await?.ok_or(AppError::NotFound)?;
+    check_access(state, claims, &execution).await?;
+    Ok(execution)
+}
+
+pub async fn check_access(state: &AppState, claims: &Claims, execution: &Execution) -> Result<()> {
+    require_role(state, claims, execution, WorkspaceRole::Viewer).await
+}
+
+/// Changing an execution (cancelling or re-running it) needs the `user`
+/// role in its workspace unless the caller owns it or is an admin.
+pub async fn authorize_mutation(state: &AppState, claims: &Claims, execution_id: &str) -> Result<Execution> {
+    let execution = find(state, execution_id).await?.ok_or(AppError::NotFound)?;
+    require_role(state, claims, &execution, WorkspaceRole::User).await?;
+    Ok(execution)
+}
+
+async fn require_role(state: &AppState, claims: &Claims, execution: &Execution, role: WorkspaceRole) -> Result<()> {
+    if claims.role == "admin" || execution.owner_id == claims.sub {
+        return Ok(());
+    }
+
+    match execution.workspace_id.as_deref() {
+        Some(workspace_id) => {
+            workspace::require_role(state, claims, workspace_id, role).await?;
+            Ok(())
+        }
+        None => Err(AppError::Forbidden),
+    }
+}
+
+/// Interactive control (stdin, signals) is limited to the user who started a
+/// still-running execution. Impersonators are refused because the socket
+/// bypasses the per-request audit log.
+pub async fn authorize_interactive(state: &AppState, claims: &Claims, execution_id: &str) -> Result<Execution> {
+    if claims.is_impersonation() || !claims.has_scope(scopes::EXECUTIONS_WRITE) {
+        return Err(AppError::Forbidden);
+    }
+
+    let execution = find(state, execution_id).await?.ok_or(AppError::NotFound)?;
+    if execution.owner_id != claims.sub {
+        return Err(AppError::Forbidden);
+    }
+    if is_terminal(&execution.status) {
+        return Err(AppError::Conflict("Execution is not running".to_string()));
+    }
+    Ok(execution)
+}
+
+/// Returns one page of executions, newest first, and the cursor for the next
+/// page if there is one.
+pub async fn list(state: &AppState, filter: &ListFilter) -> Result<(Vec<Execution>, Option<String>)> {
+    let mut query = QueryBuilder::<Postgres>::new(format!("SELECT {} FROM executions WHERE TRUE", SELECT_COLUMNS));
+
+    match &filter.visibility {
+        Visibility::All => {}
+        Visibility::Owner(owner_id) => {
+            query.push(" AND owner_id = ").push_bind(owner_id);
+        }
+        Visibility::Workspace(workspace_id) => {
+            query.push(" AND workspace_id = ").push_bind(workspace_id);
+        }
+    }
+    if !filter.statuses.is_empty() {
+        query.push(" AND status = ANY(").push_bind(&filter.statuses).push(")");
+    }
+    if !filter.languages.is_empty() {
+        query.push(" AND language = ANY(").push_bind(&filter.languages).push(")");
+    }
+    if let Some(created_by) = &filter.created_by {
+        query.push(" AND owner_id = ").push_bind(created_by);
+    }
+    if let Some(created_after) = filter.created_after {
+        query.push(" AND created_at >= ").push_bind(created_after);
+    }
+    if let Some(created_before) = filter.created_before {
+        query.push(" AND created_at < ").push_bind(created_before);
+    }
+    if !filter.tags.is_empty() {
+        query.push(" AND tags @> ").push_bind(&filter.tags);
+    }
+    if let Some(cursor) = &filter.cursor {
+        query
+            .push(" AND (created_at, id) < (")
+            .push_bind(cursor.created_at)
+            .push(", ")
+            .push_bind(&cursor.id)
+            .push(")");
+    }
+
+    // Fetch one extra row to learn whether another page exists
+    query
+        .push(" ORDER BY created_at DESC, id DESC LIMIT ")
+        .push_bind(filter.limit + 1);
+
+    let mut executions = query
+        .build_query_as::<Execution>()
+        .fetch_all(&state.db)
+        .await?;
+
+    let next_cursor = if executions.len() as i64 > filter.limit {
+        executions.truncate(filter.limit as usize);
+        executions.last().map(|last| {
+            Cursor {
+                created_at: last.created_at,
+                id: last.id.clone(),
+            }
+            .encode()
+        })
+    } else {
+        None
+    };
+
+    Ok((executions, next_cursor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_requests_stored_before_sealing() {
+        let stored = serde_json::json!({
+            "code": "print(1)",
+            "language": "python",
+            "version": null,
+            "environment": { "MODE": "fast" },
+            "input_files": [],
+        });
+        let kept: KeptRequest = serde_json::from_value(stored).unwrap();
+        assert_eq!(kept.request.environment["MODE"], "fast");
+        assert!(kept.sealed_environment.is_none());
+        assert!(kept.dropped_environment.is_empty());
+    }
+
+    #[test]
+    fn never_stores_the_session() {
+        let kept = KeptRequest {
+            request: CreateExecutionRequest {
+                code: "print(1)".to_string(),
+                language: "python".to_string(),
+                version: None,
+                environment: HashMap::new(),
+                input_files: Vec::new(),
+                session_id: Some("session-1".to_string()),
+            },
+            sealed_environment: Some("c2VhbGVk".to_string()),
+            dropped_environment: vec!["TOKEN".to_string()],
+        };
+        let stored = serde_json::to_value(&kept).unwrap();
+        assert!(stored.get("session_id").is_none());
+        assert_eq!(stored["sealed_environment"], "c2VhbGVk");
+        assert_eq!(stored["dropped_environment"], serde_json::json!(["TOKEN"]));
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::HashSet;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{clients::control_plane::OutputItem, logs::redaction::Redactor};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// The kinds of display data an execution can return.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MimeType {
+    Text,
+    Json,
+    Png,
+    Html,
+    Csv,
+}
+
+impl MimeType {
+    /// Parameters such as `; charset=utf-8` are ignored.
+    pub fn parse(mime_type: &str) -> Option<Self> {
+        let essence = mime_type.split(';').next().unwrap_or_default().trim().to_lowercase();
+        match essence.as_str() {
+            "text/plain" => Some(Self::Text),
+            "application/json" => Some(Self::Json),
+            "image/png" => Some(Self::Png),
+            "text/html" => Some(Self::Html),
+            "text/csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Text => "text/plain",
+            Self::Json => "application/json",
+            Self::Png => "image/png",
+            Self::Html => "text/html",
+            Self::Csv => "text/csv",
+        }
+    }
+}
+
+/// Why an item was left out of a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OmittedReason {
+    /// Larger than `EXECUTION_MAX_OUTPUT_ITEM_BYTES`
+    TooLarge,
+    /// Past `EXECUTION_MAX_OUTPUT_BYTES` for the execution
+    OutputLimit,
+    Unsupported,
+    /// The data does not match its MIME type
+    Invalid,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Omitted {
+    pub reason: OmittedReason,
+    pub bytes: usize,
+}
+
+/// One output item as the frontend renders it. `data` is a string for text
+/// types, any JSON value for `application/json`, and base64 for images.
+#[derive(Debug, Clone, Serialize)]
+pub struct RichOutput {
+    pub mime_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub omitted: Option<Omitted>,
+}
+
+impl RichOutput {
+    fn omitted(mime_type: &str, reason: OmittedReason, bytes: usize) -> Self {
+        Self {
+            mime_type: mime_type.to_string(),
+            data: None,
+            encoding: None,
+            metadata: None,
+            omitted: Some(Omitted { reason, bytes }),
+        }
+    }
+}
+
+/// Strips scripts, event handlers, styles and anything that could run in
+/// the page. Images are expected as `image/png` items rather than inlined.
+pub fn sanitize_html(html: &str) -> String {
+    ammonia::Builder::default()
+        .url_schemes(HashSet::from(["http", "https", "mailto"]))
+        .link_rel(Some("noopener noreferrer nofollow"))
+        .add_generic_attributes(["class"])
+        .clean(html)
+        .to_string()
+}
+
+/// Redacts every string in a JSON document, leaving its structure intact.
+fn redact_json(value: Value, redactor: &Redactor) -> Value {
+    match value {
+        Value::String(text) => Value::String(redactor.redact(&text).into_owned()),
+        Value::Array(items) => Value::Array(items.into_iter().map(|item| redact_json(item, redactor)).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key, redact_json(value, redactor)))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+/// Renders one item, returning its data and how many bytes it adds to the
+/// response.
+fn render_data(mime_type: MimeType, data: &[u8], redactor: &Redactor) -> Result<(Value, usize), OmittedReason> {
+    if mime_type == MimeType::Png {
+        if !data.starts_with(PNG_SIGNATURE) {
+            return Err(OmittedReason::Invalid);
+        }
+        let encoded = STANDARD.encode(data);
+        let bytes = encoded.len();
+        return Ok((Value::String(encoded), bytes));
+    }
+
+    let text = std::str::from_utf8(data).map_err(|_| OmittedReason::Invalid)?;
+    let rendered = match mime_type {
+        MimeType::Json => {
+            let value = serde_json::from_str(text).map_err(|_| OmittedReason::Invalid)?;
+            return Ok((redact_json(value, redactor), text.len()));
+        }
+        // Redacted first; masking only ever inserts plain text
+        MimeType::Html => sanitize_html(&redactor.redact(text)),
+        _ => redactor.redact(text).into_owned(),
+    };
+    let bytes = rendered.len();
+    Ok((Value::String(rendered), bytes))
+}
+
+/// Turns the control plane's output items into what the API returns,
+/// redacted and within `EXECUTION_MAX_OUTPUT_ITEM_BYTES` per item and
+/// `EXECUTION_MAX_OUTPUT_BYTES` in total. Executions that only produced
+/// plain output get it as a single `text/plain` item.
+pub fn render(
+    max_item_bytes: usize,
+    max_bytes: usize,
+    items: &[OutputItem],
+    plain_output: Option<&str>,
+    redactor: &Redactor,
+) -> Vec<RichOutput> {
+    let fallback;
+    let items = match plain_output {
+        Some(output) if items.is_empty() && !output.is_empty() => {
+            fallback = [OutputItem {
+                mime_type: MimeType::Text.as_str().to_string(),
+                data: output.as_bytes().to_vec(),
+                metadata: None,
+            }];
+            &fallback[..]
+        }
+        _ => items,
+    };
+
+    let mut remaining = max_bytes;
+    items
+        .iter()
+        .map(|item| {
+            let Some(mime_type) = MimeType::parse(&item.mime_type) else {
+                return RichOutput::omitted(&item.mime_type, OmittedReason::Unsupported, item.data.len());
+            };
+            if item.data.len() > max_item_bytes {
+                return RichOutput::omitted(mime_type.as_str(), OmittedReason::TooLarge, item.data.len());
+            }
+
+            let (data, bytes) = match render_data(mime_type, &item.data, redactor) {
+                Ok(rendered) => rendered,
+                Err(reason) => return RichOutput::omitted(mime_type.as_str(), reason, item.data.len()),
+            };
+            if bytes > remaining {
+                return RichOutput::omitted(mime_type.as_str(), OmittedReason::OutputLimit, item.data.len());
+            }
+            remaining -= bytes;
+
+            RichOutput {
+                mime_type: mime_type.as_str().to_string(),
+                data: Some(data),
+                encoding: (mime_type == MimeType::Png).then_some("base64"),
+                metadata: item
+                    .metadata
+                    .as_deref()
+                    .and_then(|metadata| serde_json::from_str::<Value>(metadata).ok())
+                    .filter(Value::is_object),
+                omitted: None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    fn item(mime_type: &str, data: &[u8]) -> OutputItem {
+        OutputItem {
+            mime_type: mime_type.to_string(),
+            data: data.to_vec(),
+            metadata: None,
+        }
+    }
+
+    fn render_items(max_item_bytes: usize, max_bytes: usize, items: &[OutputItem]) -> Vec<RichOutput> {
+        render(max_item_bytes, max_bytes, items, None, &Redactor::with_values(&["hunter2-secret"]))
+    }
+
+    fn omitted(output: &RichOutput) -> Option<OmittedReason> {
+        output.omitted.as_ref().map(|omitted| omitted.reason)
+    }
+
+    #[test]
+    fn strips_scripts_and_event_handlers() {
+        let html = sanitize_html(
+            r#"<p class="x" onclick="steal()">hi</p><script>alert(1)</script><img src=x onerror="steal()">"#,
+        );
+        assert!(html.contains(r#"<p class="x">hi</p>"#), "{}", html);
+        assert!(!html.contains("script"), "{}", html);
+        assert!(!html.contains("onclick"), "{}", html);
+        assert!(!html.contains("onerror"), "{}", html);
+    }
+
+    #[test]
+    fn strips_javascript_links() {
+        let html = sanitize_html(r#"<a href="javascript:steal()">a</a><a href="https://example.com">b</a>"#);
+        assert!(!html.contains("javascript"), "{}", html);
+        assert!(html.contains(r#"href="https://example.com""#), "{}", html);
+        assert!(html.contains("noopener"), "{}", html);
+    }
+
+    #[test]
+    fn renders_each_type() {
+        let outputs = render_items(
+            1024,
+            4096,
+            &[
+                item("text/plain; charset=utf-8", b"token hunter2-secret"),
+                item("application/json", br#"{"password": "hunter2-secret", "n": 1}"#),
+                item("text/html", b"<b>hunter2-secret</b><script>x</script>"),
+                item("image/png", PNG),
+            ],
+        );
+        assert_eq!(outputs[0].data, Some(json!("token [REDACTED]")));
+        assert_eq!(outputs[1].data, Some(json!({"password": "[REDACTED]", "n": 1})));
+        assert_eq!(outputs[2].data, Some(json!("<b>[REDACTED]</b>")));
+        assert_eq!(outputs[3].data, Some(json!(STANDARD.encode(PNG))));
+        assert_eq!(outputs[3].encoding, Some("base64"));
+    }
+
+    #[test]
+    fn rejects_invalid_data() {
+        let outputs = render_items(
+            1024,
+            4096,
+            &[
+                item("image/png", b"GIF89a not a png"),
+                item("application/json", b"{not json"),
+                item("text/plain", b"\xff\xfe"),
+                item("application/x-shockwave-flash", b"CWS"),
+            ],
+        );
+        assert_eq!(omitted(&outputs[0]), Some(OmittedReason::Invalid));
+        assert_eq!(omitted(&outputs[1]), Some(OmittedReason::Invalid));
+        assert_eq!(omitted(&outputs[2]), Some(OmittedReason::Invalid));
+        assert_eq!(omitted(&outputs[3]), Some(OmittedReason::Unsupported));
+        assert_eq!(outputs[3].mime_type, "application/x-shockwave-flash");
+    }
+
+    #[test]
+    fn enforces_item_and_total_limits() {
+        let outputs = render_items(
+            10,
+            15,
+            &[
+                item("text/plain", b"0123456789a"),
+                item("text/plain", b"0123456789"),
+                item("text/plain", b"012345"),
+                item("text/plain", b"01234"),
+            ],
+        );
+        assert_eq!(omitted(&outputs[0]), Some(OmittedReason::TooLarge));
+        assert_eq!(outputs[0].omitted.as_ref().map(|omitted| omitted.bytes), Some(11));
+        assert_eq!(omitted(&outputs[1]), None);
+        assert_eq!(omitted(&outputs[2]), Some(OmittedReason::OutputLimit));
+        assert_eq!(omitted(&outputs[3]), None);
+    }
+
+    #[test]
+    fn falls_back_to_plain_output() {
+        let redactor = Redactor::with_values(&[]);
+        let outputs = render(1024, 4096, &[], Some("done"), &redactor);
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].mime_type, "text/plain");
+        assert_eq!(outputs[0].data, Some(json!("done")));
+
+        assert!(render(1024, 4096, &[], Some(""), &redactor).is_empty());
+    }
+}
@@ -0,0 +1,375 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{AppError, FieldError, Result},
+    AppState,
+};
+
+const MAX_ENV_NAME_LEN: usize = 128;
+
+/// Variables the sandbox runtime relies on or that change how binaries load.
+const RESERVED_ENV_NAMES: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "SHELL",
+    "PWD",
+    "HOSTNAME",
+    "TMPDIR",
+    "LD_PRELOAD",
+    "LD_LIBRARY_PATH",
+    "LD_AUDIT",
+];
+const RESERVED_ENV_PREFIXES: &[&str] = &["LD_", "DYLD_", "HERMES_"];
+
+/// Limits applied to a new execution.
+#[derive(Debug, Clone, Serialize)]
+pub struct Policy {
+    /// Language -> allowed versions; an empty list accepts any version.
+    pub languages: BTreeMap<String, Vec<String>>,
+    pub max_code_bytes: usize,
+    pub max_env_vars: usize,
+    pub max_env_value_bytes: usize,
+}
+
+/// A workspace's stored overrides. Language entries are either `python`
+/// (every globally allowed version) or `python:3.12` (that version only).
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct WorkspacePolicy {
+    pub workspace_id: String,
+    pub allowed_languages: Option<Vec<String>>,
+    pub max_code_bytes: Option<i64>,
+    pub max_env_vars: Option<i32>,
+    pub max_env_value_bytes: Option<i64>,
+    pub updated_by: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PolicyOverrides {
+    pub allowed_languages: Option<Vec<String>>,
+    pub max_code_bytes: Option<i64>,
+    pub max_env_vars: Option<i32>,
+    pub max_env_value_bytes: Option<i64>,
+}
+
+/// The parts of an execution request the policy looks at.
+pub struct ExecutionInput<'a> {
+    pub language: &'a str,
+    pub version: Option<&'a str>,
+    pub code: &'a str,
+    pub environment: &'a HashMap<String, String>,
+}
+
+const SELECT_COLUMNS: &str =
+    "workspace_id, allowed_languages, max_code_bytes, max_env_vars, max_env_value_bytes, updated_by, updated_at";
+
+pub fn global(state: &AppState) -> Policy {
+    Policy {
+        languages: state.config.execution_languages.clone(),
+        max_code_bytes: state.config.execution_max_code_bytes,
+        max_env_vars: state.config.execution_max_env_vars,
+        max_env_value_bytes: state.config.execution_max_env_value_bytes,
+    }
+}
+
+pub async fn load(state: &AppState, workspace_id: &str) -> Result<Option<WorkspacePolicy>> {
+    Ok(sqlx::query_as::<_, WorkspacePolicy>(&format!(
+        "SELECT {} FROM workspace_execution_policies WHERE workspace_id = $1",
+        SELECT_COLUMNS
+    ))
+    .bind(workspace_id)
+    .fetch_optional(&state.db)
+    .await?)
+}
+
+/// Returns the limits for an execution in `workspace_id`: the global policy,
+/// narrowed by the workspace's overrides.
+pub async fn effective(state: &AppState, workspace_id: Option<&str>) -> Result<Policy> {
+    let policy = global(state);
+    let Some(workspace_id) = workspace_id else {
+        return Ok(policy);
+    };
+
+    Ok(match load(state, workspace_id).await? {
+        Some(overrides) => narrow(policy, &overrides),
+        None => policy,
+    })
+}
+
+fn clamp<T: TryInto<usize>>(global: usize, value: Option<T>) -> usize {
+    value
+        .and_then(|value| value.try_into().ok())
+        .map_or(global, |value| value.min(global))
+}
+
+fn narrow(mut policy: Policy, overrides: &WorkspacePolicy) -> Policy {
+    if let Some(entries) = &overrides.allowed_languages {
+        let mut languages = BTreeMap::new();
+        for entry in entries {
+            let (language, version) = match entry.split_once(':') {
+                Some((language, version)) => (language, Some(version)),
+                None => (entry.as_str(), None),
+            };
+            let Some(allowed) = policy.languages.get(language) else {
+                continue;
+            };
+
+            let versions: &mut Vec<String> = languages.entry(language.to_string()).or_default();
+            match version {
+                // A bare language keeps every globally allowed version
+                None => *versions = allowed.clone(),
+                Some(version) if allowed.is_empty() || allowed.iter().any(|v| v == version) => {
+                    versions.push(version.to_string())
+                }
+                Some(_) => {}
+            }
+        }
+        // A language whose requested versions were all disallowed is dropped
+        languages.retain(|language, versions| !versions.is_empty() || policy.languages[language].is_empty());
+        policy.languages = languages;
+    }
+
+    policy.max_code_bytes = clamp(policy.max_code_bytes, overrides.max_code_bytes);
+    policy.max_env_vars = clamp(policy.max_env_vars, overrides.max_env_vars);
+    policy.max_env_value_bytes = clamp(policy.max_env_value_bytes, overrides.max_env_value_bytes);
+    policy
+}
+
+fn is_reserved(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    RESERVED_ENV_NAMES.contains(&upper.as_str())
+        || RESERVED_ENV_PREFIXES.iter().any(|prefix| upper.starts_with(prefix))
+}
+
+fn is_valid_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Checks a request against `policy`, reporting every problem at once.
+pub fn validate(policy: &Policy, input: &ExecutionInput<'_>) -> Result<()> {
+    let mut errors = Vec::new();
+
+    match policy.languages.get(input.language) {
+        None => errors.push(FieldError::new(
+            "language",
+            format!(
+                "Unsupported language; allowed: {}",
+                policy.languages.keys().cloned().collect::<Vec<_>>().join(", ")
+            ),
+        )),
+        Some(versions) => {
+            if let Some(version) = input.version {
+                if !versions.is_empty() && !versions.iter().any(|v| v == version) {
+                    errors.push(FieldError::new(
+                        "version",
+                        format!("Unsupported version; allowed: {}", versions.join(", ")),
+                    ));
+                }
+            }
+        }
+    }
+
+    if input.code.trim().is_empty() {
+        errors.push(FieldError::new("code", "Code is required"));
+    } else if input.code.len() > policy.max_code_bytes {
+        errors.push(FieldError::new(
+            "code",
+            format!("Code exceeds {} bytes", policy.max_code_bytes),
+        ));
+    }
+
+    if input.environment.len() > policy.max_env_vars {
+        errors.push(FieldError::new(
+            "environment",
+            format!("At most {} variables are allowed", policy.max_env_vars),
+        ));
+    }
+
+    let mut names: Vec<&String> = input.environment.keys().collect();
+    names.sort();
+    for name in names {
+        let field = format!("environment.{}", name);
+        let value = &input.environment[name];
+
+        if name.len() > MAX_ENV_NAME_LEN || !is_valid_env_name(name) {
+            errors.push(FieldError::new(
+                field,
+                "Names must start with a letter or underscore and contain only letters, digits and underscores",
+            ));
+        } else if is_reserved(name) {
+            errors.push(FieldError::new(field, "This variable name is reserved"));
+        } else if value.len() > policy.max_env_value_bytes {
+            errors.push(FieldError::new(
+                field,
+                format!("Value exceeds {} bytes", policy.max_env_value_bytes),
+            ));
+        } else if value.contains('\0') {
+            errors.push(FieldError::new(field, "Value must not contain NUL bytes"));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Validation(errors))
+    }
+}
+
+/// Checks environment values again once secret references are substituted,
+/// since a short reference can expand into a value over the limit. Errors
+/// never include the values themselves.
+pub fn validate_resolved(policy: &Policy, environment: &HashMap<String, String>) -> Result<()> {
+    let mut names: Vec<&String> = environment.keys().collect();
+    names.sort();
+    let errors: Vec<FieldError> = names
+        .into_iter()
+        .filter_map(|name| {
+            let value = &environment[name];
+            let field = format!("environment.{}", name);
+            if value.len() > policy.max_env_value_bytes {
+                Some(FieldError::new(
+                    field,
+                    format!("Value exceeds {} bytes once secrets are substituted", policy.max_env_value_bytes),
+                ))
+            } else if value.contains('\0') {
+                Some(FieldError::new(field, "Value must not contain NUL bytes once secrets are substituted"))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Validation(errors))
+    }
+}
+
+/// Stores a workspace's overrides after checking they only tighten the
+/// global policy.
+pub async fn save(
+    state: &AppState,
+    workspace_id: &str,
+    overrides: &PolicyOverrides,
+    updated_by: &str,
+) -> Result<WorkspacePolicy> {
+    let global = global(state);
+    let mut errors = Vec::new();
+
+    for entry in overrides.allowed_languages.iter().flatten() {
+        let (language, version) = match entry.split_once(':') {
+            Some((language, version)) => (language, Some(version)),
+            None => (entry.as_str(), None),
+        };
+        let allowed = match global.languages.get(language) {
+            Some(versions) => match version {
+                Some(version) => versions.is_empty() || versions.iter().any(|v| v == version),
+                None => true,
+            },
+            None => false,
+        };
+        if !allowed {
+            errors.push(FieldError::new(
+                "allowed_languages",
+                format!("{} is not allowed by the platform policy", entry),
+            ));
+        }
+    }
+
+    let limits = [
+        ("max_code_bytes", overrides.max_code_bytes, global.max_code_bytes),
+        ("max_env_vars", overrides.max_env_vars.map(i64::from), global.max_env_vars),
+        ("max_env_value_bytes", overrides.max_env_value_bytes, global.max_env_value_bytes),
+    ];
+    for (field, value, maximum) in limits {
+        if let Some(value) = value {
+            if value < 1 || value as u64 > maximum as u64 {
+                errors.push(FieldError::new(field, format!("Must be between 1 and {}", maximum)));
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    Ok(sqlx::query_as::<_, WorkspacePolicy>(&format!(
+        "INSERT INTO workspace_execution_policies \
+         (workspace_id, allowed_languages, max_code_bytes, max_env_vars, max_env_value_bytes, updated_by) \
+         VALUES ($1, $2, $3, $4, $5, $6) \
+         ON CONFLICT (workspace_id) DO UPDATE SET \
+         allowed_languages = EXCLUDED.allowed_languages, max_code_bytes = EXCLUDED.max_code_bytes, \
+         max_env_vars = EXCLUDED.max_env_vars, max_env_value_bytes = EXCLUDED.max_env_value_bytes, \
+         updated_by = EXCLUDED.updated_by, updated_at = NOW() \
+         RETURNING {}",
+        SELECT_COLUMNS
+    ))
+    .bind(workspace_id)
+    .bind(&overrides.allowed_languages)
+    .bind(overrides.max_code_bytes)
+    .bind(overrides.max_env_vars)
+    .bind(overrides.max_env_value_bytes)
+    .bind(updated_by)
+    .fetch_one(&state.db)
+    .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> Policy {
+        Policy {
+            languages: BTreeMap::from([("python".to_string(), vec!["3.12".to_string()])]),
+            max_code_bytes: 1024,
+            max_env_vars: 4,
+            max_env_value_bytes: 8,
+        }
+    }
+
+    fn fields(result: Result<()>) -> Vec<String> {
+        match result {
+            Err(AppError::Validation(errors)) => errors.into_iter().map(|error| error.field).collect(),
+            other => panic!("expected validation errors, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn resolved_values_are_size_checked() {
+        let environment = HashMap::from([
+            ("SHORT".to_string(), "ok".to_string()),
+            ("TOKEN".to_string(), "much-too-long".to_string()),
+        ]);
+        assert_eq!(fields(validate_resolved(&policy(), &environment)), vec!["environment.TOKEN"]);
+    }
+
+    #[test]
+    fn resolved_errors_do_not_echo_values() {
+        let environment = HashMap::from([("TOKEN".to_string(), "s3cret-value".to_string())]);
+        let Err(AppError::Validation(errors)) = validate_resolved(&policy(), &environment) else {
+            panic!("expected a validation error");
+        };
+        assert!(errors.iter().all(|error| !error.message.contains("s3cret")));
+    }
+
+    #[test]
+    fn references_pass_the_unresolved_check() {
+        let environment = HashMap::from([("TOKEN".to_string(), "{{secrets.A}}".to_string())]);
+        let mut policy = policy();
+        policy.max_env_value_bytes = 16;
+        let input = ExecutionInput {
+            language: "python",
+            version: Some("3.12"),
+            code: "print(1)",
+            environment: &environment,
+        };
+        assert!(validate(&policy, &input).is_ok());
+    }
+}
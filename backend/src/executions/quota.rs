@@ -0,0 +1,293 @@
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    executions::Execution,
+    AppState,
+};
+
+/// Running entries older than this are assumed leaked (e.g. the BFF never
+/// saw the execution finish) and stop counting.
+const STALE_RUNNING_SECS: i64 = 24 * 3600;
+const HOURLY_TTL_SECS: i64 = 3600;
+const RUNTIME_TTL_SECS: i64 = 2 * 24 * 3600;
+/// No way to know when a slot frees up; suggest a short retry.
+const CONCURRENT_RETRY_AFTER_SECS: u64 = 10;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub concurrent: u64,
+    pub per_hour: u64,
+    pub runtime_seconds_per_day: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    User,
+    Workspace,
+}
+
+impl Scope {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::Workspace => "workspace",
+        }
+    }
+
+    fn limits(self, state: &AppState) -> Limits {
+        let config = &state.config;
+        match self {
+            Self::User => Limits {
+                concurrent: config.quota_user_concurrent,
+                per_hour: config.quota_user_per_hour,
+                runtime_seconds_per_day: config.quota_user_runtime_seconds_per_day,
+            },
+            Self::Workspace => Limits {
+                concurrent: config.quota_workspace_concurrent,
+                per_hour: config.quota_workspace_per_hour,
+                runtime_seconds_per_day: config.quota_workspace_runtime_seconds_per_day,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Counter {
+    pub used: u64,
+    /// `0` means unlimited.
+    pub limit: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Usage {
+    pub concurrent: Counter,
+    pub executions_this_hour: Counter,
+    /// Wall-clock seconds from submission to completion. The control plane
+    /// does not report CPU time.
+    pub runtime_seconds_today: Counter,
+}
+
+/// A slot taken for an execution that has not been created yet.
+#[derive(Debug)]
+pub struct Reservation {
+    token: String,
+    user_id: String,
+    workspace_id: Option<String>,
+}
+
+fn running_key(scope: Scope, id: &str) -> String {
+    format!("quota:running:{}:{}", scope.as_str(), id)
+}
+
+fn hourly_key(scope: Scope, id: &str) -> String {
+    format!("quota:hourly:{}:{}:{}", scope.as_str(), id, Utc::now().format("%Y%m%d%H"))
+}
+
+fn runtime_key(scope: Scope, id: &str) -> String {
+    format!("quota:runtime:{}:{}:{}", scope.as_str(), id, Utc::now().format("%Y%m%d"))
+}
+
+fn secs_until_midnight() -> u64 {
+    let now = Utc::now();
+    let midnight = (now.date_naive() + Duration::days(1))
+        .and_hms_opt(0, 0, 0)
+        .expect("midnight is a valid time")
+        .and_utc();
+    (midnight - now).num_seconds().max(1) as u64
+}
+
+fn scopes<'a>(user_id: &'a str, workspace_id: Option<&'a str>) -> Vec<(Scope, &'a str)> {
+    let mut scopes = vec![(Scope::User, user_id)];
+    if let Some(workspace_id) = workspace_id {
+        scopes.push((Scope::Workspace, workspace_id));
+    }
+    scopes
+}
+
+/// Checks every quota for the user and workspace and, only if all pass,
+/// takes a running slot and counts the execution towards this hour.
+const RESERVE_SCRIPT: &str = r"
+local n = #KEYS / 3
+local now, cutoff, token, hourly_ttl = tonumber(ARGV[1]), tonumber(ARGV[2]), ARGV[3], tonumber(ARGV[4])
+for i = 0, n - 1 do
+    local running, hourly, runtime = KEYS[i * 3 + 1], KEYS[i * 3 + 2], KEYS[i * 3 + 3]
+    local concurrent_limit = tonumber(ARGV[5 + i * 3])
+    local hourly_limit = tonumber(ARGV[6 + i * 3])
+    local runtime_limit = tonumber(ARGV[7 + i * 3])
+
+    redis.call('ZREMRANGEBYSCORE', running, '-inf', cutoff)
+    local used = redis.call('ZCARD', running)
+    if concurrent_limit > 0 and used >= concurrent_limit then
+        return {i, 'concurrent', used, concurrent_limit, -1}
+    end
+    used = tonumber(redis.call('GET', hourly) or '0')
+    if hourly_limit > 0 and used >= hourly_limit then
+        return {i, 'executions_per_hour', used, hourly_limit, redis.call('TTL', hourly)}
+    end
+    used = tonumber(redis.call('GET', runtime) or '0')
+    if runtime_limit > 0 and used >= runtime_limit then
+        return {i, 'runtime_seconds_per_day', used, runtime_limit, -2}
+    end
+end
+for i = 0, n - 1 do
+    redis.call('ZADD', KEYS[i * 3 + 1], now, token)
+    redis.call('INCR', KEYS[i * 3 + 2])
+    redis.call('EXPIRE', KEYS[i * 3 + 2], hourly_ttl)
+end
+return {}
+";
+
+pub async fn reserve(state: &AppState, user_id: &str, workspace_id: Option<&str>) -> Result<Reservation> {
+    let scopes = scopes(user_id, workspace_id);
+    let token = format!("reservation:{}", Uuid::new_v4());
+    let now = Utc::now().timestamp();
+
+    let script = redis::Script::new(RESERVE_SCRIPT);
+    let mut invocation = script.prepare_invoke();
+    for (scope, id) in &scopes {
+        invocation
+            .key(running_key(*scope, id))
+            .key(hourly_key(*scope, id))
+            .key(runtime_key(*scope, id));
+    }
+    invocation
+        .arg(now)
+        .arg(now - STALE_RUNNING_SECS)
+        .arg(&token)
+        .arg(HOURLY_TTL_SECS);
+    for (scope, _) in &scopes {
+        let limits = scope.limits(state);
+        invocation
+            .arg(limits.concurrent)
+            .arg(limits.per_hour)
+            .arg(limits.runtime_seconds_per_day);
+    }
+
+    let mut conn = state.redis_client.as_ref().clone();
+    let rejected: Vec<redis::Value> = invocation.invoke_async(&mut conn).await?;
+
+    if let [index, quota, used, limit, retry_after] = rejected.as_slice() {
+        let int = |value: &redis::Value| redis::from_redis_value::<i64>(value).unwrap_or_default();
+        let scope = scopes
+            .get(int(index) as usize)
+            .map_or(Scope::User, |(scope, _)| *scope);
+        let retry_after = match int(retry_after) {
+            -1 => CONCURRENT_RETRY_AFTER_SECS,
+            -2 => secs_until_midnight(),
+            secs => secs.max(1) as u64,
+        };
+
+        return Err(AppError::QuotaExceeded {
+            scope: scope.as_str(),
+            quota: redis::from_redis_value(quota).unwrap_or_default(),
+            used: int(used).max(0) as u64,
+            limit: int(limit).max(0) as u64,
+            retry_after,
+        });
+    }
+
+    Ok(Reservation {
+        token,
+        user_id: user_id.to_string(),
+        workspace_id: workspace_id.map(str::to_string),
+    })
+}
+
+/// Swaps the reservation's placeholder for the real execution id, so the
+/// slot is freed when that execution finishes.
+pub async fn commit(state: &AppState, reservation: Reservation, execution_id: &str) -> Result<()> {
+    let now = Utc::now().timestamp();
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    for (scope, id) in scopes(&reservation.user_id, reservation.workspace_id.as_deref()) {
+        let key = running_key(scope, id);
+        pipe.cmd("ZREM").arg(&key).arg(&reservation.token).ignore();
+        pipe.cmd("ZADD").arg(&key).arg(now).arg(execution_id).ignore();
+    }
+
+    let mut conn = state.redis_client.as_ref().clone();
+    pipe.query_async::<_, ()>(&mut conn).await?;
+    Ok(())
+}
+
+/// Gives the running slot back when the execution could not be created. The
+/// attempt still counts towards the hourly quota.
+pub async fn release(state: &AppState, reservation: Reservation) -> Result<()> {
+    let mut pipe = redis::pipe();
+    for (scope, id) in scopes(&reservation.user_id, reservation.workspace_id.as_deref()) {
+        pipe.cmd("ZREM").arg(running_key(scope, id)).arg(&reservation.token).ignore();
+    }
+
+    let mut conn = state.redis_client.as_ref().clone();
+    pipe.query_async::<_, ()>(&mut conn).await?;
+    Ok(())
+}
+
+/// Frees the running slot of an execution that started but was never
+/// recorded. Nothing would see it finish, so its runtime goes uncharged.
+pub async fn abandon(state: &AppState, user_id: &str, workspace_id: Option<&str>, execution_id: &str) -> Result<()> {
+    let mut pipe = redis::pipe();
+    for (scope, id) in scopes(user_id, workspace_id) {
+        pipe.cmd("ZREM").arg(running_key(scope, id)).arg(execution_id).ignore();
+    }
+
+    let mut conn = state.redis_client.as_ref().clone();
+    pipe.query_async::<_, ()>(&mut conn).await?;
+    Ok(())
+}
+
+/// Frees the execution's running slot and charges its runtime to today.
+pub async fn finish(state: &AppState, execution: &Execution) -> Result<()> {
+    let finished_at = execution.completed_at.unwrap_or_else(Utc::now);
+    let runtime_secs = (finished_at - execution.created_at).num_seconds().max(1);
+
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    for (scope, id) in scopes(&execution.owner_id, execution.workspace_id.as_deref()) {
+        let runtime = runtime_key(scope, id);
+        pipe.cmd("ZREM").arg(running_key(scope, id)).arg(&execution.id).ignore();
+        pipe.cmd("INCRBY").arg(&runtime).arg(runtime_secs).ignore();
+        pipe.cmd("EXPIRE").arg(&runtime).arg(RUNTIME_TTL_SECS).ignore();
+    }
+
+    let mut conn = state.redis_client.as_ref().clone();
+    pipe.query_async::<_, ()>(&mut conn).await?;
+    Ok(())
+}
+
+pub async fn usage(state: &AppState, scope: Scope, id: &str) -> Result<Usage> {
+    let running = running_key(scope, id);
+    let cutoff = Utc::now().timestamp() - STALE_RUNNING_SECS;
+
+    let mut conn = state.redis_client.as_ref().clone();
+    let (concurrent, hourly, runtime): (u64, Option<u64>, Option<u64>) = redis::pipe()
+        .cmd("ZCOUNT")
+        .arg(&running)
+        .arg(cutoff)
+        .arg("+inf")
+        .cmd("GET")
+        .arg(hourly_key(scope, id))
+        .cmd("GET")
+        .arg(runtime_key(scope, id))
+        .query_async(&mut conn)
+        .await?;
+
+    let limits = scope.limits(state);
+    Ok(Usage {
+        concurrent: Counter {
+            used: concurrent,
+            limit: limits.concurrent,
+        },
+        executions_this_hour: Counter {
+            used: hourly.unwrap_or_default(),
+            limit: limits.per_hour,
+        },
+        runtime_seconds_today: Counter {
+            used: runtime.unwrap_or_default(),
+            limit: limits.runtime_seconds_per_day,
+        },
+    })
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tracing::warn;
+
+use crate::{executions, AppState};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Shares one upstream status watch per execution between every request
+/// long-polling on it in this process.
+#[derive(Debug, Default)]
+pub struct StatusHub {
+    watches: Mutex<HashMap<String, watch::Sender<String>>>,
+}
+
+impl StatusHub {
+    /// Subscribes to status changes, starting an upstream watch if this is
+    /// the first waiter. `current` seeds a newly started watch.
+    pub fn subscribe(&self, state: &AppState, execution_id: &str, current: &str) -> watch::Receiver<String> {
+        let mut watches = self.watches.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sender) = watches.get(execution_id) {
+            return sender.subscribe();
+        }
+
+        let (sender, receiver) = watch::channel(current.to_string());
+        watches.insert(execution_id.to_string(), sender.clone());
+        tokio::spawn(poll(state.clone(), execution_id.to_string(), sender));
+        receiver
+    }
+
+    /// Passes on a status learned elsewhere (the background watcher, a
+    /// cancel) so waiters do not wait for the next poll.
+    pub fn publish(&self, execution_id: &str, status: &str) {
+        let watches = self.watches.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sender) = watches.get(execution_id) {
+            set(sender, status);
+        }
+    }
+
+    /// Drops the watch once nobody is waiting or the execution has finished.
+    /// Checked under the lock so a concurrent `subscribe` cannot attach to a
+    /// watch that is about to stop.
+    fn release(&self, execution_id: &str, sender: &watch::Sender<String>) -> bool {
+        let mut watches = self.watches.lock().unwrap_or_else(|e| e.into_inner());
+        let finished = executions::is_terminal(&sender.borrow());
+        if finished || sender.receiver_count() == 0 {
+            watches.remove(execution_id);
+            return true;
+        }
+        false
+    }
+}
+
+fn set(sender: &watch::Sender<String>, status: &str) {
+    sender.send_if_modified(|current| {
+        if current == status {
+            return false;
+        }
+        *current = status.to_string();
+        true
+    });
+}
+
+async fn poll(state: AppState, execution_id: String, sender: watch::Sender<String>) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        if state.execution_status.release(&execution_id, &sender) {
+            break;
+        }
+
+        match state.control_plane_client.get_execution(&execution_id).await {
+            Ok(response) => {
+                if let Err(e) = executions::update_status(&state, &execution_id, &response.status).await {
+                    warn!("Failed to record status for execution {}: {}", execution_id, e);
+                }
+                set(&sender, &response.status);
+            }
+            Err(e) => warn!("Failed to poll execution {}: {}", execution_id, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hub_with(execution_id: &str, status: &str) -> (StatusHub, watch::Sender<String>, watch::Receiver<String>) {
+        let hub = StatusHub::default();
+        let (sender, receiver) = watch::channel(status.to_string());
+        hub.watches.lock().unwrap().insert(execution_id.to_string(), sender.clone());
+        (hub, sender, receiver)
+    }
+
+    #[test]
+    fn publish_notifies_only_on_change() {
+        let (hub, _sender, mut receiver) = hub_with("exec-1", "running");
+        receiver.mark_unchanged();
+
+        hub.publish("exec-1", "running");
+        assert!(!receiver.has_changed().unwrap());
+
+        hub.publish("exec-1", "completed");
+        assert!(receiver.has_changed().unwrap());
+        assert_eq!(*receiver.borrow_and_update(), "completed");
+    }
+
+    #[test]
+    fn publish_ignores_unwatched_executions() {
+        let (hub, _sender, mut receiver) = hub_with("exec-1", "running");
+        receiver.mark_unchanged();
+        hub.publish("exec-2", "completed");
+        assert!(!receiver.has_changed().unwrap());
+    }
+
+    #[test]
+    fn keeps_watching_while_someone_waits() {
+        let (hub, sender, _receiver) = hub_with("exec-1", "running");
+        assert!(!hub.release("exec-1", &sender));
+        assert!(hub.watches.lock().unwrap().contains_key("exec-1"));
+    }
+
+    #[test]
+    fn releases_finished_or_abandoned_watches() {
+        let (hub, sender, _receiver) = hub_with("exec-1", "running");
+        set(&sender, "failed");
+        assert!(hub.release("exec-1", &sender));
+        assert!(hub.watches.lock().unwrap().is_empty());
+
+        let (hub, sender, receiver) = hub_with("exec-2", "running");
+        drop(receiver);
+        assert!(hub.release("exec-2", &sender));
+        assert!(hub.watches.lock().unwrap().is_empty());
+    }
+}
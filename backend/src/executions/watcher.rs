@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use tracing::{error, info, warn};
+
+use crate::{
+    error::AppError,
+    executions::{self, Cursor},
+    AppState,
+};
+
+const BATCH_SIZE: i64 = 200;
+
+/// Polls the control plane for executions that have not finished so status
+/// changes are recorded (and webhooks fire) even if nobody asks for them.
+/// It also keeps their output being ingested.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(state.config.execution_watch_interval_secs));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    info!("Execution watcher started");
+
+    loop {
+        interval.tick().await;
+
+        // Every unfinished execution is polled each round, however many there are
+        let mut after: Option<Cursor> = None;
+        loop {
+            let page = match executions::unfinished(&state, after.as_ref(), BATCH_SIZE).await {
+                Ok(page) => page,
+                Err(e) => {
+                    error!("Failed to load unfinished executions: {}", e);
+                    break;
+                }
+            };
+            let full = page.len() as i64 == BATCH_SIZE;
+            after = page.last().map(|execution| Cursor {
+                created_at: execution.created_at,
+                id: execution.id.clone(),
+            });
+
+            for execution in page {
+                poll(&state, &execution).await;
+            }
+            if !full {
+                break;
+            }
+        }
+    }
+}
+
+async fn poll(state: &AppState, execution: &executions::Execution) {
+    state.execution_logs.ensure(state, execution);
+    let status = match state.control_plane_client.get_execution(&execution.id).await {
+        Ok(response) => response.status,
+        // The control plane lost track of it, so it will never finish
+        Err(AppError::GrpcError(status)) if status.code() == tonic::Code::NotFound => {
+            warn!("Execution {} is unknown to the control plane; marking it lost", execution.id);
+            executions::STATUS_LOST.to_string()
+        }
+        Err(e) => {
+            warn!("Failed to poll execution {}: {}", execution.id, e);
+            return;
+        }
+    };
+    if let Err(e) = executions::update_status(state, &execution.id, &status).await {
+        error!("Failed to update execution {}: {}", execution.id, e);
+    }
+}
@@ -0,0 +1,130 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::Duration;
+use serde::Deserialize;
+
+use crate::{
+    auth::{
+        events::{self, SecurityEvent},
+        impersonation, lockout, Claims,
+    },
+    error::{AppError, Result},
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct UnlockAccountPayload {
+    pub email: Option<String>,
+    pub ip: Option<String>,
+}
+
+pub async fn unlock_account(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<UnlockAccountPayload>,
+) -> Result<impl IntoResponse> {
+    if payload.email.is_none() && payload.ip.is_none() {
+        return Err(AppError::BadRequest("email or ip is required".to_string()));
+    }
+
+    let email = payload.email.as_deref().map(lockout::normalize_email);
+    let cleared = lockout::unlock(&state, email.as_deref(), payload.ip.as_deref()).await?;
+
+    events::emit(
+        &state,
+        SecurityEvent::LoginUnlock {
+            email,
+            ip: payload.ip,
+            unlocked_by: claims.sub,
+        },
+    )
+    .await;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "cleared_keys": cleared,
+    })))
+}
+
+const DEFAULT_IMPERSONATION_MINUTES: i64 = 15;
+
+#[derive(Debug, Deserialize)]
+pub struct ImpersonatePayload {
+    pub user_id: String,
+    pub reason: String,
+    pub duration_minutes: Option<i64>,
+}
+
+pub async fn impersonate(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<ImpersonatePayload>,
+) -> Result<impl IntoResponse> {
+    let reason = payload.reason.trim();
+    if reason.is_empty() {
+        return Err(AppError::BadRequest("reason is required".to_string()));
+    }
+    if payload.user_id == claims.sub {
+        return Err(AppError::BadRequest("Cannot impersonate yourself".to_string()));
+    }
+
+    let minutes = payload
+        .duration_minutes
+        .unwrap_or(DEFAULT_IMPERSONATION_MINUTES)
+        .clamp(1, state.config.impersonation_max_minutes);
+
+    let subject = state.iam_client
+        .get_user(&payload.user_id)
+        .await?
+        .user
+        .ok_or(AppError::NotFound)?;
+
+    // Impersonation is for seeing what customers see, not for borrowing admin rights
+    if subject.role == "admin" {
+        return Err(AppError::Forbidden);
+    }
+
+    let grant = impersonation::start(&state, &claims, &subject, reason, Duration::minutes(minutes)).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "session_id": grant.session_id,
+            "access_token": grant.access_token,
+            "token_type": "Bearer",
+            "expires_in": minutes * 60,
+            "expires_at": grant.expires_at.to_rfc3339(),
+            "subject": {
+                "id": subject.id,
+                "email": subject.email,
+                "name": subject.name,
+                "role": subject.role,
+            },
+        })),
+    ))
+}
+
+pub async fn end_impersonation(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(session_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    impersonation::end(&state, &session_id, &claims.sub).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_impersonation_audit_log(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let entries = impersonation::audit_log(&state, &session_id).await?;
+
+    Ok(Json(serde_json::json!({
+        "session_id": session_id,
+        "entries": entries,
+    })))
+}
@@ -0,0 +1,130 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    alerts::{self, NewRule},
+    auth::{
+        workspace::{self, WorkspaceRole},
+        Claims,
+    },
+    error::{AppError, Result},
+    AppState,
+};
+
+const DEFAULT_NOTIFICATIONS_PAGE_SIZE: i64 = 50;
+const MAX_NOTIFICATIONS_PAGE_SIZE: i64 = 200;
+
+pub async fn list_alert_rules(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(workspace_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Viewer).await?;
+
+    let rules = alerts::rules(&state, &workspace_id).await?;
+    Ok(Json(serde_json::json!({ "rules": rules })))
+}
+
+pub async fn create_alert_rule(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(workspace_id): Path<String>,
+    Json(payload): Json<NewRule>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Admin).await?;
+
+    let rule = alerts::create_rule(&state, &workspace_id, &payload, &claims.sub).await?;
+    Ok((StatusCode::CREATED, Json(rule)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAlertRulePayload {
+    pub enabled: bool,
+}
+
+pub async fn update_alert_rule(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((workspace_id, rule_id)): Path<(String, Uuid)>,
+    Json(payload): Json<UpdateAlertRulePayload>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Admin).await?;
+
+    let rule = alerts::set_enabled(&state, &workspace_id, rule_id, payload.enabled)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(rule))
+}
+
+pub async fn delete_alert_rule(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((workspace_id, rule_id)): Path<(String, Uuid)>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Admin).await?;
+
+    if !alerts::delete_rule(&state, &workspace_id, rule_id).await? {
+        return Err(AppError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NotificationsQuery {
+    pub workspace_id: String,
+    #[serde(default)]
+    pub unread: bool,
+    /// The `next_cursor` of the previous page
+    pub cursor: Option<Uuid>,
+    pub limit: Option<i64>,
+}
+
+pub async fn list_notifications(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<NotificationsQuery>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &query.workspace_id, WorkspaceRole::Viewer).await?;
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_NOTIFICATIONS_PAGE_SIZE)
+        .clamp(1, MAX_NOTIFICATIONS_PAGE_SIZE);
+    let notifications = alerts::notifications(
+        &state,
+        &query.workspace_id,
+        &claims.sub,
+        query.unread,
+        query.cursor,
+        limit,
+    )
+    .await?;
+    let next_cursor = (notifications.len() as i64 == limit)
+        .then(|| notifications.last().map(|notification| notification.id))
+        .flatten();
+
+    Ok(Json(serde_json::json!({
+        "notifications": notifications,
+        "next_cursor": next_cursor,
+    })))
+}
+
+pub async fn mark_notification_read(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let workspace_id = alerts::notification_workspace(&state, id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Viewer).await?;
+
+    alerts::mark_read(&state, id, &claims.sub).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,138 @@
+use axum::{
+    body::Body,
+    extract::{Multipart, Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    artifacts::{self, Download},
+    auth::Claims,
+    error::{AppError, Result},
+    executions, AppState,
+};
+
+/// Accepts `multipart/form-data` with one or more file parts and streams
+/// each one to the artifact store.
+pub async fn upload_artifacts(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse> {
+    let mut uploaded = Vec::new();
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Invalid multipart body: {}", e)))?
+    {
+        // Only file parts are artifacts
+        let Some(name) = field.file_name().map(str::to_string) else {
+            continue;
+        };
+        if uploaded.len() >= state.config.artifact_max_files_per_upload {
+            return Err(AppError::PayloadTooLarge(format!(
+                "At most {} files per upload",
+                state.config.artifact_max_files_per_upload
+            )));
+        }
+
+        uploaded.push(artifacts::upload(&state, &claims.sub, &name, field).await?);
+    }
+
+    if uploaded.is_empty() {
+        return Err(AppError::BadRequest("No files in upload".to_string()));
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({ "artifacts": uploaded })),
+    ))
+}
+
+pub async fn list_artifacts(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(execution_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    executions::authorize(&state, &claims, &execution_id).await?;
+
+    let inputs = artifacts::inputs(&state, &execution_id).await?;
+    let outputs = artifacts::outputs(&state, &execution_id).await?;
+
+    Ok(Json(serde_json::json!({
+        "inputs": inputs,
+        "outputs": outputs,
+    })))
+}
+
+fn range_header(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::RANGE).and_then(|value| value.to_str().ok())
+}
+
+/// Builds a download response. Files are always served as attachments with
+/// sniffing disabled, since their contents are user-controlled.
+fn download_response(download: Download, name: &str, content_type: &str) -> Result<Response> {
+    let (status, length) = match &download.range {
+        Some(range) => (StatusCode::PARTIAL_CONTENT, range.end - range.start),
+        None => (StatusCode::OK, download.size),
+    };
+
+    let mut response = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_LENGTH, length)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff");
+    if let Some(range) = &download.range {
+        response = response.header(
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", range.start, range.end - 1, download.size),
+        );
+    }
+
+    let mut response = response
+        .body(Body::from_stream(download.stream))
+        .map_err(|_| AppError::InternalServerError)?;
+
+    // Non-ASCII names would make the header invalid; fall back to a plain one
+    let disposition = HeaderValue::from_str(&format!("attachment; filename=\"{}\"", name.replace('"', "")))
+        .unwrap_or_else(|_| HeaderValue::from_static("attachment"));
+    response.headers_mut().insert(header::CONTENT_DISPOSITION, disposition);
+    Ok(response)
+}
+
+pub async fn download_input(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((execution_id, artifact_id)): Path<(String, Uuid)>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    executions::authorize(&state, &claims, &execution_id).await?;
+
+    let artifact = artifacts::find_input(&state, &execution_id, artifact_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let location = object_store::path::Path::parse(&artifact.storage_key).map_err(|_| AppError::InternalServerError)?;
+    let download = artifacts::open(&state, &location, range_header(&headers)).await?;
+
+    download_response(download, &artifact.name, &artifact.content_type)
+}
+
+pub async fn download_output(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((execution_id, path)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    executions::authorize(&state, &claims, &execution_id).await?;
+
+    let location = artifacts::output_location(&execution_id, &path)?;
+    let download = artifacts::open(&state, &location, range_header(&headers)).await?;
+    let name = location.filename().unwrap_or("output").to_string();
+    let content_type = artifacts::detect_content_type(&name, &[]);
+
+    download_response(download, &name, &content_type)
+}
@@ -1,17 +1,26 @@
+use std::net::SocketAddr;
+
 use axum::{
-    extract::State,
-    http::StatusCode,
+    extract::{ConnectInfo, Path, State},
+    http::{header, HeaderMap, StatusCode},
     response::IntoResponse,
-    Json,
+    Extension, Json,
 };
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    auth::{generate_tokens, validate_token},
+    auth::{
+        generate_tokens, lockout, mfa,
+        session::{self, Session},
+        validate_token, validation, Claims,
+    },
+    config::TokenValidationStrategy,
     error::{AppError, Result},
     AppState,
 };
 
+const MAX_USER_AGENT_LEN: usize = 256;
+
 #[derive(Debug, Deserialize)]
 pub struct LoginRequest {
     pub email: String,
@@ -27,6 +36,13 @@ pub struct LoginResponse {
 }
 
 #[derive(Debug, Serialize)]
+pub struct MfaChallengeResponse {
+    pub mfa_required: bool,
+    pub challenge_token: String,
+    pub expires_in: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct UserInfo {
     pub id: String,
     pub email: String,
@@ -34,61 +50,254 @@ pub struct UserInfo {
     pub role: String,
 }
 
+/// A password-verified login waiting on its second factor.
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingLogin {
+    user: UserInfo,
+    iam_access_token: String,
+    iam_refresh_token: String,
+}
+
+/// Resolves the client IP, honouring `X-Forwarded-For` only when the
+/// deployment is configured to sit behind a trusted proxy.
+fn client_ip(state: &AppState, headers: &HeaderMap, addr: SocketAddr) -> String {
+    if state.config.trust_proxy_headers {
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+            .map(str::trim)
+            .filter(|value| !value.is_empty());
+        if let Some(ip) = forwarded {
+            return ip.to_string();
+        }
+    }
+    addr.ip().to_string()
+}
+
 pub async fn login(
     State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(payload): Json<LoginRequest>,
 ) -> Result<impl IntoResponse> {
+    let ip = client_ip(&state, &headers, addr);
+    let email = lockout::normalize_email(&payload.email);
+
+    // Reject early while the email or IP is throttled
+    lockout::check(&state, &email, &ip).await?;
+
     // Authenticate with IAM service
-    let auth_response = state.iam_client
+    let auth_response = match state.iam_client
         .authenticate(&payload.email, &payload.password)
         .await
-        .map_err(|_| AppError::AuthenticationError)?;
-    
-    // Generate JWT tokens
+    {
+        Ok(response) => response,
+        // An unreachable IAM service says nothing about the credentials
+        Err(AppError::GrpcError(status))
+            if matches!(status.code(), tonic::Code::Unavailable | tonic::Code::DeadlineExceeded) =>
+        {
+            return Err(AppError::ServiceUnavailable);
+        }
+        Err(_) => {
+            lockout::record_failure(&state, &email, &ip).await?;
+            return Err(AppError::AuthenticationError);
+        }
+    };
+
+    let pending = PendingLogin {
+        user: UserInfo {
+            id: auth_response.user.id,
+            email: auth_response.user.email,
+            name: auth_response.user.name,
+            role: auth_response.user.role,
+        },
+        iam_access_token: auth_response.access_token,
+        iam_refresh_token: auth_response.refresh_token,
+    };
+
+    // Hold back the tokens until the second factor is verified. The lockout
+    // counters are only cleared once it is, so alternating logins with
+    // wrong codes cannot keep them from tripping.
+    if mfa::is_enabled(&state, &pending.user.id).await? {
+        let challenge_token = mfa::create_challenge(&state, &pending).await?;
+        return Ok(Json(MfaChallengeResponse {
+            mfa_required: true,
+            challenge_token,
+            expires_in: state.config.mfa_challenge_ttl_secs,
+        })
+        .into_response());
+    }
+
+    lockout::reset(&state, &email, &ip).await?;
+    let device = DeviceInfo::from_request(&headers, ip);
+    Ok(Json(issue_session(&state, pending, device).await?).into_response())
+}
+
+/// Where a login came from, recorded on the session so users can tell their
+/// devices apart.
+struct DeviceInfo {
+    user_agent: Option<String>,
+    ip: String,
+}
+
+impl DeviceInfo {
+    fn from_request(headers: &HeaderMap, ip: String) -> Self {
+        let user_agent = headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(MAX_USER_AGENT_LEN).collect());
+        Self { user_agent, ip }
+    }
+}
+
+async fn issue_session(state: &AppState, pending: PendingLogin, device: DeviceInfo) -> Result<LoginResponse> {
+    let user = pending.user;
+
+    // Store session in Redis
+    let session = Session::new(
+        &user.id,
+        &user.email,
+        &user.role,
+        device.user_agent,
+        device.ip,
+        Some(pending.iam_access_token),
+        Some(pending.iam_refresh_token),
+    );
+    session::store(state, &session).await?;
+
+    // Generate JWT tokens bound to the session
     let tokens = generate_tokens(
-        &auth_response.user.id,
-        &auth_response.user.email,
-        &auth_response.user.role,
+        &user.id,
+        &user.email,
+        &user.role,
+        &session.id,
         &state.config.jwt_secret,
         state.config.jwt_expiry_hours,
     )?;
     
-    // Store session in Redis
-    let session_key = format!("session:{}", auth_response.user.id);
-    let session_data = serde_json::json!({
-        "user_id": auth_response.user.id,
-        "email": auth_response.user.email,
-        "role": auth_response.user.role,
-        "jti": tokens.access_token.split('.').last().unwrap_or(""),
-    });
-    
-    let mut conn = state.redis_client.clone();
-    redis::cmd("SETEX")
-        .arg(&session_key)
-        .arg(state.config.jwt_expiry_hours * 3600)
-        .arg(session_data.to_string())
-        .query_async::<_, ()>(&mut conn)
-        .await?;
-    
-    Ok(Json(LoginResponse {
+    Ok(LoginResponse {
         access_token: tokens.access_token,
         refresh_token: tokens.refresh_token,
         expires_in: tokens.expires_in,
-        user: UserInfo {
-            id: auth_response.user.id,
-            email: auth_response.user.email,
-            name: auth_response.user.name,
-            role: auth_response.user.role,
-        },
-    }))
+        user,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MfaVerifyRequest {
+    pub challenge_token: String,
+    pub code: String,
+}
+
+pub async fn verify_mfa(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(payload): Json<MfaVerifyRequest>,
+) -> Result<impl IntoResponse> {
+    let ip = client_ip(&state, &headers, addr);
+
+    let pending: PendingLogin = mfa::load_challenge(&state, &payload.challenge_token)
+        .await?
+        .ok_or(AppError::AuthenticationError)?;
+    let user = &pending.user;
+    let email = lockout::normalize_email(&user.email);
+
+    // Wrong codes count towards the same lockout as wrong passwords
+    lockout::check(&state, &email, &ip).await?;
+    if !mfa::verify(&state, &user.id, &user.email, &payload.code).await? {
+        lockout::record_failure(&state, &email, &ip).await?;
+        mfa::record_challenge_failure(&state, &payload.challenge_token).await?;
+        return Err(AppError::AuthenticationError);
+    }
+
+    if !mfa::consume_challenge(&state, &payload.challenge_token).await? {
+        return Err(AppError::AuthenticationError);
+    }
+    lockout::reset(&state, &email, &ip).await?;
+
+    let device = DeviceInfo::from_request(&headers, ip);
+    Ok(Json(issue_session(&state, pending, device).await?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MfaCodeRequest {
+    pub code: String,
+}
+
+pub async fn enroll_mfa(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<impl IntoResponse> {
+    let enrollment = mfa::begin_enrollment(&state, &claims.sub, &claims.email).await?;
+
+    Ok(Json(serde_json::json!({
+        "secret": enrollment.secret,
+        "otpauth_uri": enrollment.otpauth_uri,
+    })))
+}
+
+pub async fn confirm_mfa(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<MfaCodeRequest>,
+) -> Result<impl IntoResponse> {
+    let recovery_codes = mfa::confirm_enrollment(&state, &claims.sub, &claims.email, &payload.code).await?;
+
+    Ok(Json(serde_json::json!({
+        "enabled": true,
+        "recovery_codes": recovery_codes,
+    })))
+}
+
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<MfaCodeRequest>,
+) -> Result<impl IntoResponse> {
+    let recovery_codes = mfa::regenerate_recovery_codes(&state, &claims.sub, &claims.email, &payload.code).await?;
+
+    Ok(Json(serde_json::json!({
+        "recovery_codes": recovery_codes,
+    })))
+}
+
+pub async fn disable_mfa(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<MfaCodeRequest>,
+) -> Result<impl IntoResponse> {
+    mfa::disable(&state, &claims.sub, &claims.email, &payload.code).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 pub async fn logout(
     State(state): State<AppState>,
-    // TODO: Extract user ID from JWT middleware
+    Extension(claims): Extension<Claims>,
 ) -> Result<impl IntoResponse> {
-    // For now, return success
-    // TODO: Invalidate session in Redis
+    if let Some(session_id) = claims.sid.as_deref() {
+        session::delete(&state, &claims.sub, session_id).await?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<impl IntoResponse> {
+    let sessions = session::list(&state, &claims.sub, claims.sid.as_deref()).await?;
+    Ok(Json(serde_json::json!({ "sessions": sessions })))
+}
+
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(session_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    if !session::delete(&state, &claims.sub, &session_id).await? {
+        return Err(AppError::NotFound);
+    }
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -103,12 +312,24 @@ pub async fn refresh_token(
 ) -> Result<impl IntoResponse> {
     // Validate refresh token
     let claims = validate_token(&payload.refresh_token, &state.config.jwt_secret)?;
+    let session_id = claims.sid.clone().ok_or(AppError::Unauthorized)?;
+
+    // A revoked or expired session cannot be refreshed back to life
+    if !session::extend(&state, &claims.sub, &session_id).await? {
+        return Err(AppError::Unauthorized);
+    }
+
+    // Never mint fresh tokens for a user IAM no longer vouches for
+    if state.config.token_validation_strategy != TokenValidationStrategy::Local {
+        validation::check_with_iam(&state, &claims).await?;
+    }
     
     // Generate new tokens
     let tokens = generate_tokens(
         &claims.sub,
         &claims.email,
         &claims.role,
+        &session_id,
         &state.config.jwt_secret,
         state.config.jwt_expiry_hours,
     )?;
@@ -0,0 +1,48 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Extension, Json,
+};
+
+use crate::{
+    auth::{
+        workspace::{self, WorkspaceRole},
+        Claims,
+    },
+    error::Result,
+    executions::policy::{self, PolicyOverrides},
+    AppState,
+};
+
+pub async fn get_execution_policy(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(workspace_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Viewer).await?;
+
+    let overrides = policy::load(&state, &workspace_id).await?;
+    let effective = policy::effective(&state, Some(&workspace_id)).await?;
+
+    Ok(Json(serde_json::json!({
+        "effective": effective,
+        "overrides": overrides,
+    })))
+}
+
+pub async fn update_execution_policy(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(workspace_id): Path<String>,
+    Json(payload): Json<PolicyOverrides>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Admin).await?;
+
+    let overrides = policy::save(&state, &workspace_id, &payload, &claims.sub).await?;
+    let effective = policy::effective(&state, Some(&workspace_id)).await?;
+
+    Ok(Json(serde_json::json!({
+        "effective": effective,
+        "overrides": overrides,
+    })))
+}
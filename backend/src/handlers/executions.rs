@@ -1,22 +1,94 @@
+use std::future::Future;
+use std::time::Duration;
+
 use axum::{
-    extract::{Path, State},
-    response::IntoResponse,
-    Json,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use tracing::error;
 use uuid::Uuid;
 
 use crate::{
-    clients::control_plane::{CreateExecutionRequest, CreateExecutionResponse},
-    error::Result,
+    auth::{
+        workspace::{self, WorkspaceRole},
+        Claims,
+    },
+    artifacts,
+    clients::control_plane::{CreateExecutionRequest, InputFile},
+    error::{AppError, FieldError, Result},
+    executions::{
+        self,
+        idempotency::{self, Claim},
+        launch::launch,
+        outputs, Cursor, ListFilter, Visibility,
+    },
+    logs::{self, parse::ParsingRules, redaction::Redactor, LineFilter},
     AppState,
 };
 
-#[derive(Debug, Deserialize)]
+const FIELD_PARAM_PREFIX: &str = "field.";
+const MAX_FIELD_FILTERS: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CreateExecutionPayload {
     pub code: String,
     pub language: String,
+    pub version: Option<String>,
     pub environment: Option<std::collections::HashMap<String, String>>,
+    pub workspace_id: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Uploads from `POST /api/artifacts` to make available to the run
+    #[serde(default)]
+    pub input_files: Vec<Uuid>,
+    /// How output lines are parsed into structured fields
+    pub log_parsing: Option<ParsingRules>,
+}
+
+/// Overrides applied on top of the parent execution's request.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RerunPayload {
+    pub code: Option<String>,
+    pub language: Option<String>,
+    pub version: Option<String>,
+    /// Merged into the parent's environment; `null` removes a variable
+    #[serde(default)]
+    pub environment: std::collections::HashMap<String, Option<String>>,
+    /// Replaces the parent's tags
+    pub tags: Option<Vec<String>>,
+    /// Replaces the parent's log parsing rules
+    pub log_parsing: Option<ParsingRules>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListExecutionsQuery {
+    /// Comma-separated list of statuses
+    pub status: Option<String>,
+    /// Comma-separated list of languages
+    pub language: Option<String>,
+    pub created_by: Option<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    /// Comma-separated list; executions must carry all of them
+    pub tags: Option<String>,
+    pub workspace_id: Option<String>,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+pub(crate) fn split_list(value: Option<&str>) -> Vec<String> {
+    value
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
 }
 
 #[derive(Debug, Serialize)]
@@ -28,68 +100,436 @@ pub struct ExecutionResponse {
 
 pub async fn create_execution(
     State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
     Json(payload): Json<CreateExecutionPayload>,
-) -> Result<impl IntoResponse> {
+) -> Result<Response> {
+    let fingerprint = idempotency::fingerprint(&payload)?;
+    idempotent(&state, &claims, &headers, fingerprint, submit_execution(&state, &claims, payload)).await
+}
+
+/// Creates an execution from a copy of an earlier one's request, with
+/// optional overrides, and links it back to its parent.
+pub async fn rerun_execution(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response> {
+    // The body is optional; an empty one re-runs the execution unchanged
+    let payload: RerunPayload = if body.is_empty() {
+        RerunPayload::default()
+    } else {
+        serde_json::from_slice(&body).map_err(|e| AppError::BadRequest(format!("Invalid request body: {}", e)))?
+    };
+    let fingerprint = idempotency::fingerprint(&(&id, &payload))?;
+    idempotent(&state, &claims, &headers, fingerprint, rerun(&state, &claims, &id, payload)).await
+}
+
+/// Runs `submit` at most once per `Idempotency-Key`, replaying the stored
+/// response for retries. Without the header it simply runs.
+async fn idempotent(
+    state: &AppState,
+    claims: &Claims,
+    headers: &HeaderMap,
+    fingerprint: String,
+    submit: impl Future<Output = Result<ExecutionResponse>>,
+) -> Result<Response> {
+    let Some(key) = headers.get(idempotency::HEADER) else {
+        return Ok(Json(submit.await?).into_response());
+    };
+    let key = key
+        .to_str()
+        .map_err(|_| AppError::BadRequest("Invalid Idempotency-Key".to_string()))?;
+    idempotency::validate_key(key)?;
+
+    let lock = match idempotency::begin(state, &claims.sub, key, fingerprint).await? {
+        Claim::Acquired(lock) => lock,
+        Claim::Replay(stored) => {
+            let status = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+            let mut response = (status, Json(stored.body)).into_response();
+            response
+                .headers_mut()
+                .insert(idempotency::REPLAYED_HEADER, HeaderValue::from_static("true"));
+            return Ok(response);
+        }
+    };
+
+    match submit.await {
+        Ok(execution) => {
+            let body = serde_json::to_value(&execution).map_err(|_| AppError::InternalServerError)?;
+            // The run has started; failing the request now would only invite
+            // the retry we are trying to absorb
+            if let Err(e) = idempotency::complete(state, lock, StatusCode::OK.as_u16(), body.clone()).await {
+                error!("Failed to store idempotent response for {}: {}", execution.id, e);
+            }
+            Ok(Json(body).into_response())
+        }
+        Err(e) => {
+            if let Err(release_error) = idempotency::release(state, lock).await {
+                error!("Failed to release idempotency lock: {}", release_error);
+            }
+            Err(e)
+        }
+    }
+}
+
+async fn submit_execution(
+    state: &AppState,
+    claims: &Claims,
+    payload: CreateExecutionPayload,
+) -> Result<ExecutionResponse> {
+    // Service accounts always run inside their own workspace
+    let workspace_id = payload.workspace_id.or_else(|| claims.workspace_id.clone());
+    let input_files = artifacts::claim(state, &claims.sub, &payload.input_files).await?;
+
     let request = CreateExecutionRequest {
         code: payload.code,
         language: payload.language,
+        version: payload.version,
         environment: payload.environment.unwrap_or_default(),
+        input_files: input_files
+            .into_iter()
+            .map(|artifact| InputFile {
+                name: artifact.name,
+                storage_key: artifact.storage_key,
+            })
+            .collect(),
+        session_id: None,
     };
-    
-    let response = state.control_plane_client
-        .create_execution(request)
-        .await?;
-    
-    Ok(Json(ExecutionResponse {
-        id: response.execution_id,
-        status: "pending".to_string(),
-        created_at: chrono::Utc::now().to_rfc3339(),
-    }))
+
+    let launched = launch(
+        state,
+        claims,
+        workspace_id.as_deref(),
+        &payload.tags,
+        None,
+        payload.log_parsing.as_ref(),
+        request,
+    )
+    .await;
+    let execution = match launched {
+        Ok(execution) => execution,
+        Err(e) => {
+            if let Err(unclaim_error) = artifacts::unclaim(state, &payload.input_files).await {
+                error!("Failed to release input files: {}", unclaim_error);
+            }
+            return Err(e);
+        }
+    };
+    // The execution already has the files, so this only affects listings
+    if let Err(e) = artifacts::attach(state, &payload.input_files, &execution.id).await {
+        error!("Failed to attach input files to execution {}: {}", execution.id, e);
+    }
+
+    Ok(ExecutionResponse {
+        id: execution.id,
+        status: execution.status,
+        created_at: execution.created_at.to_rfc3339(),
+    })
+}
+
+async fn rerun(state: &AppState, claims: &Claims, parent_id: &str, payload: RerunPayload) -> Result<ExecutionResponse> {
+    let parent = executions::authorize_mutation(state, claims, parent_id).await?;
+    // A re-run carries the parent's environment, so only its owner or a
+    // workspace admin may start one from someone else's execution
+    if claims.role != "admin" && parent.owner_id != claims.sub {
+        let workspace_id = parent.workspace_id.as_deref().ok_or(AppError::Forbidden)?;
+        workspace::require_role(state, claims, workspace_id, WorkspaceRole::Admin).await?;
+    }
+    let stored = executions::stored_request(state, parent_id)
+        .await?
+        .ok_or_else(|| AppError::UnprocessableEntity("Execution was created before requests were kept and cannot be re-run".to_string()))?;
+    let mut request = stored.request;
+
+    // Values that were not kept must be given again (or removed)
+    let missing: Vec<&String> = stored
+        .dropped_environment
+        .iter()
+        .filter(|name| !payload.environment.contains_key(*name))
+        .collect();
+    if !missing.is_empty() {
+        return Err(AppError::Validation(
+            missing
+                .into_iter()
+                .map(|name| FieldError::new(format!("environment.{}", name), "Value was not kept; provide it again"))
+                .collect(),
+        ));
+    }
+
+    // A new language makes the old version meaningless
+    if let Some(language) = payload.language {
+        if language != request.language {
+            request.version = None;
+        }
+        request.language = language;
+    }
+    if let Some(version) = payload.version {
+        request.version = Some(version);
+    }
+    if let Some(code) = payload.code {
+        request.code = code;
+    }
+    for (name, value) in payload.environment {
+        match value {
+            Some(value) => request.environment.insert(name, value),
+            None => request.environment.remove(&name),
+        };
+    }
+    let tags = payload.tags.unwrap_or(parent.tags);
+    let log_parsing = match payload.log_parsing {
+        Some(rules) => rules,
+        None => executions::log_parsing(state, parent_id).await?,
+    };
+
+    let execution = launch(
+        state,
+        claims,
+        parent.workspace_id.as_deref(),
+        &tags,
+        Some(parent_id),
+        Some(&log_parsing),
+        request,
+    )
+    .await?;
+    // The request already names the files; this only affects listings
+    if let Err(e) = artifacts::copy_inputs(state, parent_id, &execution.id, &claims.sub).await {
+        error!("Failed to copy input files to re-run {}: {}", execution.id, e);
+    }
+
+    Ok(ExecutionResponse {
+        id: execution.id,
+        status: execution.status,
+        created_at: execution.created_at.to_rfc3339(),
+    })
+}
+
+/// Returns the chain of re-runs an execution belongs to. Executions the
+/// caller cannot see are left out.
+pub async fn get_lineage(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let execution = executions::authorize(&state, &claims, &id).await?;
+
+    let ancestors = visible(&state, &claims, executions::ancestors(&state, &id).await?).await?;
+    let descendants = visible(&state, &claims, executions::descendants(&state, &id).await?).await?;
+
+    Ok(Json(serde_json::json!({
+        "ancestors": ancestors,
+        "execution": execution,
+        "descendants": descendants,
+    })))
+}
+
+async fn visible(
+    state: &AppState,
+    claims: &Claims,
+    candidates: Vec<executions::Execution>,
+) -> Result<Vec<executions::Execution>> {
+    let mut visible = Vec::with_capacity(candidates.len());
+    for execution in candidates {
+        match executions::check_access(state, claims, &execution).await {
+            Ok(()) => visible.push(execution),
+            Err(AppError::Forbidden | AppError::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(visible)
+}
+
+pub async fn list_executions(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<ListExecutionsQuery>,
+) -> Result<impl IntoResponse> {
+    let visibility = match query.workspace_id {
+        Some(workspace_id) => {
+            workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Viewer).await?;
+            Visibility::Workspace(workspace_id)
+        }
+        None if claims.role == "admin" => Visibility::All,
+        None => Visibility::Owner(claims.sub.clone()),
+    };
+
+    let filter = ListFilter {
+        visibility,
+        statuses: split_list(query.status.as_deref()),
+        languages: split_list(query.language.as_deref()),
+        created_by: query.created_by,
+        created_after: query.created_after,
+        created_before: query.created_before,
+        tags: split_list(query.tags.as_deref()),
+        cursor: query.cursor.as_deref().map(Cursor::decode).transpose()?,
+        limit: query
+            .limit
+            .unwrap_or(executions::DEFAULT_PAGE_SIZE)
+            .clamp(1, executions::MAX_PAGE_SIZE),
+    };
+
+    let (executions, next_cursor) = executions::list(&state, &filter).await?;
+
+    Ok(Json(serde_json::json!({
+        "executions": executions,
+        "next_cursor": next_cursor,
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetExecutionQuery {
+    /// Long-poll for a status change, e.g. `30s`, `1500ms` or `2m`
+    pub wait: Option<String>,
+}
+
+fn parse_wait(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (amount, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value, "s"),
+    };
+    let amount: u64 = amount.parse().ok()?;
+    match unit {
+        "ms" => Some(Duration::from_millis(amount)),
+        "s" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_secs(amount.checked_mul(60)?)),
+        _ => None,
+    }
 }
 
 pub async fn get_execution(
     State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
     Path(id): Path<String>,
+    Query(query): Query<GetExecutionQuery>,
 ) -> Result<impl IntoResponse> {
+    let execution = executions::authorize(&state, &claims, &id).await?;
+
+    if let Some(wait) = query.wait.as_deref() {
+        let wait = parse_wait(wait)
+            .ok_or_else(|| AppError::BadRequest("wait must look like 30s, 1500ms or 2m".to_string()))?
+            .min(Duration::from_secs(state.config.execution_max_wait_secs));
+
+        // Finished executions have nothing left to wait for
+        if !executions::is_terminal(&execution.status) {
+            let mut status = state.execution_status.subscribe(&state, &id, &execution.status);
+            let _ = tokio::time::timeout(wait, status.wait_for(|status| *status != execution.status)).await;
+        }
+    }
+
     let response = state.control_plane_client
         .get_execution(&id)
         .await?;
+    executions::update_status(&state, &id, &response.status).await?;
+
+    let redactor = Redactor::for_execution(&state, &execution).await?;
+    let output = response.output.as_deref().map(|output| redactor.redact(output).into_owned());
+    let error = response.error.as_deref().map(|error| redactor.redact(error).into_owned());
+    let outputs = outputs::render(
+        state.config.execution_max_output_item_bytes,
+        state.config.execution_max_output_bytes,
+        &response.outputs,
+        response.output.as_deref(),
+        &redactor,
+    );
     
     Ok(Json(serde_json::json!({
         "id": response.execution_id,
         "status": response.status,
-        "output": response.output,
-        "error": response.error,
+        "output": output,
+        "outputs": outputs,
+        "error": error,
+        "language": execution.language,
+        "version": execution.language_version,
+        "workspace_id": execution.workspace_id,
+        "tags": execution.tags,
+        "created_at": execution.created_at.to_rfc3339(),
     })))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct LogsQuery {
+    /// Return lines after this sequence number
+    #[serde(default)]
+    pub after: i64,
+    pub limit: Option<i64>,
+    /// `stdout` or `stderr`
+    pub stream: Option<String>,
+    /// Comma-separated list of parsed levels
+    pub level: Option<String>,
+}
+
+/// Pages through stored (already redacted) output lines. `field.<name>=`
+/// parameters match parsed attributes.
 pub async fn get_execution_logs(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
     Path(id): Path<String>,
+    Query(query): Query<LogsQuery>,
+    Query(params): Query<Vec<(String, String)>>,
 ) -> Result<impl IntoResponse> {
-    // TODO: Stream logs from Redis pub/sub
+    executions::authorize(&state, &claims, &id).await?;
+
+    let filter = LineFilter {
+        stream: query.stream,
+        levels: split_list(query.level.as_deref()),
+        fields: params
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.strip_prefix(FIELD_PARAM_PREFIX)?.to_string(), value)))
+            .filter(|(key, _)| !key.is_empty())
+            .collect(),
+    };
+    if filter.fields.len() > MAX_FIELD_FILTERS {
+        return Err(AppError::BadRequest(format!("At most {} field filters are allowed", MAX_FIELD_FILTERS)));
+    }
+
+    let limit = query.limit.unwrap_or(logs::DEFAULT_PAGE_SIZE).clamp(1, logs::MAX_PAGE_SIZE);
+    let lines = logs::after(&state, &id, query.after, &filter, limit).await?;
+    let next_after = lines.last().map_or(query.after, |line| line.seq);
+
     Ok(Json(serde_json::json!({
         "execution_id": id,
-        "logs": [
-            {
-                "timestamp": chrono::Utc::now().to_rfc3339(),
-                "level": "info",
-                "message": "Execution started"
-            }
-        ]
+        "logs": lines,
+        "next_after": next_after,
     })))
 }
 
 pub async fn cancel_execution(
     State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
     Path(id): Path<String>,
 ) -> Result<impl IntoResponse> {
+    executions::authorize_mutation(&state, &claims, &id).await?;
+
     let response = state.control_plane_client
         .cancel_execution(&id)
         .await?;
+    if response.success {
+        executions::update_status(&state, &id, "cancelled").await?;
+    }
     
     Ok(Json(serde_json::json!({
         "success": response.success,
         "execution_id": id,
     })))
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_wait_durations() {
+        assert_eq!(parse_wait("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_wait("1500ms"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_wait("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_wait(" 45 "), Some(Duration::from_secs(45)));
+    }
+
+    #[test]
+    fn rejects_malformed_waits() {
+        for value in ["", "s", "10h", "-5s", "1.5s", "ten", &format!("{}m", u64::MAX)] {
+            assert_eq!(parse_wait(value), None, "{:?}", value);
+        }
+    }
+}
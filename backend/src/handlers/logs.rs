@@ -0,0 +1,148 @@
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use super::executions::split_list;
+use crate::{
+    auth::{
+        workspace::{self, WorkspaceRole},
+        Claims,
+    },
+    error::{AppError, Result},
+    executions::{self, Visibility},
+    logs::{
+        self,
+        export::Format,
+        search::{self, Cursor, SearchFilter},
+    },
+    AppState,
+};
+
+const EXECUTION_ID_HEADER: HeaderName = HeaderName::from_static("x-execution-id");
+const LOG_LINES_HEADER: HeaderName = HeaderName::from_static("x-log-lines");
+const LOG_START_HEADER: HeaderName = HeaderName::from_static("x-log-start");
+const LOG_END_HEADER: HeaderName = HeaderName::from_static("x-log-end");
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    /// `ndjson` (default), `text` or `csv`
+    pub format: Option<String>,
+    /// Serve a `.gz` file instead of plain text
+    #[serde(default)]
+    pub gzip: bool,
+}
+
+/// Keeps execution ids that reach a file name to a safe character set.
+fn file_stem(execution_id: &str) -> String {
+    execution_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// Downloads an execution's stored output as one file, streamed batch by
+/// batch from the log store.
+pub async fn export_execution_logs(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response> {
+    executions::authorize(&state, &claims, &id).await?;
+
+    let format: Format = query.format.as_deref().unwrap_or("ndjson").parse()?;
+    let summary = logs::summary(&state, &id).await?;
+
+    let mut filename = format!("execution-{}-logs.{}", file_stem(&id), format.extension());
+    let content_type = if query.gzip {
+        filename.push_str(".gz");
+        "application/gzip"
+    } else {
+        format.content_type()
+    };
+
+    let mut response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename))
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header(LOG_LINES_HEADER, summary.lines)
+        .body(Body::from_stream(logs::export::stream(state, id.clone(), format, query.gzip)))
+        .map_err(|_| AppError::InternalServerError)?;
+
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        headers.insert(EXECUTION_ID_HEADER, value);
+    }
+    if let (Some(first_at), Some(last_at)) = (summary.first_at, summary.last_at) {
+        headers.insert(LOG_START_HEADER, HeaderValue::from_str(&first_at.to_rfc3339()).map_err(|_| AppError::InternalServerError)?);
+        headers.insert(LOG_END_HEADER, HeaderValue::from_str(&last_at.to_rfc3339()).map_err(|_| AppError::InternalServerError)?);
+    }
+    Ok(response)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    /// Search terms; supports quoted phrases, `or` and `-excluded` words
+    pub q: String,
+    pub workspace_id: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Comma-separated list of parsed levels
+    pub level: Option<String>,
+    /// Comma-separated list of languages
+    pub language: Option<String>,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Searches stored output across the executions the caller can see: a
+/// workspace's when `workspace_id` is given, otherwise their own.
+pub async fn search_logs(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<SearchQuery>,
+) -> Result<impl IntoResponse> {
+    let terms = query.q.trim();
+    if terms.is_empty() || terms.len() > search::MAX_QUERY_LEN {
+        return Err(AppError::BadRequest(format!(
+            "q must be between 1 and {} characters",
+            search::MAX_QUERY_LEN
+        )));
+    }
+
+    let visibility = match query.workspace_id {
+        Some(workspace_id) => {
+            workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Viewer).await?;
+            Visibility::Workspace(workspace_id)
+        }
+        None => Visibility::Owner(claims.sub.clone()),
+    };
+
+    let filter = SearchFilter {
+        query: terms.to_string(),
+        visibility,
+        since: query.since,
+        until: query.until,
+        levels: split_list(query.level.as_deref()),
+        languages: split_list(query.language.as_deref()),
+        cursor: query.cursor.as_deref().map(Cursor::decode).transpose()?,
+        limit: query
+            .limit
+            .unwrap_or(search::DEFAULT_PAGE_SIZE)
+            .clamp(1, search::MAX_PAGE_SIZE),
+    };
+
+    let (results, next_cursor) = search::search(&state, &filter).await?;
+
+    Ok(Json(serde_json::json!({
+        "results": results,
+        "next_cursor": next_cursor,
+    })))
+}
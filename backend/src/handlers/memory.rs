@@ -3,7 +3,7 @@ use axum::{
     response::IntoResponse,
     Json,
 };
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 
 use crate::{
     clients::memory::{QueryRequest, SearchRequest, StoreRequest},
@@ -1,4 +1,17 @@
+pub mod admin;
+pub mod alerts;
+pub mod artifacts;
 pub mod auth;
+pub mod execution_policy;
 pub mod executions;
+pub mod logs;
 pub mod memory;
+pub mod notebooks;
+pub mod oauth;
+pub mod redaction_rules;
+pub mod schedules;
+pub mod secrets;
+pub mod service_accounts;
+pub mod usage;
+pub mod webhooks;
 pub mod workspaces;
\ No newline at end of file
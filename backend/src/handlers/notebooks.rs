@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::Deserialize;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        workspace::{self, WorkspaceRole},
+        Claims,
+    },
+    clients::control_plane::CreateExecutionRequest,
+    error::{AppError, Result},
+    executions::{launch::launch, Execution, Visibility},
+    notebooks::{self, Cell, NewNotebook, Notebook},
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateNotebookPayload {
+    pub name: String,
+    pub language: String,
+    pub version: Option<String>,
+    /// May reference workspace secrets as `{{secrets.NAME}}`
+    pub environment: Option<HashMap<String, String>>,
+    pub workspace_id: Option<String>,
+    /// Sources of the initial cells, in order
+    #[serde(default)]
+    pub cells: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateNotebookPayload {
+    pub name: Option<String>,
+    pub environment: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListNotebooksQuery {
+    pub workspace_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCellPayload {
+    #[serde(default)]
+    pub source: String,
+    /// Zero-based; defaults to the end
+    pub position: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateCellPayload {
+    pub source: Option<String>,
+    pub position: Option<i32>,
+}
+
+async fn notebook_response(state: &AppState, notebook: &Notebook) -> Result<serde_json::Value> {
+    let cells = notebooks::cells(state, notebook.id).await?;
+    let mut view = notebook.view(state.config.notebook_idle_timeout_secs);
+    view["cells"] = serde_json::json!(cells);
+    Ok(view)
+}
+
+pub async fn list_notebooks(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<ListNotebooksQuery>,
+) -> Result<impl IntoResponse> {
+    let visibility = match query.workspace_id {
+        Some(workspace_id) => {
+            workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Viewer).await?;
+            Visibility::Workspace(workspace_id)
+        }
+        None => Visibility::Owner(claims.sub.clone()),
+    };
+
+    let notebooks: Vec<serde_json::Value> = notebooks::list(&state, &visibility)
+        .await?
+        .iter()
+        .map(|notebook| notebook.view(state.config.notebook_idle_timeout_secs))
+        .collect();
+    Ok(Json(serde_json::json!({ "notebooks": notebooks })))
+}
+
+pub async fn create_notebook(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<CreateNotebookPayload>,
+) -> Result<impl IntoResponse> {
+    // Service accounts always work inside their own workspace
+    let workspace_id = payload.workspace_id.or_else(|| claims.workspace_id.clone());
+    if let Some(workspace_id) = workspace_id.as_deref() {
+        workspace::require_role(&state, &claims, workspace_id, WorkspaceRole::User).await?;
+    }
+
+    let notebook = notebooks::create(
+        &state,
+        &NewNotebook {
+            owner_id: &claims.sub,
+            workspace_id: workspace_id.as_deref(),
+            name: &payload.name,
+            language: &payload.language,
+            version: payload.version.as_deref(),
+            environment: &payload.environment.unwrap_or_default(),
+            cells: &payload.cells,
+        },
+    )
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(notebook_response(&state, &notebook).await?)))
+}
+
+pub async fn get_notebook(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let notebook = notebooks::authorize(&state, &claims, id, false).await?;
+    Ok(Json(notebook_response(&state, &notebook).await?))
+}
+
+pub async fn update_notebook(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<UpdateNotebookPayload>,
+) -> Result<impl IntoResponse> {
+    let notebook = notebooks::authorize(&state, &claims, id, true).await?;
+    let notebook = notebooks::update(&state, &notebook, payload.name.as_deref(), payload.environment.as_ref()).await?;
+    Ok(Json(notebook.view(state.config.notebook_idle_timeout_secs)))
+}
+
+pub async fn delete_notebook(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let notebook = notebooks::authorize(&state, &claims, id, true).await?;
+    notebooks::delete(&state, &notebook).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn create_cell(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<CreateCellPayload>,
+) -> Result<impl IntoResponse> {
+    notebooks::authorize(&state, &claims, id, true).await?;
+    let cell = notebooks::insert_cell(&state, id, &payload.source, payload.position).await?;
+    Ok((StatusCode::CREATED, Json(cell)))
+}
+
+pub async fn update_cell(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((id, cell_id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<UpdateCellPayload>,
+) -> Result<impl IntoResponse> {
+    notebooks::authorize(&state, &claims, id, true).await?;
+    let cell = notebooks::update_cell(&state, id, cell_id, payload.source.as_deref(), payload.position)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(cell))
+}
+
+pub async fn delete_cell(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((id, cell_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse> {
+    notebooks::authorize(&state, &claims, id, true).await?;
+    if !notebooks::delete_cell(&state, id, cell_id).await? {
+        return Err(AppError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Runs a cell as an execution in the notebook's session, starting the
+/// session first if it has none. One cell runs at a time.
+pub async fn run_cell(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((id, cell_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse> {
+    let notebook = notebooks::authorize(&state, &claims, id, true).await?;
+    let cell = notebooks::find_cell(&state, id, cell_id).await?.ok_or(AppError::NotFound)?;
+    notebooks::claim_run(&state, id).await?;
+
+    let execution = match start_cell(&state, &claims, &notebook, &cell.source).await {
+        Ok(execution) => execution,
+        Err(e) => {
+            if let Err(release_error) = notebooks::release_run(&state, id).await {
+                error!("Failed to release run claim of notebook {}: {}", id, release_error);
+            }
+            return Err(e);
+        }
+    };
+    // The cell is running now. If it cannot be linked the claim is left to
+    // expire, so no other cell starts alongside it meanwhile
+    let cell = match notebooks::start_run(&state, id, cell_id, &execution).await {
+        Ok(cell) => cell,
+        Err(e) => {
+            error!("Failed to link cell {} to execution {}: {}", cell_id, execution.id, e);
+            Cell {
+                execution_id: Some(execution.id),
+                status: Some(execution.status),
+                ..cell
+            }
+        }
+    };
+
+    Ok((StatusCode::ACCEPTED, Json(cell)))
+}
+
+async fn start_cell(state: &AppState, claims: &Claims, notebook: &Notebook, source: &str) -> Result<Execution> {
+    let session_id = notebooks::ensure_session(state, notebook).await?;
+    let request = CreateExecutionRequest {
+        code: source.to_string(),
+        language: notebook.language.clone(),
+        version: notebook.version.clone(),
+        environment: notebook.open_environment(state)?,
+        input_files: Vec::new(),
+        session_id: Some(session_id),
+    };
+    launch(state, claims, notebook.workspace_id.as_deref(), &[], None, None, request).await
+}
+
+/// Clears the session's state, interrupting a running cell. Cells keep the
+/// outputs of their last run.
+pub async fn restart_notebook(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let notebook = notebooks::authorize(&state, &claims, id, true).await?;
+
+    if let Some(execution_id) = notebooks::running_cell(&state, id).await?.and_then(|cell| cell.execution_id) {
+        if let Err(e) = state.control_plane_client.cancel_execution(&execution_id).await {
+            warn!("Failed to cancel {} before restarting notebook {}: {}", execution_id, id, e);
+        }
+    }
+    notebooks::restart_session(&state, &notebook).await?;
+
+    let notebook = notebooks::find(&state, id).await?.ok_or(AppError::NotFound)?;
+    Ok(Json(notebook.view(state.config.notebook_idle_timeout_secs)))
+}
+
+/// Stops the session; the next cell run starts a fresh one.
+pub async fn stop_notebook_session(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let notebook = notebooks::authorize(&state, &claims, id, true).await?;
+    if let Some(session_id) = &notebook.session_id {
+        notebooks::stop_session(&state, id, session_id).await?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
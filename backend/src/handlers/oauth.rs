@@ -0,0 +1,127 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Form, Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::{generate_service_token, service_accounts},
+    error::AppError,
+    AppState,
+};
+
+const CLIENT_CREDENTIALS: &str = "client_credentials";
+
+#[derive(Debug, Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: i64,
+    pub scope: String,
+}
+
+/// Errors in the RFC 6749 section 5.2 shape rather than `AppError`'s.
+#[derive(Debug)]
+pub enum OAuthError {
+    InvalidRequest(&'static str),
+    InvalidClient,
+    InvalidScope,
+    UnsupportedGrantType,
+    Internal(AppError),
+}
+
+impl From<AppError> for OAuthError {
+    fn from(error: AppError) -> Self {
+        OAuthError::Internal(error)
+    }
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        let (status, code, description) = match self {
+            OAuthError::InvalidRequest(description) => (StatusCode::BAD_REQUEST, "invalid_request", description),
+            OAuthError::InvalidClient => (StatusCode::UNAUTHORIZED, "invalid_client", "Client authentication failed"),
+            OAuthError::InvalidScope => (StatusCode::BAD_REQUEST, "invalid_scope", "Requested scope exceeds the client's grant"),
+            OAuthError::UnsupportedGrantType => (StatusCode::BAD_REQUEST, "unsupported_grant_type", "Only client_credentials is supported"),
+            OAuthError::Internal(error) => return error.into_response(),
+        };
+
+        let body = Json(serde_json::json!({
+            "error": code,
+            "error_description": description,
+        }));
+
+        if status == StatusCode::UNAUTHORIZED {
+            return (status, [(header::WWW_AUTHENTICATE, "Basic")], body).into_response();
+        }
+        (status, body).into_response()
+    }
+}
+
+/// Reads `client_secret_basic` credentials from the Authorization header.
+fn basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let encoded = value.strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(STANDARD.decode(encoded).ok()?).ok()?;
+    let (client_id, client_secret) = decoded.split_once(':')?;
+    Some((client_id.to_string(), client_secret.to_string()))
+}
+
+pub async fn token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(payload): Form<TokenRequest>,
+) -> Result<Json<TokenResponse>, OAuthError> {
+    if payload.grant_type != CLIENT_CREDENTIALS {
+        return Err(OAuthError::UnsupportedGrantType);
+    }
+
+    let (client_id, client_secret) = match (basic_credentials(&headers), payload.client_id, payload.client_secret) {
+        (Some(credentials), _, _) => credentials,
+        (None, Some(client_id), Some(client_secret)) => (client_id, client_secret),
+        _ => return Err(OAuthError::InvalidRequest("Missing client credentials")),
+    };
+
+    let account = service_accounts::authenticate_client(&state, &client_id, &client_secret)
+        .await?
+        .ok_or(OAuthError::InvalidClient)?;
+
+    // A request may narrow the granted scopes but never widen them
+    let scopes: Vec<String> = match payload.scope.as_deref() {
+        Some(requested) => {
+            let requested: Vec<String> = requested.split_whitespace().map(str::to_string).collect();
+            if !requested.iter().all(|scope| account.scopes.contains(scope)) {
+                return Err(OAuthError::InvalidScope);
+            }
+            requested
+        }
+        None => account.scopes.clone(),
+    };
+
+    let access_token = generate_service_token(
+        &account.id.to_string(),
+        &account.client_id,
+        &account.workspace_id,
+        &scopes,
+        &state.config.jwt_secret,
+        state.config.service_token_expiry_secs,
+    )?;
+
+    Ok(Json(TokenResponse {
+        access_token,
+        token_type: "Bearer",
+        expires_in: state.config.service_token_expiry_secs,
+        scope: scopes.join(" "),
+    }))
+}
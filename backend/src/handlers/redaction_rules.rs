@@ -0,0 +1,62 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        workspace::{self, WorkspaceRole},
+        Claims,
+    },
+    error::{AppError, Result},
+    logs::redaction,
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRedactionRulePayload {
+    pub name: String,
+    /// A regular expression; a `secret` capture group limits masking to
+    /// that group
+    pub pattern: String,
+}
+
+pub async fn list_redaction_rules(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(workspace_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Viewer).await?;
+
+    let rules = redaction::rules(&state, &workspace_id).await?;
+    Ok(Json(serde_json::json!({ "rules": rules })))
+}
+
+pub async fn create_redaction_rule(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(workspace_id): Path<String>,
+    Json(payload): Json<CreateRedactionRulePayload>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Admin).await?;
+
+    let rule = redaction::create_rule(&state, &workspace_id, &payload.name, &payload.pattern, &claims.sub).await?;
+    Ok((StatusCode::CREATED, Json(rule)))
+}
+
+pub async fn delete_redaction_rule(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((workspace_id, rule_id)): Path<(String, Uuid)>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Admin).await?;
+
+    if !redaction::delete_rule(&state, &workspace_id, rule_id).await? {
+        return Err(AppError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        workspace::{self, WorkspaceRole},
+        Claims,
+    },
+    clients::control_plane::CreateExecutionRequest,
+    error::{AppError, Result},
+    logs::parse::ParsingRules,
+    schedules::{self, CatchUp, Overlap, ScheduleSpec},
+    AppState,
+};
+
+const DEFAULT_RUNS_PAGE_SIZE: i64 = 50;
+const MAX_RUNS_PAGE_SIZE: i64 = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSchedulePayload {
+    /// Defaults to a service account's own workspace
+    pub workspace_id: Option<String>,
+    pub name: String,
+    pub cron: String,
+    pub timezone: Option<String>,
+    pub code: String,
+    pub language: String,
+    pub version: Option<String>,
+    /// May reference workspace secrets as `{{secrets.NAME}}`
+    pub environment: Option<HashMap<String, String>>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub log_parsing: Option<ParsingRules>,
+    #[serde(default)]
+    pub catch_up: CatchUp,
+    #[serde(default)]
+    pub overlap: Overlap,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSchedulePayload {
+    pub name: Option<String>,
+    pub cron: Option<String>,
+    pub timezone: Option<String>,
+    pub code: Option<String>,
+    pub language: Option<String>,
+    pub version: Option<String>,
+    pub environment: Option<HashMap<String, String>>,
+    pub tags: Option<Vec<String>>,
+    pub log_parsing: Option<ParsingRules>,
+    pub catch_up: Option<CatchUp>,
+    pub overlap: Option<Overlap>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListSchedulesQuery {
+    pub workspace_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListRunsQuery {
+    /// The `next_cursor` of the previous page
+    pub cursor: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+}
+
+pub async fn list_schedules(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<ListSchedulesQuery>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &query.workspace_id, WorkspaceRole::Viewer).await?;
+
+    let schedules: Vec<serde_json::Value> = schedules::list(&state, &query.workspace_id)
+        .await?
+        .iter()
+        .map(|schedule| schedule.view())
+        .collect();
+    Ok(Json(serde_json::json!({ "schedules": schedules })))
+}
+
+pub async fn create_schedule(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<CreateSchedulePayload>,
+) -> Result<impl IntoResponse> {
+    let workspace_id = payload
+        .workspace_id
+        .or_else(|| claims.workspace_id.clone())
+        .ok_or_else(|| AppError::BadRequest("workspace_id is required".to_string()))?;
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::User).await?;
+
+    let spec = ScheduleSpec {
+        name: payload.name,
+        cron: payload.cron,
+        timezone: payload
+            .timezone
+            .unwrap_or_else(|| schedules::DEFAULT_TIMEZONE.to_string()),
+        request: CreateExecutionRequest {
+            code: payload.code,
+            language: payload.language,
+            version: payload.version,
+            environment: payload.environment.unwrap_or_default(),
+            input_files: Vec::new(),
+            session_id: None,
+        },
+        tags: payload.tags,
+        log_parsing: payload.log_parsing,
+        catch_up: payload.catch_up,
+        overlap: payload.overlap,
+    };
+    let schedule = schedules::create(&state, &claims, &workspace_id, &spec).await?;
+
+    Ok((StatusCode::CREATED, Json(schedule.view())))
+}
+
+pub async fn get_schedule(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let schedule = schedules::authorize(&state, &claims, id, false).await?;
+    Ok(Json(schedule.view()))
+}
+
+/// Changing what runs (code, language, version or environment) makes the
+/// caller the schedule's owner.
+pub async fn update_schedule(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<UpdateSchedulePayload>,
+) -> Result<impl IntoResponse> {
+    let schedule = schedules::authorize(&state, &claims, id, true).await?;
+    let take_ownership = payload.code.is_some()
+        || payload.language.is_some()
+        || payload.version.is_some()
+        || payload.environment.is_some();
+    if take_ownership {
+        workspace::require_role(&state, &claims, &schedule.workspace_id, WorkspaceRole::User).await?;
+    }
+
+    let mut spec = schedule.spec(&state)?;
+    if let Some(name) = payload.name {
+        spec.name = name;
+    }
+    if let Some(cron) = payload.cron {
+        spec.cron = cron;
+    }
+    if let Some(timezone) = payload.timezone {
+        spec.timezone = timezone;
+    }
+    if let Some(code) = payload.code {
+        spec.request.code = code;
+    }
+    if let Some(language) = payload.language {
+        spec.request.language = language;
+    }
+    if let Some(version) = payload.version {
+        spec.request.version = Some(version);
+    }
+    if let Some(environment) = payload.environment {
+        spec.request.environment = environment;
+    }
+    if let Some(tags) = payload.tags {
+        spec.tags = tags;
+    }
+    if let Some(log_parsing) = payload.log_parsing {
+        spec.log_parsing = Some(log_parsing);
+    }
+    if let Some(catch_up) = payload.catch_up {
+        spec.catch_up = catch_up;
+    }
+    if let Some(overlap) = payload.overlap {
+        spec.overlap = overlap;
+    }
+
+    let schedule = schedules::update(&state, &claims, &schedule, &spec, take_ownership).await?;
+    Ok(Json(schedule.view()))
+}
+
+pub async fn delete_schedule(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    schedules::authorize(&state, &claims, id, true).await?;
+    schedules::delete(&state, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn pause_schedule(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let schedule = schedules::authorize(&state, &claims, id, true).await?;
+    let schedule = schedules::set_paused(&state, &schedule, true).await?;
+    Ok(Json(schedule.view()))
+}
+
+pub async fn resume_schedule(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let schedule = schedules::authorize(&state, &claims, id, true).await?;
+    let schedule = schedules::set_paused(&state, &schedule, false).await?;
+    Ok(Json(schedule.view()))
+}
+
+pub async fn list_schedule_runs(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<ListRunsQuery>,
+) -> Result<impl IntoResponse> {
+    schedules::authorize(&state, &claims, id, false).await?;
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_RUNS_PAGE_SIZE)
+        .clamp(1, MAX_RUNS_PAGE_SIZE);
+    let runs = schedules::runs(&state, id, query.cursor, limit).await?;
+    let next_cursor = (runs.len() as i64 == limit)
+        .then(|| runs.last().map(|run| run.scheduled_for))
+        .flatten();
+
+    Ok(Json(serde_json::json!({
+        "runs": runs,
+        "next_cursor": next_cursor,
+    })))
+}
@@ -0,0 +1,60 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::Deserialize;
+
+use crate::{
+    auth::{
+        workspace::{self, WorkspaceRole},
+        Claims,
+    },
+    error::{AppError, Result},
+    secrets::{self, SecretValue},
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct PutSecretPayload {
+    pub value: SecretValue,
+}
+
+pub async fn list_secrets(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(workspace_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::User).await?;
+
+    let secrets = secrets::list(&state, &workspace_id).await?;
+    Ok(Json(serde_json::json!({ "secrets": secrets })))
+}
+
+/// Stores a secret. The response carries metadata only; there is no way to
+/// read a value back.
+pub async fn put_secret(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((workspace_id, name)): Path<(String, String)>,
+    Json(payload): Json<PutSecretPayload>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Admin).await?;
+
+    let secret = secrets::put(&state, &workspace_id, &name, &payload.value, &claims.sub).await?;
+    Ok(Json(secret))
+}
+
+pub async fn delete_secret(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((workspace_id, name)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Admin).await?;
+
+    if !secrets::delete(&state, &workspace_id, &name).await? {
+        return Err(AppError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,94 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        service_accounts,
+        workspace::{self, WorkspaceRole},
+        Claims,
+    },
+    error::{AppError, Result},
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateServiceAccountPayload {
+    pub name: String,
+    pub scopes: Vec<String>,
+}
+
+pub async fn list_service_accounts(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(workspace_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Admin).await?;
+
+    let accounts = service_accounts::list(&state, &workspace_id).await?;
+
+    Ok(Json(serde_json::json!({
+        "service_accounts": accounts,
+    })))
+}
+
+pub async fn create_service_account(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(workspace_id): Path<String>,
+    Json(payload): Json<CreateServiceAccountPayload>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Admin).await?;
+
+    if payload.name.trim().is_empty() {
+        return Err(AppError::BadRequest("name is required".to_string()));
+    }
+
+    let (account, client_secret) = service_accounts::create(
+        &state,
+        &workspace_id,
+        payload.name.trim(),
+        &payload.scopes,
+        &claims.sub,
+    )
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "service_account": account,
+            "client_secret": client_secret,
+        })),
+    ))
+}
+
+pub async fn rotate_service_account_secret(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((workspace_id, id)): Path<(String, Uuid)>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Admin).await?;
+
+    let client_secret = service_accounts::rotate_secret(&state, &workspace_id, id).await?;
+
+    Ok(Json(serde_json::json!({
+        "id": id,
+        "client_secret": client_secret,
+    })))
+}
+
+pub async fn disable_service_account(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((workspace_id, id)): Path<(String, Uuid)>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Admin).await?;
+
+    service_accounts::disable(&state, &workspace_id, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,45 @@
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::Deserialize;
+
+use crate::{
+    auth::{
+        workspace::{self, WorkspaceRole},
+        Claims,
+    },
+    error::Result,
+    executions::quota::{self, Scope},
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    pub workspace_id: Option<String>,
+}
+
+pub async fn get_usage(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<UsageQuery>,
+) -> Result<impl IntoResponse> {
+    let user = quota::usage(&state, Scope::User, &claims.sub).await?;
+
+    let workspace = match query.workspace_id.or_else(|| claims.workspace_id.clone()) {
+        Some(workspace_id) => {
+            workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Viewer).await?;
+            Some(serde_json::json!({
+                "id": workspace_id,
+                "usage": quota::usage(&state, Scope::Workspace, &workspace_id).await?,
+            }))
+        }
+        None => None,
+    };
+
+    Ok(Json(serde_json::json!({
+        "user": user,
+        "workspace": workspace,
+    })))
+}
@@ -0,0 +1,173 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        workspace::{self, WorkspaceRole},
+        Claims,
+    },
+    error::{AppError, Result},
+    executions,
+    webhooks::{self, Target, Webhook},
+    AppState,
+};
+
+const DEFAULT_DELIVERY_LIMIT: i64 = 50;
+const MAX_DELIVERY_LIMIT: i64 = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookPayload {
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeliveriesQuery {
+    /// `pending`, `delivered` or `dead`
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Execution webhooks are managed by the execution's owner or an admin of
+/// its workspace; merely being able to see the execution is not enough.
+async fn require_execution_manager(state: &AppState, claims: &Claims, execution_id: &str) -> Result<()> {
+    let execution = executions::authorize(state, claims, execution_id).await?;
+    if claims.role == "admin" || execution.owner_id == claims.sub {
+        return Ok(());
+    }
+
+    match execution.workspace_id.as_deref() {
+        Some(workspace_id) => {
+            workspace::require_role(state, claims, workspace_id, WorkspaceRole::Admin).await?;
+            Ok(())
+        }
+        None => Err(AppError::Forbidden),
+    }
+}
+
+async fn authorize_webhook(state: &AppState, claims: &Claims, webhook_id: Uuid) -> Result<Webhook> {
+    let webhook = webhooks::find(state, webhook_id).await?.ok_or(AppError::NotFound)?;
+
+    match (&webhook.workspace_id, &webhook.execution_id) {
+        (Some(workspace_id), _) => {
+            workspace::require_role(state, claims, workspace_id, WorkspaceRole::Admin).await?;
+        }
+        (None, Some(execution_id)) => require_execution_manager(state, claims, execution_id).await?,
+        (None, None) => return Err(AppError::NotFound),
+    }
+    Ok(webhook)
+}
+
+fn created_response(webhook: Webhook, secret: String) -> impl IntoResponse {
+    (
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "webhook": webhook,
+            "secret": secret,
+        })),
+    )
+}
+
+pub async fn list_workspace_webhooks(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(workspace_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Admin).await?;
+
+    let webhooks = webhooks::list(&state, Target::Workspace(&workspace_id)).await?;
+    Ok(Json(serde_json::json!({ "webhooks": webhooks })))
+}
+
+pub async fn create_workspace_webhook(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(workspace_id): Path<String>,
+    Json(payload): Json<CreateWebhookPayload>,
+) -> Result<impl IntoResponse> {
+    workspace::require_role(&state, &claims, &workspace_id, WorkspaceRole::Admin).await?;
+
+    let (webhook, secret) = webhooks::create(&state, Target::Workspace(&workspace_id), &payload.url, &claims.sub).await?;
+    Ok(created_response(webhook, secret))
+}
+
+pub async fn list_execution_webhooks(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(execution_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    require_execution_manager(&state, &claims, &execution_id).await?;
+
+    let webhooks = webhooks::list(&state, Target::Execution(&execution_id)).await?;
+    Ok(Json(serde_json::json!({ "webhooks": webhooks })))
+}
+
+pub async fn create_execution_webhook(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(execution_id): Path<String>,
+    Json(payload): Json<CreateWebhookPayload>,
+) -> Result<impl IntoResponse> {
+    require_execution_manager(&state, &claims, &execution_id).await?;
+
+    let (webhook, secret) = webhooks::create(&state, Target::Execution(&execution_id), &payload.url, &claims.sub).await?;
+    Ok(created_response(webhook, secret))
+}
+
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(webhook_id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    authorize_webhook(&state, &claims, webhook_id).await?;
+
+    webhooks::disable(&state, webhook_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_deliveries(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(webhook_id): Path<Uuid>,
+    Query(query): Query<DeliveriesQuery>,
+) -> Result<impl IntoResponse> {
+    authorize_webhook(&state, &claims, webhook_id).await?;
+
+    let limit = query.limit.unwrap_or(DEFAULT_DELIVERY_LIMIT).clamp(1, MAX_DELIVERY_LIMIT);
+    let deliveries = webhooks::deliveries(&state, webhook_id, query.status.as_deref(), limit).await?;
+    Ok(Json(serde_json::json!({ "deliveries": deliveries })))
+}
+
+pub async fn get_delivery(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((webhook_id, delivery_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse> {
+    authorize_webhook(&state, &claims, webhook_id).await?;
+
+    let delivery = webhooks::delivery(&state, webhook_id, delivery_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let attempts = webhooks::attempts(&state, delivery_id).await?;
+
+    Ok(Json(serde_json::json!({
+        "delivery": delivery,
+        "attempts": attempts,
+    })))
+}
+
+pub async fn redeliver(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((webhook_id, delivery_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse> {
+    authorize_webhook(&state, &claims, webhook_id).await?;
+
+    webhooks::redeliver(&state, webhook_id, delivery_id).await?;
+    Ok(StatusCode::ACCEPTED)
+}
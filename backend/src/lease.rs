@@ -0,0 +1,57 @@
+use crate::{error::Result, AppState};
+
+const RENEW_SCRIPT: &str = r"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('EXPIRE', KEYS[1], ARGV[2])
+end
+return 0
+";
+
+const RELEASE_SCRIPT: &str = r"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+";
+
+/// Takes the lease at `key` for `token` if nobody holds it. It expires after
+/// `secs` unless renewed, so a holder that dies gives it up on its own.
+pub async fn acquire(state: &AppState, key: &str, token: &str, secs: u64) -> Result<bool> {
+    let mut conn = state.redis_client.as_ref().clone();
+    let acquired: Option<String> = redis::cmd("SET")
+        .arg(key)
+        .arg(token)
+        .arg("NX")
+        .arg("EX")
+        .arg(secs)
+        .query_async(&mut conn)
+        .await?;
+    Ok(acquired.is_some())
+}
+
+/// Extends the lease by `secs`. Returns false if `token` no longer holds it.
+pub async fn renew(state: &AppState, key: &str, token: &str, secs: u64) -> Result<bool> {
+    Ok(run_script(state, RENEW_SCRIPT, key, token, secs).await? == 1)
+}
+
+/// Gives the lease up, unless someone else has taken it meanwhile.
+pub async fn release(state: &AppState, key: &str, token: &str) -> Result<()> {
+    run_script(state, RELEASE_SCRIPT, key, token, 0).await?;
+    Ok(())
+}
+
+/// Whether anyone holds the lease.
+pub async fn is_held(state: &AppState, key: &str) -> Result<bool> {
+    let mut conn = state.redis_client.as_ref().clone();
+    Ok(redis::cmd("EXISTS").arg(key).query_async(&mut conn).await?)
+}
+
+async fn run_script(state: &AppState, script: &str, key: &str, token: &str, secs: u64) -> Result<i64> {
+    let mut conn = state.redis_client.as_ref().clone();
+    Ok(redis::Script::new(script)
+        .key(key)
+        .arg(token)
+        .arg(secs)
+        .invoke_async(&mut conn)
+        .await?)
+}
@@ -0,0 +1,267 @@
+use std::io::Write;
+
+use axum::body::Bytes;
+use flate2::{write::GzEncoder, Compression};
+use futures::{stream, Stream};
+
+use crate::{
+    error::{AppError, Result},
+    logs::{self, LineFilter, LogLine},
+    AppState,
+};
+
+/// Lines fetched per round trip; the export holds at most one batch.
+const BATCH_LINES: i64 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Ndjson,
+    Text,
+    Csv,
+}
+
+impl std::str::FromStr for Format {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "ndjson" => Ok(Self::Ndjson),
+            "text" => Ok(Self::Text),
+            "csv" => Ok(Self::Csv),
+            _ => Err(AppError::BadRequest("format must be ndjson, text or csv".to_string())),
+        }
+    }
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Ndjson => "ndjson",
+            Self::Text => "log",
+            Self::Csv => "csv",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Ndjson => "application/x-ndjson",
+            Self::Text => "text/plain; charset=utf-8",
+            Self::Csv => "text/csv; charset=utf-8",
+        }
+    }
+
+    fn header(self) -> &'static str {
+        match self {
+            Self::Csv => "seq,timestamp,stream,level,message\n",
+            Self::Ndjson | Self::Text => "",
+        }
+    }
+
+    fn write_line(self, out: &mut String, line: &LogLine) {
+        match self {
+            Self::Ndjson => {
+                // Serializing a plain struct of strings and numbers cannot fail
+                out.push_str(&serde_json::to_string(line).unwrap_or_default());
+            }
+            Self::Text => {
+                out.push_str(&format!("{} [{}] {}", line.timestamp.to_rfc3339(), line.stream, line.message));
+            }
+            Self::Csv => {
+                out.push_str(&format!(
+                    "{},{},{},{},",
+                    line.seq,
+                    line.timestamp.to_rfc3339(),
+                    line.stream,
+                    line.level.as_deref().unwrap_or_default()
+                ));
+                out.push_str(&csv_field(&line.message));
+            }
+        }
+        out.push('\n');
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Compresses as it goes, handing back whatever output is ready.
+struct Gzip(GzEncoder<Vec<u8>>);
+
+impl Gzip {
+    fn write(&mut self, data: &[u8]) -> Vec<u8> {
+        // Writing into a Vec cannot fail
+        let _ = self.0.write_all(data);
+        std::mem::take(self.0.get_mut())
+    }
+
+    fn finish(self) -> Vec<u8> {
+        self.0.finish().unwrap_or_default()
+    }
+}
+
+struct Export {
+    state: AppState,
+    execution_id: String,
+    format: Format,
+    gzip: Option<Gzip>,
+    after_seq: i64,
+    started: bool,
+    done: bool,
+}
+
+impl Export {
+    fn encode(&mut self, text: String) -> Bytes {
+        match &mut self.gzip {
+            Some(gzip) => Bytes::from(gzip.write(text.as_bytes())),
+            None => Bytes::from(text),
+        }
+    }
+
+    async fn next_chunk(&mut self) -> Result<Option<Bytes>> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let mut text = String::new();
+        if !self.started {
+            self.started = true;
+            text.push_str(self.format.header());
+        }
+
+        let lines = logs::after(&self.state, &self.execution_id, self.after_seq, &LineFilter::default(), BATCH_LINES).await?;
+        for line in &lines {
+            self.format.write_line(&mut text, line);
+        }
+        if let Some(last) = lines.last() {
+            self.after_seq = last.seq;
+        }
+
+        if (lines.len() as i64) < BATCH_LINES {
+            self.done = true;
+            let mut chunk = self.encode(text).to_vec();
+            if let Some(gzip) = self.gzip.take() {
+                chunk.extend(gzip.finish());
+            }
+            return Ok(Some(Bytes::from(chunk)));
+        }
+        Ok(Some(self.encode(text)))
+    }
+}
+
+/// Streams an execution's stored lines in `format`, one batch at a time.
+pub fn stream(
+    state: AppState,
+    execution_id: String,
+    format: Format,
+    gzip: bool,
+) -> impl Stream<Item = Result<Bytes>> + Send {
+    let export = Export {
+        state,
+        execution_id,
+        format,
+        gzip: gzip.then(|| Gzip(GzEncoder::new(Vec::new(), Compression::default()))),
+        after_seq: 0,
+        started: false,
+        done: false,
+    };
+
+    stream::unfold(export, |mut export| async move {
+        match export.next_chunk().await {
+            Ok(Some(chunk)) => Some((Ok(chunk), export)),
+            Ok(None) => None,
+            Err(e) => {
+                export.done = true;
+                Some((Err(e), export))
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use chrono::{TimeZone, Utc};
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    fn line(seq: i64, level: Option<&str>, message: &str) -> LogLine {
+        LogLine {
+            seq,
+            stream: "stdout".to_string(),
+            message: message.to_string(),
+            timestamp: Utc.with_ymd_and_hms(2024, 6, 3, 10, 0, 0).unwrap(),
+            format: None,
+            level: level.map(str::to_string),
+            parsed_message: None,
+            attributes: None,
+        }
+    }
+
+    fn export(format: Format, lines: &[LogLine]) -> String {
+        let mut out = format.header().to_string();
+        for line in lines {
+            format.write_line(&mut out, line);
+        }
+        out
+    }
+
+    #[test]
+    fn quotes_csv_fields_only_when_needed() {
+        assert_eq!(csv_field("plain text"), "plain text");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("carriage\rreturn"), "\"carriage\rreturn\"");
+    }
+
+    #[test]
+    fn writes_csv_with_a_header() {
+        let csv = export(Format::Csv, &[line(1, Some("info"), "started"), line(2, None, "x,\"y\"")]);
+        assert_eq!(
+            csv,
+            "seq,timestamp,stream,level,message\n\
+             1,2024-06-03T10:00:00+00:00,stdout,info,started\n\
+             2,2024-06-03T10:00:00+00:00,stdout,,\"x,\"\"y\"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn writes_text_lines() {
+        assert_eq!(
+            export(Format::Text, &[line(1, Some("warn"), "disk low")]),
+            "2024-06-03T10:00:00+00:00 [stdout] disk low\n"
+        );
+    }
+
+    #[test]
+    fn writes_one_json_object_per_line() {
+        let ndjson = export(Format::Ndjson, &[line(1, Some("info"), "a"), line(2, None, "b")]);
+        let lines: Vec<serde_json::Value> = ndjson.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["level"], "info");
+        assert!(lines[1].get("level").is_none());
+    }
+
+    #[test]
+    fn streamed_gzip_output_decompresses_whole() {
+        let mut gzip = Gzip(GzEncoder::new(Vec::new(), Compression::default()));
+        let parts = ["first batch\n", "", &"second batch\n".repeat(1000)];
+
+        let mut compressed = Vec::new();
+        for part in parts {
+            compressed.extend(gzip.write(part.as_bytes()));
+        }
+        compressed.extend(gzip.finish());
+
+        let mut decompressed = String::new();
+        GzDecoder::new(compressed.as_slice()).read_to_string(&mut decompressed).unwrap();
+        assert_eq!(decompressed, parts.concat());
+    }
+}
@@ -0,0 +1,40 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use tokio::sync::watch;
+
+use crate::{executions::Execution, logs::ingest, AppState};
+
+/// Tracks which executions this process is ingesting output for, and lets
+/// local readers wait for new lines instead of polling.
+#[derive(Debug, Default)]
+pub struct LogHub {
+    ingests: Mutex<HashMap<String, watch::Sender<i64>>>,
+}
+
+impl LogHub {
+    /// Starts ingesting an execution's output unless this process already
+    /// is. Another instance holding the ingest lease makes this a no-op.
+    pub fn ensure(&self, state: &AppState, execution: &Execution) {
+        let mut ingests = self.ingests.lock().unwrap_or_else(|e| e.into_inner());
+        if ingests.contains_key(&execution.id) {
+            return;
+        }
+
+        let (sender, _) = watch::channel(0);
+        ingests.insert(execution.id.clone(), sender.clone());
+        tokio::spawn(ingest::run(state.clone(), execution.clone(), sender));
+    }
+
+    /// Yields the last stored sequence number while this process ingests
+    /// the execution.
+    pub fn subscribe(&self, execution_id: &str) -> Option<watch::Receiver<i64>> {
+        let ingests = self.ingests.lock().unwrap_or_else(|e| e.into_inner());
+        ingests.get(execution_id).map(watch::Sender::subscribe)
+    }
+
+    pub(super) fn finish(&self, execution_id: &str) {
+        let mut ingests = self.ingests.lock().unwrap_or_else(|e| e.into_inner());
+        ingests.remove(execution_id);
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tokio::sync::watch;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::{
+    alerts,
+    clients::control_plane::OutputChunk,
+    error::Result,
+    executions::{self, Execution},
+    lease,
+    logs::{
+        self,
+        parse::{self, ParsingRules},
+        redaction::Redactor,
+        LogLine,
+    },
+    AppState,
+};
+
+const LEASE_SECS: u64 = 30;
+const LEASE_RENEW_INTERVAL: Duration = Duration::from_secs(10);
+/// Longer lines are split so one runaway line cannot grow without bound.
+const MAX_LINE_BYTES: usize = 16 * 1024;
+
+fn lease_key(execution_id: &str) -> String {
+    format!("log_ingest:{}", execution_id)
+}
+
+/// Partial output of one stream, waiting for the rest of its line.
+#[derive(Default)]
+struct PendingStream {
+    buffer: Vec<u8>,
+    in_private_key: bool,
+}
+
+struct Ingest {
+    redactor: Redactor,
+    rules: ParsingRules,
+    streams: HashMap<String, PendingStream>,
+    seq: i64,
+}
+
+impl Ingest {
+    fn line(&mut self, stream: &str, bytes: &[u8], timestamp: DateTime<Utc>) -> LogLine {
+        let pending = self.streams.entry(stream.to_string()).or_default();
+        let text = String::from_utf8_lossy(bytes);
+        let text = text.strip_suffix('\r').unwrap_or(&text);
+        let message = self.redactor.redact_line(text, &mut pending.in_private_key).into_owned();
+        // Parsed after redaction, so fields never hold what was masked
+        let parsed = parse::parse(&message, &self.rules);
+
+        self.seq += 1;
+        LogLine {
+            seq: self.seq,
+            stream: stream.to_string(),
+            timestamp,
+            format: parsed.as_ref().map(|parsed| parsed.format.as_str().to_string()),
+            level: parsed.as_ref().and_then(|parsed| parsed.level.clone()),
+            parsed_message: parsed.as_ref().and_then(|parsed| parsed.message.clone()),
+            attributes: parsed
+                .map(|parsed| serde_json::Value::Object(parsed.attributes))
+                .filter(|attributes| attributes.as_object().is_some_and(|map| !map.is_empty())),
+            message,
+        }
+    }
+
+    /// Splits a chunk into complete lines, keeping any trailing partial line
+    /// for the next chunk.
+    fn chunk(&mut self, chunk: OutputChunk) -> Vec<LogLine> {
+        let timestamp = chunk
+            .timestamp
+            .as_deref()
+            .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+            .map_or_else(Utc::now, |timestamp| timestamp.with_timezone(&Utc));
+
+        let mut buffer = std::mem::take(&mut self.streams.entry(chunk.stream.clone()).or_default().buffer);
+        buffer.extend_from_slice(&chunk.data);
+
+        let mut lines = Vec::new();
+        let mut start = 0;
+        while let Some(end) = buffer[start..].iter().position(|byte| *byte == b'\n').map(|index| start + index) {
+            lines.push(self.line(&chunk.stream, &buffer[start..end], timestamp));
+            start = end + 1;
+        }
+        while buffer.len() - start > MAX_LINE_BYTES {
+            lines.push(self.line(&chunk.stream, &buffer[start..start + MAX_LINE_BYTES], timestamp));
+            start += MAX_LINE_BYTES;
+        }
+
+        buffer.drain(..start);
+        self.streams.entry(chunk.stream).or_default().buffer = buffer;
+        lines
+    }
+
+    /// Emits whatever is left once the stream has ended.
+    fn flush(&mut self) -> Vec<LogLine> {
+        let streams: Vec<(String, Vec<u8>)> = self
+            .streams
+            .iter_mut()
+            .filter(|(_, pending)| !pending.buffer.is_empty())
+            .map(|(stream, pending)| (stream.clone(), std::mem::take(&mut pending.buffer)))
+            .collect();
+
+        let now = Utc::now();
+        streams
+            .into_iter()
+            .map(|(stream, buffer)| self.line(&stream, &buffer, now))
+            .collect()
+    }
+}
+
+/// Whether any instance currently holds the ingest lease.
+pub async fn is_active(state: &AppState, execution_id: &str) -> Result<bool> {
+    lease::is_held(state, &lease_key(execution_id)).await
+}
+
+/// Consumes an execution's output from the control plane, splits it into
+/// lines, redacts and parses them, and stores them. A Redis lease keeps
+/// other instances from ingesting the same execution.
+pub async fn run(state: AppState, execution: Execution, progress: watch::Sender<i64>) {
+    let token = Uuid::new_v4().to_string();
+    let key = lease_key(&execution.id);
+    match lease::acquire(&state, &key, &token, LEASE_SECS).await {
+        Ok(true) => {
+            if let Err(e) = ingest(&state, &execution, &token, &progress).await {
+                warn!("Log ingest for {} stopped: {}", execution.id, e);
+            }
+            if let Err(e) = lease::release(&state, &key, &token).await {
+                warn!("Failed to release log ingest lease for {}: {}", execution.id, e);
+            }
+        }
+        Ok(false) => {}
+        Err(e) => warn!("Failed to take log ingest lease for {}: {}", execution.id, e),
+    }
+
+    state.execution_logs.finish(&execution.id);
+}
+
+async fn ingest(state: &AppState, execution: &Execution, token: &str, progress: &watch::Sender<i64>) -> Result<()> {
+    let mut ingest = Ingest {
+        redactor: Redactor::for_execution(state, execution).await?,
+        rules: executions::log_parsing(state, &execution.id).await?,
+        streams: HashMap::new(),
+        seq: logs::last_seq(state, &execution.id).await?,
+    };
+    let mut alerts = alerts::Evaluator::load(state, execution).await?;
+    let mut output = state.control_plane_client.stream_output(&execution.id).await?;
+    let mut renew = tokio::time::interval(LEASE_RENEW_INTERVAL);
+
+    loop {
+        let lines = tokio::select! {
+            _ = renew.tick() => {
+                lease::renew(state, &lease_key(&execution.id), token, LEASE_SECS).await?;
+                continue;
+            }
+            chunk = output.message() => match chunk? {
+                Some(chunk) => ingest.chunk(chunk),
+                None => break,
+            },
+        };
+        store(state, &execution.id, &lines, progress).await;
+        evaluate(state, &execution.id, &lines, alerts.as_mut()).await;
+    }
+
+    let lines = ingest.flush();
+    store(state, &execution.id, &lines, progress).await;
+    evaluate(state, &execution.id, &lines, alerts.as_mut()).await;
+    Ok(())
+}
+
+async fn evaluate(state: &AppState, execution_id: &str, lines: &[LogLine], alerts: Option<&mut alerts::Evaluator>) {
+    let Some(alerts) = alerts else {
+        return;
+    };
+    if lines.is_empty() {
+        return;
+    }
+    if let Err(e) = alerts.evaluate(state, execution_id, lines).await {
+        warn!("Failed to evaluate alert rules for {}: {}", execution_id, e);
+    }
+}
+
+async fn store(state: &AppState, execution_id: &str, lines: &[LogLine], progress: &watch::Sender<i64>) {
+    let Some(last) = lines.last() else {
+        return;
+    };
+    match logs::append(state, execution_id, lines).await {
+        Ok(()) => {
+            progress.send_replace(last.seq);
+        }
+        Err(e) => error!("Failed to store {} log lines for {}: {}", lines.len(), execution_id, e),
+    }
+}
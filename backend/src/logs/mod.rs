@@ -0,0 +1,128 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::{error::Result, AppState};
+
+pub mod export;
+pub mod hub;
+pub mod ingest;
+pub mod parse;
+pub mod redaction;
+pub mod search;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 500;
+pub const MAX_PAGE_SIZE: i64 = 5000;
+
+/// One line of an execution's stdout or stderr, already redacted. Lines
+/// printed as JSON or logfmt also carry their parsed fields.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct LogLine {
+    pub seq: i64,
+    pub stream: String,
+    /// The raw line
+    pub message: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parsed_message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<serde_json::Value>,
+}
+
+const SELECT_COLUMNS: &str =
+    "seq, stream, message, logged_at AS timestamp, format, level, parsed_message, attributes";
+
+/// Narrows which stored lines are returned.
+#[derive(Debug, Default)]
+pub struct LineFilter {
+    pub stream: Option<String>,
+    pub levels: Vec<String>,
+    /// Parsed attributes that must equal the given value.
+    pub fields: Vec<(String, String)>,
+}
+
+/// How many lines are stored for an execution and the time they span.
+#[derive(Debug, sqlx::FromRow)]
+pub struct Summary {
+    pub lines: i64,
+    pub first_at: Option<DateTime<Utc>>,
+    pub last_at: Option<DateTime<Utc>>,
+}
+
+pub async fn summary(state: &AppState, execution_id: &str) -> Result<Summary> {
+    Ok(sqlx::query_as::<_, Summary>(
+        "SELECT COUNT(*) AS lines, MIN(logged_at) AS first_at, MAX(logged_at) AS last_at \
+         FROM execution_logs WHERE execution_id = $1",
+    )
+    .bind(execution_id)
+    .fetch_one(&state.db)
+    .await?)
+}
+
+/// The highest sequence number stored for an execution, or 0.
+pub async fn last_seq(state: &AppState, execution_id: &str) -> Result<i64> {
+    Ok(sqlx::query_scalar("SELECT COALESCE(MAX(seq), 0) FROM execution_logs WHERE execution_id = $1")
+        .bind(execution_id)
+        .fetch_one(&state.db)
+        .await?)
+}
+
+pub async fn append(state: &AppState, execution_id: &str, lines: &[LogLine]) -> Result<()> {
+    if lines.is_empty() {
+        return Ok(());
+    }
+
+    let mut query = QueryBuilder::<Postgres>::new(
+        "INSERT INTO execution_logs \
+         (execution_id, seq, stream, message, logged_at, format, level, parsed_message, attributes) ",
+    );
+    query.push_values(lines, |mut row, line| {
+        row.push_bind(execution_id)
+            .push_bind(line.seq)
+            .push_bind(&line.stream)
+            .push_bind(&line.message)
+            .push_bind(line.timestamp)
+            .push_bind(&line.format)
+            .push_bind(&line.level)
+            .push_bind(&line.parsed_message)
+            .push_bind(&line.attributes);
+    });
+    query.push(" ON CONFLICT (execution_id, seq) DO NOTHING");
+
+    query.build().execute(&state.db).await?;
+    Ok(())
+}
+
+/// Lines after `after_seq` that match `filter`, in order.
+pub async fn after(
+    state: &AppState,
+    execution_id: &str,
+    after_seq: i64,
+    filter: &LineFilter,
+    limit: i64,
+) -> Result<Vec<LogLine>> {
+    let mut query = QueryBuilder::<Postgres>::new(format!("SELECT {} FROM execution_logs WHERE execution_id = ", SELECT_COLUMNS));
+    query.push_bind(execution_id).push(" AND seq > ").push_bind(after_seq);
+
+    if let Some(stream) = &filter.stream {
+        query.push(" AND stream = ").push_bind(stream);
+    }
+    if !filter.levels.is_empty() {
+        query.push(" AND level = ANY(").push_bind(&filter.levels).push(")");
+    }
+    // Compared as text so `?field.status=500` matches a JSON number too
+    for (key, value) in &filter.fields {
+        query
+            .push(" AND attributes ->> ")
+            .push_bind(key)
+            .push(" = ")
+            .push_bind(value);
+    }
+    query.push(" ORDER BY seq LIMIT ").push_bind(limit);
+
+    Ok(query.build_query_as::<LogLine>().fetch_all(&state.db).await?)
+}
@@ -0,0 +1,342 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::error::{AppError, Result};
+
+const MAX_KEYS: usize = 10;
+const MAX_KEY_LEN: usize = 64;
+const DEFAULT_LEVEL_KEYS: &[&str] = &["level", "lvl", "severity"];
+const DEFAULT_MESSAGE_KEYS: &[&str] = &["msg", "message"];
+/// A line needs at least this many `key=value` pairs to count as logfmt, so
+/// prose such as `retrying with n=3` stays plain text.
+const MIN_LOGFMT_PAIRS: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineFormat {
+    Json,
+    Logfmt,
+}
+
+impl LineFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Logfmt => "logfmt",
+        }
+    }
+}
+
+/// How an execution's output lines are parsed. Set per execution; an empty
+/// `formats` list turns parsing off.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParsingRules {
+    #[serde(default = "default_formats")]
+    pub formats: Vec<LineFormat>,
+    /// Keys holding the level, tried in order; defaults to `level`, `lvl`, `severity`
+    #[serde(default)]
+    pub level_keys: Vec<String>,
+    /// Keys holding the message, tried in order; defaults to `msg`, `message`
+    #[serde(default)]
+    pub message_keys: Vec<String>,
+}
+
+fn default_formats() -> Vec<LineFormat> {
+    vec![LineFormat::Json, LineFormat::Logfmt]
+}
+
+impl Default for ParsingRules {
+    fn default() -> Self {
+        Self {
+            formats: default_formats(),
+            level_keys: Vec::new(),
+            message_keys: Vec::new(),
+        }
+    }
+}
+
+impl ParsingRules {
+    pub fn validate(&self) -> Result<()> {
+        for keys in [&self.level_keys, &self.message_keys] {
+            if keys.len() > MAX_KEYS || keys.iter().any(|key| key.is_empty() || key.len() > MAX_KEY_LEN) {
+                return Err(AppError::BadRequest(format!(
+                    "log_parsing accepts up to {} keys of at most {} characters",
+                    MAX_KEYS, MAX_KEY_LEN
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn level_keys(&self) -> Vec<&str> {
+        keys_or(&self.level_keys, DEFAULT_LEVEL_KEYS)
+    }
+
+    fn message_keys(&self) -> Vec<&str> {
+        keys_or(&self.message_keys, DEFAULT_MESSAGE_KEYS)
+    }
+}
+
+fn keys_or<'a>(keys: &'a [String], defaults: &'a [&'a str]) -> Vec<&'a str> {
+    if keys.is_empty() {
+        defaults.to_vec()
+    } else {
+        keys.iter().map(String::as_str).collect()
+    }
+}
+
+/// The structured view of a line. The raw line is kept alongside it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parsed {
+    pub format: LineFormat,
+    pub level: Option<String>,
+    pub message: Option<String>,
+    pub attributes: Map<String, Value>,
+}
+
+pub fn parse(line: &str, rules: &ParsingRules) -> Option<Parsed> {
+    let trimmed = line.trim();
+    let fields = rules.formats.iter().find_map(|format| {
+        let fields = match format {
+            LineFormat::Json => parse_json(trimmed),
+            LineFormat::Logfmt => parse_logfmt(trimmed),
+        }?;
+        Some((*format, fields))
+    });
+    let (format, mut attributes) = fields?;
+
+    let level = take_first(&mut attributes, &rules.level_keys()).and_then(|value| normalize_level(&value));
+    let message = take_first(&mut attributes, &rules.message_keys()).map(|value| match value {
+        Value::String(message) => message,
+        other => other.to_string(),
+    });
+
+    Some(Parsed {
+        format,
+        level,
+        message,
+        attributes,
+    })
+}
+
+fn take_first(fields: &mut Map<String, Value>, keys: &[&str]) -> Option<Value> {
+    keys.iter().find_map(|key| fields.remove(*key))
+}
+
+/// Maps the spellings loggers use onto `trace`, `debug`, `info`, `warn`,
+/// `error` and `fatal`. Numeric levels follow pino/bunyan.
+pub fn normalize_level(value: &Value) -> Option<String> {
+    let level = match value {
+        Value::Number(number) => match number.as_u64()? {
+            0..=10 => "trace",
+            11..=20 => "debug",
+            21..=30 => "info",
+            31..=40 => "warn",
+            41..=50 => "error",
+            _ => "fatal",
+        },
+        Value::String(name) => match name.to_lowercase().as_str() {
+            "trace" => "trace",
+            "debug" | "dbug" => "debug",
+            "info" | "information" | "notice" => "info",
+            "warn" | "warning" => "warn",
+            "error" | "err" | "eror" => "error",
+            "fatal" | "critical" | "crit" | "panic" | "alert" | "emergency" => "fatal",
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(level.to_string())
+}
+
+/// Orders normalized levels; unknown names have no rank.
+pub fn level_rank(level: &str) -> Option<u8> {
+    match level {
+        "trace" => Some(0),
+        "debug" => Some(1),
+        "info" => Some(2),
+        "warn" => Some(3),
+        "error" => Some(4),
+        "fatal" => Some(5),
+        _ => None,
+    }
+}
+
+fn parse_json(line: &str) -> Option<Map<String, Value>> {
+    if !line.starts_with('{') {
+        return None;
+    }
+    match serde_json::from_str(line).ok()? {
+        Value::Object(fields) => Some(fields),
+        _ => None,
+    }
+}
+
+fn is_key_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/')
+}
+
+/// Parses `key=value key="quoted value"` lines. Every token must be a pair.
+fn parse_logfmt(line: &str) -> Option<Map<String, Value>> {
+    let mut fields = Map::new();
+    let mut rest = line;
+
+    while !rest.is_empty() {
+        let key_end = rest.find(|c: char| !is_key_char(c)).unwrap_or(rest.len());
+        if key_end == 0 || !rest[key_end..].starts_with('=') {
+            return None;
+        }
+        let key = &rest[..key_end];
+        rest = &rest[key_end + 1..];
+
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            let mut end = None;
+            while let Some((index, c)) = chars.next() {
+                match c {
+                    '\\' => {
+                        if let Some((_, escaped)) = chars.next() {
+                            value.push(match escaped {
+                                'n' => '\n',
+                                't' => '\t',
+                                other => other,
+                            });
+                        }
+                    }
+                    '"' => {
+                        end = Some(index);
+                        break;
+                    }
+                    c => value.push(c),
+                }
+            }
+            rest = &quoted[end? + 1..];
+            value
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let value = rest[..end].to_string();
+            rest = &rest[end..];
+            value
+        };
+
+        if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+            return None;
+        }
+        rest = rest.trim_start();
+        fields.insert(key.to_string(), Value::String(value));
+    }
+
+    (fields.len() >= MIN_LOGFMT_PAIRS).then_some(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn rules(level_keys: &[&str], message_keys: &[&str]) -> ParsingRules {
+        ParsingRules {
+            level_keys: level_keys.iter().map(|key| key.to_string()).collect(),
+            message_keys: message_keys.iter().map(|key| key.to_string()).collect(),
+            ..ParsingRules::default()
+        }
+    }
+
+    #[test]
+    fn parses_json_lines() {
+        let parsed = parse(r#"  {"level":"WARN","msg":"disk low","free_mb":12}  "#, &ParsingRules::default()).unwrap();
+        assert_eq!(parsed.format, LineFormat::Json);
+        assert_eq!(parsed.level.as_deref(), Some("warn"));
+        assert_eq!(parsed.message.as_deref(), Some("disk low"));
+        assert_eq!(Value::Object(parsed.attributes), json!({ "free_mb": 12 }));
+
+        // Only objects count
+        assert!(parse("[1, 2]", &ParsingRules::default()).is_none());
+        assert!(parse("{not json", &ParsingRules::default()).is_none());
+    }
+
+    #[test]
+    fn parses_logfmt_quotes_and_escapes() {
+        let line = r#"level=info msg="user \"bob\" logged in\nagain" user_id=42 path=/a.b"#;
+        let parsed = parse(line, &ParsingRules::default()).unwrap();
+        assert_eq!(parsed.format, LineFormat::Logfmt);
+        assert_eq!(parsed.level.as_deref(), Some("info"));
+        assert_eq!(parsed.message.as_deref(), Some("user \"bob\" logged in\nagain"));
+        assert_eq!(Value::Object(parsed.attributes), json!({ "user_id": "42", "path": "/a.b" }));
+    }
+
+    #[test]
+    fn rejects_logfmt_with_trailing_garbage() {
+        for line in [r#"a=1 b="x"y"#, r#"a=1 b="unterminated"#, "a=1 b=2 trailing", "a=1 =2"] {
+            assert!(parse_logfmt(line).is_none(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn prose_is_not_logfmt() {
+        assert!(parse("retrying with n=3", &ParsingRules::default()).is_none());
+        assert!(parse("n=3", &ParsingRules::default()).is_none());
+        assert!(parse("n=3 attempts=2", &ParsingRules::default()).is_some());
+    }
+
+    #[test]
+    fn normalizes_levels() {
+        let cases = [
+            (json!(10), Some("trace")),
+            (json!(20), Some("debug")),
+            (json!(30), Some("info")),
+            (json!(40), Some("warn")),
+            (json!(50), Some("error")),
+            (json!(60), Some("fatal")),
+            (json!(-1), None),
+            (json!("Warning"), Some("warn")),
+            (json!("CRIT"), Some("fatal")),
+            (json!("verbose"), None),
+            (json!(true), None),
+        ];
+        for (value, level) in cases {
+            assert_eq!(normalize_level(&value).as_deref(), level, "{}", value);
+        }
+    }
+
+    #[test]
+    fn default_keys_are_tried_in_order() {
+        let parsed = parse(r#"{"message":"second","msg":"first"}"#, &ParsingRules::default()).unwrap();
+        assert_eq!(parsed.message.as_deref(), Some("first"));
+        assert_eq!(Value::Object(parsed.attributes), json!({ "message": "second" }));
+    }
+
+    #[test]
+    fn custom_keys_replace_the_defaults() {
+        let rules = rules(&["sev"], &["text", "body"]);
+        let parsed = parse(r#"{"sev":"error","body":"boom","level":"info","msg":"kept"}"#, &rules).unwrap();
+        assert_eq!(parsed.level.as_deref(), Some("error"));
+        assert_eq!(parsed.message.as_deref(), Some("boom"));
+        assert_eq!(Value::Object(parsed.attributes), json!({ "level": "info", "msg": "kept" }));
+    }
+
+    #[test]
+    fn only_enabled_formats_are_tried() {
+        let logfmt_only = ParsingRules {
+            formats: vec![LineFormat::Logfmt],
+            ..ParsingRules::default()
+        };
+        assert!(parse(r#"{"level":"info","msg":"x"}"#, &logfmt_only).is_none());
+
+        let off = ParsingRules {
+            formats: Vec::new(),
+            ..ParsingRules::default()
+        };
+        assert!(parse("level=info msg=x", &off).is_none());
+    }
+
+    #[test]
+    fn validates_keys() {
+        assert!(rules(&["sev"], &["text"]).validate().is_ok());
+        assert!(rules(&[""], &[]).validate().is_err());
+        assert!(rules(&[], &[&"k".repeat(MAX_KEY_LEN + 1)]).validate().is_err());
+        assert!(rules(&["k"; MAX_KEYS + 1], &[]).validate().is_err());
+    }
+}
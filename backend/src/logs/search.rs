@@ -0,0 +1,223 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::{
+    error::{AppError, Result},
+    executions::Visibility,
+    AppState,
+};
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 200;
+pub const MAX_QUERY_LEN: usize = 256;
+
+/// Private-use characters mark matches in `ts_headline` output, so the
+/// snippet can be HTML-escaped before the markers become `<mark>` tags.
+const HIGHLIGHT_START: char = '\u{E000}';
+const HIGHLIGHT_END: char = '\u{E001}';
+
+#[derive(Debug)]
+pub struct SearchFilter {
+    pub query: String,
+    pub visibility: Visibility,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub levels: Vec<String>,
+    pub languages: Vec<String>,
+    pub cursor: Option<Cursor>,
+    pub limit: i64,
+}
+
+/// Keyset position of the last hit on a page, opaque to clients.
+#[derive(Debug, Clone)]
+pub struct Cursor {
+    timestamp: DateTime<Utc>,
+    execution_id: String,
+    seq: i64,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!(
+            "{}|{}|{}",
+            self.timestamp.timestamp_micros(),
+            self.seq,
+            self.execution_id
+        ))
+    }
+
+    pub fn decode(value: &str) -> Result<Self> {
+        let invalid = || AppError::BadRequest("Invalid cursor".to_string());
+
+        let decoded = URL_SAFE_NO_PAD.decode(value).map_err(|_| invalid())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+        let mut parts = decoded.splitn(3, '|');
+        let micros = parts.next().and_then(|part| part.parse().ok()).ok_or_else(invalid)?;
+        let seq = parts.next().and_then(|part| part.parse().ok()).ok_or_else(invalid)?;
+        let execution_id = parts.next().ok_or_else(invalid)?;
+
+        Ok(Self {
+            timestamp: DateTime::from_timestamp_micros(micros).ok_or_else(invalid)?,
+            execution_id: execution_id.to_string(),
+            seq,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct Hit {
+    pub execution_id: String,
+    pub seq: i64,
+    pub stream: String,
+    pub level: Option<String>,
+    pub timestamp: DateTime<Utc>,
+    pub language: String,
+    pub workspace_id: Option<String>,
+    /// HTML-escaped excerpt with matches wrapped in `<mark>`
+    pub snippet: String,
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            HIGHLIGHT_START => escaped.push_str("<mark>"),
+            HIGHLIGHT_END => escaped.push_str("</mark>"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Full-text search over stored lines, newest first.
+pub async fn search(state: &AppState, filter: &SearchFilter) -> Result<(Vec<Hit>, Option<String>)> {
+    let mut query = QueryBuilder::<Postgres>::new(
+        "SELECT l.execution_id, l.seq, l.stream, l.level, l.logged_at AS timestamp, e.language, e.workspace_id, \
+         ts_headline('simple', l.message, q, ",
+    );
+    query
+        .push_bind(format!(
+            "StartSel=\"{}\", StopSel=\"{}\", MaxFragments=2, MaxWords=30, MinWords=10",
+            HIGHLIGHT_START, HIGHLIGHT_END
+        ))
+        .push(") AS snippet FROM execution_logs l JOIN executions e ON e.id = l.execution_id, websearch_to_tsquery('simple', ")
+        .push_bind(&filter.query)
+        .push(") q WHERE l.search @@ q");
+
+    match &filter.visibility {
+        Visibility::All => {}
+        Visibility::Owner(owner_id) => {
+            query.push(" AND e.owner_id = ").push_bind(owner_id);
+        }
+        Visibility::Workspace(workspace_id) => {
+            query.push(" AND e.workspace_id = ").push_bind(workspace_id);
+        }
+    }
+    if let Some(since) = filter.since {
+        query.push(" AND l.logged_at >= ").push_bind(since);
+    }
+    if let Some(until) = filter.until {
+        query.push(" AND l.logged_at < ").push_bind(until);
+    }
+    if !filter.levels.is_empty() {
+        query.push(" AND l.level = ANY(").push_bind(&filter.levels).push(")");
+    }
+    if !filter.languages.is_empty() {
+        query.push(" AND e.language = ANY(").push_bind(&filter.languages).push(")");
+    }
+    if let Some(cursor) = &filter.cursor {
+        query
+            .push(" AND (l.logged_at, l.execution_id, l.seq) < (")
+            .push_bind(cursor.timestamp)
+            .push(", ")
+            .push_bind(&cursor.execution_id)
+            .push(", ")
+            .push_bind(cursor.seq)
+            .push(")");
+    }
+
+    // Fetch one extra row to learn whether another page exists
+    query
+        .push(" ORDER BY l.logged_at DESC, l.execution_id DESC, l.seq DESC LIMIT ")
+        .push_bind(filter.limit + 1);
+
+    let mut hits = query.build_query_as::<Hit>().fetch_all(&state.db).await?;
+
+    let next_cursor = if hits.len() as i64 > filter.limit {
+        hits.truncate(filter.limit as usize);
+        hits.last().map(|last| {
+            Cursor {
+                timestamp: last.timestamp,
+                execution_id: last.execution_id.clone(),
+                seq: last.seq,
+            }
+            .encode()
+        })
+    } else {
+        None
+    };
+
+    for hit in &mut hits {
+        hit.snippet = escape_html(&hit.snippet);
+    }
+    Ok((hits, next_cursor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(execution_id: &str) -> Cursor {
+        Cursor {
+            timestamp: DateTime::from_timestamp_micros(1_717_408_800_123_456).unwrap(),
+            execution_id: execution_id.to_string(),
+            seq: 42,
+        }
+    }
+
+    #[test]
+    fn cursors_round_trip() {
+        for execution_id in ["exec-1", "exec|with|pipes", ""] {
+            let decoded = Cursor::decode(&cursor(execution_id).encode()).unwrap();
+            assert_eq!(decoded.timestamp, cursor(execution_id).timestamp);
+            assert_eq!(decoded.seq, 42);
+            assert_eq!(decoded.execution_id, execution_id);
+        }
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        let encode = |raw: &str| URL_SAFE_NO_PAD.encode(raw);
+        for value in [
+            "not base64!".to_string(),
+            URL_SAFE_NO_PAD.encode([0xff, 0xfe]),
+            encode("1717408800123456|42"),
+            encode("soon|42|exec-1"),
+            encode("1717408800123456|next|exec-1"),
+            encode(&format!("{}|42|exec-1", i64::MAX)),
+        ] {
+            assert!(matches!(Cursor::decode(&value), Err(AppError::BadRequest(_))), "{}", value);
+        }
+    }
+
+    #[test]
+    fn escapes_snippets_and_turns_markers_into_marks() {
+        let snippet = format!("<b>\"{}token{}\" & 'x'</b>", HIGHLIGHT_START, HIGHLIGHT_END);
+        assert_eq!(
+            escape_html(&snippet),
+            "&lt;b&gt;&quot;<mark>token</mark>&quot; &amp; &#39;x&#39;&lt;/b&gt;"
+        );
+    }
+
+    #[test]
+    fn literal_mark_tags_stay_escaped() {
+        assert_eq!(escape_html("<mark>x</mark>"), "&lt;mark&gt;x&lt;/mark&gt;");
+    }
+}
@@ -1,29 +1,39 @@
+// tonic::Status makes AppError large; errors are rare enough not to box it
+#![allow(clippy::result_large_err)]
+
 use std::net::SocketAddr;
 use std::sync::Arc;
 
 use axum::{
-    extract::State,
-    http::StatusCode,
+    extract::DefaultBodyLimit,
     response::IntoResponse,
-    routing::{get, post},
+    routing::{delete, get, patch, post, put},
     Json, Router,
 };
-use serde::{Deserialize, Serialize};
 use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
 use tracing::info;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod alerts;
+mod artifacts;
 mod auth;
 mod clients;
 mod config;
 mod error;
+mod executions;
 mod handlers;
+mod lease;
+mod logs;
 mod middleware;
+mod notebooks;
+mod schedules;
+mod secrets;
+mod webhooks;
 mod websocket;
 
+use crate::auth::scopes;
 use crate::config::Config;
-use crate::error::AppError;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -32,6 +42,12 @@ pub struct AppState {
     pub memory_client: Arc<clients::MemoryClient>,
     pub iam_client: Arc<clients::IamClient>,
     pub redis_client: Arc<redis::aio::ConnectionManager>,
+    pub db: sqlx::PgPool,
+    pub session_activity: Arc<auth::session::ActivityTracker>,
+    pub execution_status: Arc<executions::status_hub::StatusHub>,
+    pub artifact_store: Arc<dyn object_store::ObjectStore>,
+    pub execution_logs: Arc<logs::hub::LogHub>,
+    pub notifications: Arc<alerts::NotificationHub>,
 }
 
 #[tokio::main]
@@ -52,10 +68,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let redis_client = redis::Client::open(config.redis_url.as_str())?;
     let redis_conn = Arc::new(redis_client.get_connection_manager().await?);
 
+    // Initialize Postgres pool and apply migrations
+    let db = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(config.database_max_connections)
+        .connect(&config.database_url)
+        .await?;
+    sqlx::migrate!("./migrations").run(&db).await?;
+
     // Initialize gRPC clients
-    let control_plane_client = Arc::new(clients::ControlPlaneClient::new(&config.control_plane_url).await?);
-    let memory_client = Arc::new(clients::MemoryClient::new(&config.memory_service_url).await?);
-    let iam_client = Arc::new(clients::IamClient::new(&config.iam_service_url).await?);
+    let control_plane_client = Arc::new(clients::ControlPlaneClient::new(&config.control_plane_url, config.grpc_json_codec).await?);
+    let memory_client = Arc::new(clients::MemoryClient::new(&config.memory_service_url, config.grpc_json_codec).await?);
+    let iam_client = Arc::new(clients::IamClient::new(&config.iam_service_url, config.grpc_json_codec).await?);
+
+    // Artifact storage
+    let artifact_store = artifacts::build_store(&config)?;
 
     // Create app state
     let state = AppState {
@@ -64,21 +90,54 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         memory_client,
         iam_client,
         redis_client: redis_conn,
+        db,
+        session_activity: Arc::new(Default::default()),
+        execution_status: Arc::new(Default::default()),
+        artifact_store,
+        execution_logs: Arc::new(Default::default()),
+        notifications: Arc::new(Default::default()),
     };
 
+    // Background workers
+    tokio::spawn(executions::watcher::run(state.clone()));
+    tokio::spawn(webhooks::worker::run(state.clone()));
+    tokio::spawn(notebooks::reaper::run(state.clone()));
+    tokio::spawn(artifacts::reaper::run(state.clone()));
+    tokio::spawn(schedules::scheduler::run(state.clone()));
+
     // Build the router
     let app = Router::new()
         // Health check
         .route("/health", get(health_check))
         // Auth routes
-        .nest("/api/auth", auth_routes())
+        .nest("/api/auth", auth_routes(state.clone()))
+        // OAuth2 token endpoint for service accounts
+        .nest("/api/oauth", oauth_routes())
+        // Admin routes
+        .nest("/api/admin", admin_routes(state.clone()))
         // Execution routes
-        .nest("/api/executions", execution_routes())
+        .nest("/api/executions", execution_routes(state.clone()))
+        // Input file uploads
+        .nest("/api/artifacts", artifact_routes(state.clone()))
+        // Notebooks run cell by cell in a shared session
+        .nest("/api/notebooks", notebook_routes(state.clone()))
+        // Recurring executions
+        .nest("/api/schedules", schedule_routes(state.clone()))
+        // Log search across executions
+        .nest("/api/logs", log_routes(state.clone()))
+        // Alert notifications
+        .nest("/api/notifications", notification_routes(state.clone()))
+        // Quota consumption
+        .nest("/api/usage", usage_routes(state.clone()))
+        // Webhook management and delivery logs
+        .nest("/api/webhooks", webhook_routes(state.clone()))
         // Memory routes
-        .nest("/api/memory", memory_routes())
+        .nest("/api/memory", memory_routes(state.clone()))
         // Workspace routes
-        .nest("/api/workspaces", workspace_routes())
-        // WebSocket for logs
+        .nest("/api/workspaces", workspace_routes(state.clone()))
+        // WebSocket tickets
+        .nest("/api/ws", ws_routes(state.clone()))
+        // WebSocket for logs (authenticates during the upgrade)
         .route("/ws/logs", get(websocket::handle_websocket))
         // Add middleware
         .layer(CorsLayer::permissive())
@@ -90,7 +149,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("Web UI backend listening on {}", addr);
     
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
@@ -102,37 +165,205 @@ async fn health_check() -> impl IntoResponse {
     }))
 }
 
-fn auth_routes() -> Router<AppState> {
+fn auth_routes(state: AppState) -> Router<AppState> {
+    let protected = Router::new()
+        .route("/logout", post(handlers::auth::logout))
+        .route("/sessions", get(handlers::auth::list_sessions))
+        .route("/sessions/:id", delete(handlers::auth::revoke_session))
+        .route("/mfa/enroll", post(handlers::auth::enroll_mfa))
+        .route("/mfa/enroll/confirm", post(handlers::auth::confirm_mfa))
+        .route("/mfa/recovery-codes", post(handlers::auth::regenerate_recovery_codes))
+        .route("/mfa/disable", post(handlers::auth::disable_mfa))
+        .layer(axum::middleware::from_fn_with_state(state, middleware::auth::require_auth));
+
     Router::new()
         .route("/login", post(handlers::auth::login))
-        .route("/logout", post(handlers::auth::logout))
         .route("/refresh", post(handlers::auth::refresh_token))
         .route("/me", get(handlers::auth::get_current_user))
+        .route("/mfa/verify", post(handlers::auth::verify_mfa))
+        .merge(protected)
 }
 
-fn execution_routes() -> Router<AppState> {
+fn admin_routes(state: AppState) -> Router<AppState> {
     Router::new()
-        .route("/", post(handlers::executions::create_execution))
+        .route("/unlock", post(handlers::admin::unlock_account))
+        .route("/impersonate", post(handlers::admin::impersonate))
+        .route("/impersonations/:session_id", delete(handlers::admin::end_impersonation))
+        .route("/impersonations/:session_id/audit", get(handlers::admin::get_impersonation_audit_log))
+        .layer(axum::middleware::from_fn(middleware::auth::require_admin))
+        .layer(axum::middleware::from_fn_with_state(state, middleware::auth::require_auth))
+}
+
+fn execution_routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/", get(handlers::executions::list_executions).post(handlers::executions::create_execution))
         .route("/:id", get(handlers::executions::get_execution))
         .route("/:id/logs", get(handlers::executions::get_execution_logs))
+        .route("/:id/logs/export", get(handlers::logs::export_execution_logs))
         .route("/:id/cancel", post(handlers::executions::cancel_execution))
-        .layer(axum::middleware::from_fn(middleware::auth::require_auth))
+        .route("/:id/rerun", post(handlers::executions::rerun_execution))
+        .route("/:id/lineage", get(handlers::executions::get_lineage))
+        .route("/:id/artifacts", get(handlers::artifacts::list_artifacts))
+        .route("/:id/artifacts/inputs/:artifact_id", get(handlers::artifacts::download_input))
+        .route("/:id/artifacts/outputs/*path", get(handlers::artifacts::download_output))
+        .route("/:id/webhooks", get(handlers::webhooks::list_execution_webhooks))
+        .route("/:id/webhooks", post(handlers::webhooks::create_execution_webhook))
+        .layer(axum::middleware::from_fn(|req, next| {
+            middleware::auth::require_method_scope(scopes::EXECUTIONS_READ, scopes::EXECUTIONS_WRITE, req, next)
+        }))
+        .layer(axum::middleware::from_fn_with_state(state, middleware::auth::require_auth))
+}
+
+fn artifact_routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/", post(handlers::artifacts::upload_artifacts))
+        // Uploads are streamed and size-checked per file instead
+        .layer(DefaultBodyLimit::disable())
+        .layer(axum::middleware::from_fn(|req, next| {
+            middleware::auth::require_scope(scopes::EXECUTIONS_WRITE, req, next)
+        }))
+        .layer(axum::middleware::from_fn_with_state(state, middleware::auth::require_auth))
+}
+
+fn notebook_routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/", get(handlers::notebooks::list_notebooks).post(handlers::notebooks::create_notebook))
+        .route(
+            "/:id",
+            get(handlers::notebooks::get_notebook)
+                .patch(handlers::notebooks::update_notebook)
+                .delete(handlers::notebooks::delete_notebook),
+        )
+        .route("/:id/cells", post(handlers::notebooks::create_cell))
+        .route("/:id/cells/:cell_id", patch(handlers::notebooks::update_cell).delete(handlers::notebooks::delete_cell))
+        .route("/:id/cells/:cell_id/run", post(handlers::notebooks::run_cell))
+        .route("/:id/restart", post(handlers::notebooks::restart_notebook))
+        .route("/:id/session", delete(handlers::notebooks::stop_notebook_session))
+        .layer(axum::middleware::from_fn(|req, next| {
+            middleware::auth::require_method_scope(scopes::EXECUTIONS_READ, scopes::EXECUTIONS_WRITE, req, next)
+        }))
+        .layer(axum::middleware::from_fn_with_state(state, middleware::auth::require_auth))
+}
+
+fn schedule_routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/", get(handlers::schedules::list_schedules).post(handlers::schedules::create_schedule))
+        .route(
+            "/:id",
+            get(handlers::schedules::get_schedule)
+                .patch(handlers::schedules::update_schedule)
+                .delete(handlers::schedules::delete_schedule),
+        )
+        .route("/:id/pause", post(handlers::schedules::pause_schedule))
+        .route("/:id/resume", post(handlers::schedules::resume_schedule))
+        .route("/:id/runs", get(handlers::schedules::list_schedule_runs))
+        .layer(axum::middleware::from_fn(|req, next| {
+            middleware::auth::require_method_scope(scopes::EXECUTIONS_READ, scopes::EXECUTIONS_WRITE, req, next)
+        }))
+        .layer(axum::middleware::from_fn_with_state(state, middleware::auth::require_auth))
 }
 
-fn memory_routes() -> Router<AppState> {
+fn log_routes(state: AppState) -> Router<AppState> {
     Router::new()
+        .route("/search", get(handlers::logs::search_logs))
+        .layer(axum::middleware::from_fn(|req, next| {
+            middleware::auth::require_scope(scopes::EXECUTIONS_READ, req, next)
+        }))
+        .layer(axum::middleware::from_fn_with_state(state, middleware::auth::require_auth))
+}
+
+fn notification_routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/", get(handlers::alerts::list_notifications))
+        .route("/:id/read", post(handlers::alerts::mark_notification_read))
+        .layer(axum::middleware::from_fn(|req, next| {
+            middleware::auth::require_scope(scopes::EXECUTIONS_READ, req, next)
+        }))
+        .layer(axum::middleware::from_fn_with_state(state, middleware::auth::require_auth))
+}
+
+fn usage_routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/", get(handlers::usage::get_usage))
+        .layer(axum::middleware::from_fn(|req, next| {
+            middleware::auth::require_scope(scopes::EXECUTIONS_READ, req, next)
+        }))
+        .layer(axum::middleware::from_fn_with_state(state, middleware::auth::require_auth))
+}
+
+fn webhook_routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/:id", delete(handlers::webhooks::delete_webhook))
+        .route("/:id/deliveries", get(handlers::webhooks::list_deliveries))
+        .route("/:id/deliveries/:delivery_id", get(handlers::webhooks::get_delivery))
+        .route("/:id/deliveries/:delivery_id/redeliver", post(handlers::webhooks::redeliver))
+        .layer(axum::middleware::from_fn(|req, next| {
+            middleware::auth::require_method_scope(scopes::EXECUTIONS_READ, scopes::EXECUTIONS_WRITE, req, next)
+        }))
+        .layer(axum::middleware::from_fn_with_state(state, middleware::auth::require_auth))
+}
+
+fn memory_routes(state: AppState) -> Router<AppState> {
+    // Search and query are reads even though they are POSTs
+    let read_routes = Router::new()
         .route("/search", post(handlers::memory::search_memory))
-        .route("/store", post(handlers::memory::store_memory))
         .route("/query", post(handlers::memory::query_memory))
-        .layer(axum::middleware::from_fn(middleware::auth::require_auth))
+        .layer(axum::middleware::from_fn(|req, next| {
+            middleware::auth::require_scope(scopes::MEMORY_READ, req, next)
+        }));
+
+    let write_routes = Router::new()
+        .route("/store", post(handlers::memory::store_memory))
+        .layer(axum::middleware::from_fn(|req, next| {
+            middleware::auth::require_scope(scopes::MEMORY_WRITE, req, next)
+        }));
+
+    read_routes
+        .merge(write_routes)
+        .layer(axum::middleware::from_fn_with_state(state, middleware::auth::require_auth))
 }
 
-fn workspace_routes() -> Router<AppState> {
+fn workspace_routes(state: AppState) -> Router<AppState> {
     Router::new()
         .route("/", get(handlers::workspaces::list_workspaces))
         .route("/", post(handlers::workspaces::create_workspace))
         .route("/:id", get(handlers::workspaces::get_workspace))
         .route("/:id", put(handlers::workspaces::update_workspace))
         .route("/:id", delete(handlers::workspaces::delete_workspace))
-        .layer(axum::middleware::from_fn(middleware::auth::require_auth))
+        .route("/:id/execution-policy", get(handlers::execution_policy::get_execution_policy))
+        .route("/:id/execution-policy", put(handlers::execution_policy::update_execution_policy))
+        .route("/:id/webhooks", get(handlers::webhooks::list_workspace_webhooks))
+        .route("/:id/webhooks", post(handlers::webhooks::create_workspace_webhook))
+        .route("/:id/secrets", get(handlers::secrets::list_secrets))
+        .route("/:id/secrets/:name", put(handlers::secrets::put_secret))
+        .route("/:id/secrets/:name", delete(handlers::secrets::delete_secret))
+        .route("/:id/redaction-rules", get(handlers::redaction_rules::list_redaction_rules))
+        .route("/:id/redaction-rules", post(handlers::redaction_rules::create_redaction_rule))
+        .route("/:id/redaction-rules/:rule_id", delete(handlers::redaction_rules::delete_redaction_rule))
+        .route("/:id/alert-rules", get(handlers::alerts::list_alert_rules))
+        .route("/:id/alert-rules", post(handlers::alerts::create_alert_rule))
+        .route("/:id/alert-rules/:rule_id", patch(handlers::alerts::update_alert_rule))
+        .route("/:id/alert-rules/:rule_id", delete(handlers::alerts::delete_alert_rule))
+        .route("/:id/service-accounts", get(handlers::service_accounts::list_service_accounts))
+        .route("/:id/service-accounts", post(handlers::service_accounts::create_service_account))
+        .route("/:id/service-accounts/:account_id", delete(handlers::service_accounts::disable_service_account))
+        .route("/:id/service-accounts/:account_id/rotate-secret", post(handlers::service_accounts::rotate_service_account_secret))
+        .layer(axum::middleware::from_fn(|req, next| {
+            middleware::auth::require_method_scope(scopes::WORKSPACES_READ, scopes::WORKSPACES_WRITE, req, next)
+        }))
+        .layer(axum::middleware::from_fn_with_state(state, middleware::auth::require_auth))
+}
+
+fn ws_routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/ticket", post(websocket::issue_ticket))
+        .layer(axum::middleware::from_fn(|req, next| {
+            middleware::auth::require_scope(scopes::EXECUTIONS_READ, req, next)
+        }))
+        .layer(axum::middleware::from_fn_with_state(state, middleware::auth::require_auth))
+}
+
+fn oauth_routes() -> Router<AppState> {
+    Router::new()
+        .route("/token", post(handlers::oauth::token))
 }
\ No newline at end of file
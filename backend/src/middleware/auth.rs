@@ -1,13 +1,21 @@
 use axum::{
-    extract::{Request, State},
-    http::{header, StatusCode},
+    extract::{OriginalUri, Request, State},
+    http::{header, Method, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
     Json,
 };
 use serde_json::json;
 
-use crate::{auth::validate_token, AppState};
+use crate::{
+    auth::{
+        impersonation::{self, Guard, Outcome},
+        validation::authenticate,
+        Claims,
+    },
+    error::AppError,
+    AppState,
+};
 
 pub async fn require_auth(
     State(state): State<AppState>,
@@ -31,12 +39,19 @@ pub async fn require_auth(
         }
     };
 
-    match validate_token(token, &state.config.jwt_secret) {
+    match authenticate(&state, token).await {
+        Ok(claims) if claims.is_impersonation() => Ok(run_impersonated(&state, claims, request, next).await),
         Ok(claims) => {
             // Add user info to request extensions
             request.extensions_mut().insert(claims);
             Ok(next.run(request).await)
         }
+        Err(AppError::ServiceUnavailable) => Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({
+                "error": "Identity service unavailable"
+            })),
+        )),
         Err(_) => Err((
             StatusCode::UNAUTHORIZED,
             Json(json!({
@@ -44,4 +59,102 @@ pub async fn require_auth(
             })),
         )),
     }
-}
\ No newline at end of file
+}
+
+/// Applies the destructive-action guard to an impersonated request and
+/// writes the outcome to the audit log.
+async fn run_impersonated(state: &AppState, claims: Claims, mut request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|uri| uri.path().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let confirmed = request
+        .headers()
+        .get(impersonation::CONFIRM_HEADER)
+        .and_then(|value| value.to_str().ok())
+        == Some("true");
+
+    let (response, outcome) = match impersonation::classify(&method, &path) {
+        Guard::Block => (
+            (
+                StatusCode::FORBIDDEN,
+                Json(json!({
+                    "error": "Not permitted while impersonating"
+                })),
+            )
+                .into_response(),
+            Outcome::Blocked,
+        ),
+        Guard::Confirm if !confirmed => (
+            (
+                StatusCode::PRECONDITION_REQUIRED,
+                Json(json!({
+                    "error": "Destructive action requires confirmation while impersonating",
+                    "confirm_header": impersonation::CONFIRM_HEADER,
+                })),
+            )
+                .into_response(),
+            Outcome::Unconfirmed,
+        ),
+        guard => {
+            request.extensions_mut().insert(claims.clone());
+            let outcome = if guard == Guard::Confirm { Outcome::Confirmed } else { Outcome::Allowed };
+            (next.run(request).await, outcome)
+        }
+    };
+
+    impersonation::record(state, &claims, &method, &path, response.status().as_u16(), outcome).await;
+    response
+}
+
+pub async fn require_admin(
+    request: Request,
+    next: Next,
+) -> Result<Response, impl IntoResponse> {
+    match request.extensions().get::<Claims>() {
+        // An impersonation token never carries admin rights, even for the admin behind it
+        Some(claims) if claims.role == "admin" && !claims.is_impersonation() => Ok(next.run(request).await),
+        _ => Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": "Admin access required"
+            })),
+        )),
+    }
+}
+
+/// Requires an OAuth2 scope. Runs after `require_auth`, which provides the
+/// claims; human users are mapped to scopes by role.
+pub async fn require_scope(
+    scope: &'static str,
+    request: Request,
+    next: Next,
+) -> Result<Response, impl IntoResponse> {
+    match request.extensions().get::<Claims>() {
+        Some(claims) if claims.has_scope(scope) => Ok(next.run(request).await),
+        _ => Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": "Insufficient scope",
+                "required_scope": scope,
+            })),
+        )),
+    }
+}
+
+/// Requires `read_scope` for safe methods and `write_scope` for the rest.
+pub async fn require_method_scope(
+    read_scope: &'static str,
+    write_scope: &'static str,
+    request: Request,
+    next: Next,
+) -> Result<Response, impl IntoResponse> {
+    let scope = match *request.method() {
+        Method::GET | Method::HEAD | Method::OPTIONS => read_scope,
+        _ => write_scope,
+    };
+
+    require_scope(scope, request, next).await
+}
@@ -0,0 +1,689 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sqlx::{types::Json, Postgres, QueryBuilder};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        workspace::{self, WorkspaceRole},
+        Claims,
+    },
+    clients::control_plane::CreateSessionRequest,
+    error::{AppError, Result},
+    executions::{outputs, policy, Execution, Visibility, TERMINAL_STATUSES},
+    logs::redaction::Redactor,
+    secrets::{self, SealedEnvironment},
+    AppState,
+};
+
+pub mod reaper;
+
+pub const MAX_CELLS: i64 = 200;
+const MAX_NAME_LEN: usize = 128;
+const MAX_ERROR_LEN: usize = 64 * 1024;
+const MAX_LISTED: i64 = 500;
+/// A claimed run that has not started by then is taken to have been
+/// abandoned, so a crashed request does not block the notebook for good.
+const RUN_CLAIM_TIMEOUT_SECS: i64 = 300;
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Notebook {
+    pub id: Uuid,
+    pub owner_id: String,
+    pub workspace_id: Option<String>,
+    pub name: String,
+    pub language: String,
+    pub version: Option<String>,
+    /// Secret references. Returned as names only
+    #[serde(skip_serializing)]
+    pub environment: Json<HashMap<String, String>>,
+    /// Literal values, which may be credentials
+    #[serde(skip_serializing)]
+    pub sealed_environment: Option<Json<SealedEnvironment>>,
+    #[serde(skip_serializing)]
+    pub session_id: Option<String>,
+    #[serde(skip_serializing)]
+    pub session_started_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing)]
+    pub last_activity_at: Option<DateTime<Utc>>,
+    pub execution_count: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+const SELECT_COLUMNS: &str = "id, owner_id, workspace_id, name, language, version, environment, \
+     sealed_environment, session_id, session_started_at, last_activity_at, execution_count, created_at, updated_at";
+
+impl Notebook {
+    /// The notebook as the API returns it, with its session's state.
+    pub fn view(&self, idle_timeout_secs: u64) -> serde_json::Value {
+        let mut environment: Vec<&String> = self.environment.keys().collect();
+        if let Some(sealed) = &self.sealed_environment {
+            environment.extend(&sealed.names);
+        }
+        environment.sort();
+
+        let session = self.session_id.as_ref().map(|_| {
+            serde_json::json!({
+                "started_at": self.session_started_at,
+                "last_activity_at": self.last_activity_at,
+                "idle_expires_at": self
+                    .last_activity_at
+                    .map(|at| at + Duration::seconds(idle_timeout_secs as i64)),
+            })
+        });
+
+        let mut view = serde_json::to_value(self).unwrap_or_default();
+        view["environment"] = serde_json::json!(environment);
+        view["session"] = serde_json::json!(session);
+        view
+    }
+
+    /// The notebook's whole environment, secret references unresolved.
+    pub fn open_environment(&self, state: &AppState) -> Result<HashMap<String, String>> {
+        let mut environment = self.environment.0.clone();
+        if let Some(sealed) = &self.sealed_environment {
+            environment.extend(sealed.open(state, &sealing_context(self.id))?);
+        }
+        Ok(environment)
+    }
+}
+
+/// Sealed values are bound to their notebook so they cannot be moved.
+fn sealing_context(notebook_id: Uuid) -> String {
+    format!("notebook-environment/{}", notebook_id)
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Cell {
+    pub id: Uuid,
+    pub position: i32,
+    pub source: String,
+    /// The execution of the cell's last run
+    pub execution_id: Option<String>,
+    pub execution_count: Option<i32>,
+    pub status: Option<String>,
+    /// Rich outputs of the last run, redacted and sanitized
+    pub outputs: Option<Json<serde_json::Value>>,
+    pub error: Option<String>,
+    pub ran_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+const CELL_COLUMNS: &str =
+    "id, position, source, execution_id, execution_count, status, outputs, error, ran_at, created_at, updated_at";
+
+pub struct NewNotebook<'a> {
+    pub owner_id: &'a str,
+    pub workspace_id: Option<&'a str>,
+    pub name: &'a str,
+    pub language: &'a str,
+    pub version: Option<&'a str>,
+    pub environment: &'a HashMap<String, String>,
+    pub cells: &'a [String],
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    if name.trim().is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(AppError::BadRequest(format!(
+            "Notebook names must be between 1 and {} characters",
+            MAX_NAME_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// Checks the notebook's language and environment against the execution
+/// policy; each cell's code is checked when it runs.
+async fn validate_runtime(
+    state: &AppState,
+    workspace_id: Option<&str>,
+    language: &str,
+    version: Option<&str>,
+    environment: &HashMap<String, String>,
+) -> Result<()> {
+    let policy = policy::effective(state, workspace_id).await?;
+    policy::validate(
+        &policy,
+        &policy::ExecutionInput {
+            language,
+            version,
+            code: "",
+            environment,
+        },
+    )
+}
+
+/// Notebooks are visible like executions: to their owner, admins and the
+/// workspace's viewers. Changing or running one needs the `user` role.
+pub async fn authorize(state: &AppState, claims: &Claims, id: Uuid, write: bool) -> Result<Notebook> {
+    let notebook = find(state, id).await?.ok_or(AppError::NotFound)?;
+    if claims.role == "admin" || notebook.owner_id == claims.sub {
+        return Ok(notebook);
+    }
+
+    let role = if write { WorkspaceRole::User } else { WorkspaceRole::Viewer };
+    match notebook.workspace_id.as_deref() {
+        Some(workspace_id) => {
+            workspace::require_role(state, claims, workspace_id, role).await?;
+            Ok(notebook)
+        }
+        None => Err(AppError::Forbidden),
+    }
+}
+
+pub async fn create(state: &AppState, new: &NewNotebook<'_>) -> Result<Notebook> {
+    validate_name(new.name)?;
+    if new.cells.len() as i64 > MAX_CELLS {
+        return Err(AppError::BadRequest(format!("A notebook can have at most {} cells", MAX_CELLS)));
+    }
+    validate_runtime(state, new.workspace_id, new.language, new.version, new.environment).await?;
+
+    let id = Uuid::new_v4();
+    let (references, sealed) = SealedEnvironment::seal(state, &sealing_context(id), new.environment)?;
+
+    let mut tx = state.db.begin().await?;
+    let notebook = sqlx::query_as::<_, Notebook>(&format!(
+        "INSERT INTO notebooks (id, owner_id, workspace_id, name, language, version, environment, \
+         sealed_environment) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING {}",
+        SELECT_COLUMNS
+    ))
+    .bind(id)
+    .bind(new.owner_id)
+    .bind(new.workspace_id)
+    .bind(new.name.trim())
+    .bind(new.language)
+    .bind(new.version)
+    .bind(Json(references))
+    .bind(sealed.map(Json))
+    .fetch_one(&mut *tx)
+    .await?;
+
+    for (position, source) in new.cells.iter().enumerate() {
+        sqlx::query("INSERT INTO notebook_cells (id, notebook_id, position, source) VALUES ($1, $2, $3, $4)")
+            .bind(Uuid::new_v4())
+            .bind(notebook.id)
+            .bind(position as i32)
+            .bind(source)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(notebook)
+}
+
+pub async fn find(state: &AppState, id: Uuid) -> Result<Option<Notebook>> {
+    Ok(sqlx::query_as::<_, Notebook>(&format!("SELECT {} FROM notebooks WHERE id = $1", SELECT_COLUMNS))
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?)
+}
+
+/// Most recently changed first.
+pub async fn list(state: &AppState, visibility: &Visibility) -> Result<Vec<Notebook>> {
+    let mut query = QueryBuilder::<Postgres>::new(format!("SELECT {} FROM notebooks WHERE TRUE", SELECT_COLUMNS));
+    match visibility {
+        Visibility::All => {}
+        Visibility::Owner(owner_id) => {
+            query.push(" AND owner_id = ").push_bind(owner_id);
+        }
+        Visibility::Workspace(workspace_id) => {
+            query.push(" AND workspace_id = ").push_bind(workspace_id);
+        }
+    }
+    query.push(" ORDER BY updated_at DESC LIMIT ").push_bind(MAX_LISTED);
+
+    Ok(query.build_query_as::<Notebook>().fetch_all(&state.db).await?)
+}
+
+/// Renames a notebook or replaces its environment. A new environment is
+/// used by the next session, once the current one is stopped.
+pub async fn update(
+    state: &AppState,
+    notebook: &Notebook,
+    name: Option<&str>,
+    environment: Option<&HashMap<String, String>>,
+) -> Result<Notebook> {
+    if let Some(name) = name {
+        validate_name(name)?;
+    }
+    if let Some(environment) = environment {
+        validate_runtime(
+            state,
+            notebook.workspace_id.as_deref(),
+            &notebook.language,
+            notebook.version.as_deref(),
+            environment,
+        )
+        .await?;
+    }
+    let (references, sealed) = match environment {
+        Some(environment) => {
+            let (references, sealed) = SealedEnvironment::seal(state, &sealing_context(notebook.id), environment)?;
+            (Some(references), sealed)
+        }
+        None => (None, None),
+    };
+
+    Ok(sqlx::query_as::<_, Notebook>(&format!(
+        "UPDATE notebooks SET name = COALESCE($2, name), environment = COALESCE($3, environment), \
+         sealed_environment = CASE WHEN $3 IS NULL THEN sealed_environment ELSE $4 END, \
+         updated_at = NOW() WHERE id = $1 RETURNING {}",
+        SELECT_COLUMNS
+    ))
+    .bind(notebook.id)
+    .bind(name.map(str::trim))
+    .bind(references.map(Json))
+    .bind(sealed.map(Json))
+    .fetch_one(&state.db)
+    .await?)
+}
+
+pub async fn delete(state: &AppState, notebook: &Notebook) -> Result<()> {
+    sqlx::query("DELETE FROM notebooks WHERE id = $1")
+        .bind(notebook.id)
+        .execute(&state.db)
+        .await?;
+    if let Some(session_id) = &notebook.session_id {
+        close(state, session_id).await;
+    }
+    Ok(())
+}
+
+pub async fn cells(state: &AppState, notebook_id: Uuid) -> Result<Vec<Cell>> {
+    Ok(sqlx::query_as::<_, Cell>(&format!(
+        "SELECT {} FROM notebook_cells WHERE notebook_id = $1 ORDER BY position",
+        CELL_COLUMNS
+    ))
+    .bind(notebook_id)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+pub async fn find_cell(state: &AppState, notebook_id: Uuid, cell_id: Uuid) -> Result<Option<Cell>> {
+    Ok(sqlx::query_as::<_, Cell>(&format!(
+        "SELECT {} FROM notebook_cells WHERE id = $1 AND notebook_id = $2",
+        CELL_COLUMNS
+    ))
+    .bind(cell_id)
+    .bind(notebook_id)
+    .fetch_optional(&state.db)
+    .await?)
+}
+
+/// Locks the notebook so concurrent edits keep positions contiguous, and
+/// returns how many cells it has.
+async fn lock_cells(tx: &mut sqlx::PgConnection, notebook_id: Uuid) -> Result<i32> {
+    sqlx::query("SELECT id FROM notebooks WHERE id = $1 FOR UPDATE")
+        .bind(notebook_id)
+        .execute(&mut *tx)
+        .await?;
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM notebook_cells WHERE notebook_id = $1")
+        .bind(notebook_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    Ok(count as i32)
+}
+
+async fn touch_notebook(tx: &mut sqlx::PgConnection, notebook_id: Uuid) -> Result<()> {
+    sqlx::query("UPDATE notebooks SET updated_at = NOW() WHERE id = $1")
+        .bind(notebook_id)
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+/// Inserts a cell at `position`, or at the end.
+pub async fn insert_cell(state: &AppState, notebook_id: Uuid, source: &str, position: Option<i32>) -> Result<Cell> {
+    let mut tx = state.db.begin().await?;
+    let count = lock_cells(&mut tx, notebook_id).await?;
+    if count as i64 >= MAX_CELLS {
+        return Err(AppError::Conflict(format!("A notebook can have at most {} cells", MAX_CELLS)));
+    }
+    let position = position.unwrap_or(count).clamp(0, count);
+
+    sqlx::query("UPDATE notebook_cells SET position = position + 1 WHERE notebook_id = $1 AND position >= $2")
+        .bind(notebook_id)
+        .bind(position)
+        .execute(&mut *tx)
+        .await?;
+    let cell = sqlx::query_as::<_, Cell>(&format!(
+        "INSERT INTO notebook_cells (id, notebook_id, position, source) VALUES ($1, $2, $3, $4) RETURNING {}",
+        CELL_COLUMNS
+    ))
+    .bind(Uuid::new_v4())
+    .bind(notebook_id)
+    .bind(position)
+    .bind(source)
+    .fetch_one(&mut *tx)
+    .await?;
+    touch_notebook(&mut tx, notebook_id).await?;
+
+    tx.commit().await?;
+    Ok(cell)
+}
+
+/// Edits a cell's source and/or moves it. The outputs of its last run are
+/// kept until it runs again.
+pub async fn update_cell(
+    state: &AppState,
+    notebook_id: Uuid,
+    cell_id: Uuid,
+    source: Option<&str>,
+    position: Option<i32>,
+) -> Result<Option<Cell>> {
+    let mut tx = state.db.begin().await?;
+    let count = lock_cells(&mut tx, notebook_id).await?;
+
+    let current: Option<i32> =
+        sqlx::query_scalar("SELECT position FROM notebook_cells WHERE id = $1 AND notebook_id = $2")
+            .bind(cell_id)
+            .bind(notebook_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+    let Some(current) = current else {
+        return Ok(None);
+    };
+    let position = position.unwrap_or(current).clamp(0, count - 1);
+
+    if position < current {
+        sqlx::query(
+            "UPDATE notebook_cells SET position = position + 1 \
+             WHERE notebook_id = $1 AND position >= $2 AND position < $3",
+        )
+        .bind(notebook_id)
+        .bind(position)
+        .bind(current)
+        .execute(&mut *tx)
+        .await?;
+    } else if position > current {
+        sqlx::query(
+            "UPDATE notebook_cells SET position = position - 1 \
+             WHERE notebook_id = $1 AND position > $2 AND position <= $3",
+        )
+        .bind(notebook_id)
+        .bind(current)
+        .bind(position)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let cell = sqlx::query_as::<_, Cell>(&format!(
+        "UPDATE notebook_cells SET position = $2, source = COALESCE($3, source), updated_at = NOW() \
+         WHERE id = $1 RETURNING {}",
+        CELL_COLUMNS
+    ))
+    .bind(cell_id)
+    .bind(position)
+    .bind(source)
+    .fetch_one(&mut *tx)
+    .await?;
+    touch_notebook(&mut tx, notebook_id).await?;
+
+    tx.commit().await?;
+    Ok(Some(cell))
+}
+
+pub async fn delete_cell(state: &AppState, notebook_id: Uuid, cell_id: Uuid) -> Result<bool> {
+    let mut tx = state.db.begin().await?;
+    lock_cells(&mut tx, notebook_id).await?;
+
+    let position: Option<i32> =
+        sqlx::query_scalar("DELETE FROM notebook_cells WHERE id = $1 AND notebook_id = $2 RETURNING position")
+            .bind(cell_id)
+            .bind(notebook_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+    let Some(position) = position else {
+        return Ok(false);
+    };
+    sqlx::query("UPDATE notebook_cells SET position = position - 1 WHERE notebook_id = $1 AND position > $2")
+        .bind(notebook_id)
+        .bind(position)
+        .execute(&mut *tx)
+        .await?;
+    touch_notebook(&mut tx, notebook_id).await?;
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// The cell whose run has not finished yet, if any. A session runs one cell
+/// at a time.
+pub async fn running_cell(state: &AppState, notebook_id: Uuid) -> Result<Option<Cell>> {
+    let mut conn = state.db.acquire().await?;
+    running(&mut conn, notebook_id).await
+}
+
+async fn running(conn: &mut sqlx::PgConnection, notebook_id: Uuid) -> Result<Option<Cell>> {
+    Ok(sqlx::query_as::<_, Cell>(&format!(
+        "SELECT {} FROM notebook_cells c WHERE notebook_id = $1 AND EXISTS \
+         (SELECT 1 FROM executions e WHERE e.id = c.execution_id AND NOT (e.status = ANY($2)))",
+        CELL_COLUMNS
+    ))
+    .bind(notebook_id)
+    .bind(TERMINAL_STATUSES)
+    .fetch_optional(&mut *conn)
+    .await?)
+}
+
+/// Reserves the notebook's session for one cell run until `start_run` or
+/// `release_run`. Fails while another cell is running or starting.
+pub async fn claim_run(state: &AppState, notebook_id: Uuid) -> Result<()> {
+    let mut tx = state.db.begin().await?;
+    let claimed_at: Option<DateTime<Utc>> =
+        sqlx::query_scalar("SELECT run_claimed_at FROM notebooks WHERE id = $1 FOR UPDATE")
+            .bind(notebook_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(AppError::NotFound)?;
+    if claimed_at.is_some_and(|at| at > Utc::now() - Duration::seconds(RUN_CLAIM_TIMEOUT_SECS)) {
+        return Err(AppError::Conflict(
+            "Another cell is starting; wait for it or restart the session".to_string(),
+        ));
+    }
+    if let Some(cell) = running(&mut tx, notebook_id).await? {
+        return Err(AppError::Conflict(format!(
+            "Cell {} is still running; wait for it or restart the session",
+            cell.id
+        )));
+    }
+
+    sqlx::query("UPDATE notebooks SET run_claimed_at = NOW() WHERE id = $1")
+        .bind(notebook_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Gives up a claimed run that could not be started.
+pub async fn release_run(state: &AppState, notebook_id: Uuid) -> Result<()> {
+    sqlx::query("UPDATE notebooks SET run_claimed_at = NULL WHERE id = $1")
+        .bind(notebook_id)
+        .execute(&state.db)
+        .await?;
+    Ok(())
+}
+
+/// Returns the notebook's session, starting one if it has none. Secret
+/// references in the environment are resolved just for the control plane.
+pub async fn ensure_session(state: &AppState, notebook: &Notebook) -> Result<String> {
+    if let Some(session_id) = &notebook.session_id {
+        return Ok(session_id.clone());
+    }
+
+    let mut environment = notebook.open_environment(state)?;
+    let secret_values = secrets::resolve(state, notebook.workspace_id.as_deref(), &mut environment).await?;
+    let policy = policy::effective(state, notebook.workspace_id.as_deref()).await?;
+    policy::validate_resolved(&policy, &environment)?;
+    let response = state
+        .control_plane_client
+        .create_session(CreateSessionRequest {
+            language: notebook.language.clone(),
+            version: notebook.version.clone(),
+            environment,
+        })
+        .await
+        .map_err(|e| match e {
+            // The control plane may echo the environment back in its error
+            AppError::GrpcError(status) => AppError::GrpcError(tonic::Status::new(
+                status.code(),
+                secrets::redact(status.message(), &secret_values),
+            )),
+            e => e,
+        })?;
+
+    let claimed = sqlx::query(
+        "UPDATE notebooks SET session_id = $2, session_started_at = NOW(), last_activity_at = NOW(), \
+         execution_count = 0 WHERE id = $1 AND session_id IS NULL",
+    )
+    .bind(notebook.id)
+    .bind(&response.session_id)
+    .execute(&state.db)
+    .await?
+    .rows_affected()
+        > 0;
+    if claimed {
+        return Ok(response.session_id);
+    }
+
+    // Another request started one first; use that and drop ours
+    close(state, &response.session_id).await;
+    find(state, notebook.id)
+        .await?
+        .and_then(|notebook| notebook.session_id)
+        .ok_or_else(|| AppError::Conflict("The notebook's session was stopped while starting".to_string()))
+}
+
+/// Detaches the session from the notebook and closes it. Returns false when
+/// the notebook no longer had that session.
+pub async fn stop_session(state: &AppState, notebook_id: Uuid, session_id: &str) -> Result<bool> {
+    let released = sqlx::query(
+        "UPDATE notebooks SET session_id = NULL, session_started_at = NULL, last_activity_at = NULL \
+         WHERE id = $1 AND session_id = $2",
+    )
+    .bind(notebook_id)
+    .bind(session_id)
+    .execute(&state.db)
+    .await?
+    .rows_affected()
+        > 0;
+    if released {
+        close(state, session_id).await;
+    }
+    Ok(released)
+}
+
+async fn close(state: &AppState, session_id: &str) {
+    if let Err(e) = state.control_plane_client.close_session(session_id).await {
+        warn!("Failed to close notebook session {}: {}", session_id, e);
+    }
+}
+
+/// Clears the session's state; prompt numbers start again from 1.
+pub async fn restart_session(state: &AppState, notebook: &Notebook) -> Result<()> {
+    let Some(session_id) = &notebook.session_id else {
+        return Ok(());
+    };
+    state.control_plane_client.restart_session(session_id).await?;
+    sqlx::query("UPDATE notebooks SET execution_count = 0, last_activity_at = NOW() WHERE id = $1")
+        .bind(notebook.id)
+        .execute(&state.db)
+        .await?;
+    Ok(())
+}
+
+/// Points a cell at the execution that is running it, clears its old
+/// results and releases the notebook's claim.
+pub async fn start_run(state: &AppState, notebook_id: Uuid, cell_id: Uuid, execution: &Execution) -> Result<Cell> {
+    let mut tx = state.db.begin().await?;
+    let execution_count: i32 = sqlx::query_scalar(
+        "UPDATE notebooks SET execution_count = execution_count + 1, run_claimed_at = NULL, \
+         last_activity_at = NOW(), updated_at = NOW() WHERE id = $1 RETURNING execution_count",
+    )
+    .bind(notebook_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    let cell = sqlx::query_as::<_, Cell>(&format!(
+        "UPDATE notebook_cells SET execution_id = $2, execution_count = $3, status = $4, outputs = NULL, \
+         error = NULL, ran_at = NOW(), updated_at = NOW() WHERE id = $1 RETURNING {}",
+        CELL_COLUMNS
+    ))
+    .bind(cell_id)
+    .bind(&execution.id)
+    .bind(execution_count)
+    .bind(&execution.status)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(cell)
+}
+
+/// Stores a finished cell run's outputs on its cell, so the notebook shows
+/// them when reopened.
+pub async fn execution_finished(state: &AppState, execution: &Execution) -> Result<()> {
+    let cell: Option<(Uuid, Uuid)> =
+        sqlx::query_as("SELECT id, notebook_id FROM notebook_cells WHERE execution_id = $1")
+            .bind(&execution.id)
+            .fetch_optional(&state.db)
+            .await?;
+    let Some((cell_id, notebook_id)) = cell else {
+        return Ok(());
+    };
+
+    let response = state.control_plane_client.get_execution(&execution.id).await?;
+    let redactor = Redactor::for_execution(state, execution).await?;
+    let outputs = outputs::render(
+        state.config.execution_max_output_item_bytes,
+        state.config.execution_max_output_bytes,
+        &response.outputs,
+        response.output.as_deref(),
+        &redactor,
+    );
+    let error = response
+        .error
+        .as_deref()
+        .map(|error| redactor.redact(error).chars().take(MAX_ERROR_LEN).collect::<String>());
+
+    sqlx::query(
+        "UPDATE notebook_cells SET status = $3, outputs = $4, error = $5, updated_at = NOW() \
+         WHERE id = $1 AND execution_id = $2",
+    )
+    .bind(cell_id)
+    .bind(&execution.id)
+    .bind(&execution.status)
+    .bind(Json(&outputs))
+    .bind(error)
+    .execute(&state.db)
+    .await?;
+    sqlx::query("UPDATE notebooks SET last_activity_at = NOW() WHERE id = $1 AND session_id IS NOT NULL")
+        .bind(notebook_id)
+        .execute(&state.db)
+        .await?;
+    Ok(())
+}
+
+/// Notebooks whose session has been idle since before `cutoff` and that are
+/// not running or starting a cell.
+pub async fn idle_sessions(state: &AppState, cutoff: DateTime<Utc>) -> Result<Vec<(Uuid, String)>> {
+    Ok(sqlx::query_as(
+        "SELECT n.id, n.session_id FROM notebooks n \
+         WHERE n.session_id IS NOT NULL AND n.last_activity_at < $1 \
+         AND (n.run_claimed_at IS NULL OR n.run_claimed_at < $3) AND NOT EXISTS \
+         (SELECT 1 FROM notebook_cells c JOIN executions e ON e.id = c.execution_id \
+          WHERE c.notebook_id = n.id AND NOT (e.status = ANY($2))) \
+         LIMIT 100",
+    )
+    .bind(cutoff)
+    .bind(TERMINAL_STATUSES)
+    .bind(Utc::now() - Duration::seconds(RUN_CLAIM_TIMEOUT_SECS))
+    .fetch_all(&state.db)
+    .await?)
+}
+
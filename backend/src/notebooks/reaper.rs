@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use tracing::{error, info};
+
+use crate::{notebooks, AppState};
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Stops notebook sessions that have been idle for longer than
+/// `NOTEBOOK_IDLE_TIMEOUT_SECS`. Every replica runs one; a session is only
+/// detached once, so only one of them closes it.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+    info!("Notebook session reaper started");
+
+    loop {
+        interval.tick().await;
+
+        let cutoff = Utc::now() - chrono::Duration::seconds(state.config.notebook_idle_timeout_secs as i64);
+        let idle = match notebooks::idle_sessions(&state, cutoff).await {
+            Ok(idle) => idle,
+            Err(e) => {
+                error!("Failed to load idle notebook sessions: {}", e);
+                continue;
+            }
+        };
+
+        for (notebook_id, session_id) in idle {
+            match notebooks::stop_session(&state, notebook_id, &session_id).await {
+                Ok(true) => info!("Stopped idle session of notebook {}", notebook_id),
+                Ok(false) => {}
+                Err(e) => error!("Failed to stop idle session of notebook {}: {}", notebook_id, e),
+            }
+        }
+    }
+}
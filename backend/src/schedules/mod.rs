@@ -0,0 +1,644 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        workspace::{self, WorkspaceRole},
+        Claims,
+    },
+    clients::control_plane::CreateExecutionRequest,
+    error::{AppError, Result},
+    executions::{self, policy, TERMINAL_STATUSES},
+    logs::parse::ParsingRules,
+    secrets::SealedEnvironment,
+    AppState,
+};
+
+pub mod scheduler;
+
+const MAX_SCHEDULES_PER_WORKSPACE: i64 = 100;
+const MAX_NAME_LEN: usize = 128;
+pub const DEFAULT_TIMEZONE: &str = "UTC";
+const MAX_REASON_LEN: usize = 1000;
+
+/// What happens to occurrences missed while no scheduler was running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CatchUp {
+    /// Skip them; only an occurrence less than a minute late still fires
+    #[default]
+    None,
+    /// Fire once for the most recent one
+    Latest,
+    /// Fire every one of them, oldest first
+    All,
+}
+
+impl CatchUp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Latest => "latest",
+            Self::All => "all",
+        }
+    }
+}
+
+impl FromStr for CatchUp {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(Self::None),
+            "latest" => Ok(Self::Latest),
+            "all" => Ok(Self::All),
+            _ => Err(AppError::BadRequest("catch_up must be none, latest or all".to_string())),
+        }
+    }
+}
+
+/// What happens when an occurrence is due while the previous run is still
+/// going.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Overlap {
+    #[default]
+    Skip,
+    Allow,
+    CancelPrevious,
+}
+
+impl Overlap {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Skip => "skip",
+            Self::Allow => "allow",
+            Self::CancelPrevious => "cancel_previous",
+        }
+    }
+}
+
+impl FromStr for Overlap {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "skip" => Ok(Self::Skip),
+            "allow" => Ok(Self::Allow),
+            "cancel_previous" => Ok(Self::CancelPrevious),
+            _ => Err(AppError::BadRequest(
+                "overlap must be skip, allow or cancel_previous".to_string(),
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, sqlx::FromRow)]
+pub struct Schedule {
+    pub id: Uuid,
+    pub workspace_id: String,
+    pub name: String,
+    pub cron: String,
+    pub timezone: String,
+    /// Its environment holds secret references only
+    #[serde(skip_serializing)]
+    pub request: Json<CreateExecutionRequest>,
+    /// Literal environment values, which may be credentials
+    #[serde(skip_serializing)]
+    pub sealed_environment: Option<Json<SealedEnvironment>>,
+    pub tags: Vec<String>,
+    pub log_parsing: Option<Json<ParsingRules>>,
+    pub catch_up: String,
+    pub overlap: String,
+    pub paused: bool,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub owner_id: String,
+    #[serde(skip_serializing)]
+    pub owner_is_service_account: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+const SELECT_COLUMNS: &str = "id, workspace_id, name, cron, timezone, request, sealed_environment, tags, log_parsing, \
+     catch_up, overlap, paused, next_run_at, last_run_at, owner_id, owner_is_service_account, created_at, updated_at";
+
+impl Schedule {
+    /// The schedule as the API returns it. Environment values are left out;
+    /// they may be credentials.
+    pub fn view(&self) -> serde_json::Value {
+        let mut environment: Vec<&String> = self.request.environment.keys().collect();
+        if let Some(sealed) = &self.sealed_environment {
+            environment.extend(&sealed.names);
+        }
+        environment.sort();
+
+        let mut view = serde_json::to_value(self).unwrap_or_default();
+        view["code"] = serde_json::json!(self.request.code);
+        view["language"] = serde_json::json!(self.request.language);
+        view["version"] = serde_json::json!(self.request.version);
+        view["environment"] = serde_json::json!(environment);
+        view
+    }
+
+    /// The request runs are started with, secret references unresolved.
+    pub fn open_request(&self, state: &AppState) -> Result<CreateExecutionRequest> {
+        let mut request = self.request.0.clone();
+        if let Some(sealed) = &self.sealed_environment {
+            request.environment.extend(sealed.open(state, &sealing_context(self.id))?);
+        }
+        Ok(request)
+    }
+
+    pub fn spec(&self, state: &AppState) -> Result<ScheduleSpec> {
+        Ok(ScheduleSpec {
+            name: self.name.clone(),
+            cron: self.cron.clone(),
+            timezone: self.timezone.clone(),
+            request: self.open_request(state)?,
+            tags: self.tags.clone(),
+            log_parsing: self.log_parsing.as_ref().map(|rules| rules.0.clone()),
+            catch_up: self.catch_up.parse()?,
+            overlap: self.overlap.parse()?,
+        })
+    }
+}
+
+/// Sealed values are bound to their schedule so they cannot be moved.
+fn sealing_context(schedule_id: Uuid) -> String {
+    format!("schedule-environment/{}", schedule_id)
+}
+
+/// The request as stored, with literal environment values sealed.
+fn seal_request(
+    state: &AppState,
+    schedule_id: Uuid,
+    request: &CreateExecutionRequest,
+) -> Result<(CreateExecutionRequest, Option<SealedEnvironment>)> {
+    let (references, sealed) = SealedEnvironment::seal(state, &sealing_context(schedule_id), &request.environment)?;
+    let request = CreateExecutionRequest {
+        environment: references,
+        ..request.clone()
+    };
+    Ok((request, sealed))
+}
+
+/// Everything a schedule is created or replaced with.
+#[derive(Clone)]
+pub struct ScheduleSpec {
+    pub name: String,
+    pub cron: String,
+    pub timezone: String,
+    pub request: CreateExecutionRequest,
+    pub tags: Vec<String>,
+    pub log_parsing: Option<ParsingRules>,
+    pub catch_up: CatchUp,
+    pub overlap: Overlap,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct Run {
+    pub id: Uuid,
+    pub scheduled_for: DateTime<Utc>,
+    pub triggered_at: DateTime<Utc>,
+    /// `pending`, `started`, `skipped` or `failed`
+    pub status: String,
+    pub execution_id: Option<String>,
+    pub reason: Option<String>,
+}
+
+const RUN_COLUMNS: &str = "id, scheduled_for, triggered_at, status, execution_id, reason";
+
+/// A parsed cron expression and the timezone it is read in.
+pub struct Cron {
+    schedule: cron::Schedule,
+    timezone: Tz,
+}
+
+impl Cron {
+    /// Takes standard five-field expressions (`0 3 * * *`) or aliases such as
+    /// `@daily`, and an IANA timezone name.
+    pub fn parse(expression: &str, timezone: &str) -> Result<Self> {
+        let expression = expression.trim();
+        let fields = expression.split_whitespace().count();
+        if !expression.starts_with('@') && fields != 5 {
+            return Err(AppError::BadRequest(
+                "cron must have five fields: minute hour day-of-month month day-of-week".to_string(),
+            ));
+        }
+
+        // The parser wants a seconds field, and numbers days of the week from
+        // 1 (Sunday) rather than crontab's 0
+        let normalized = if expression.starts_with('@') {
+            expression.to_string()
+        } else {
+            let mut fields: Vec<&str> = expression.split_whitespace().collect();
+            let days_of_week = days_of_week(fields[4])?;
+            fields[4] = &days_of_week;
+            format!("0 {}", fields.join(" "))
+        };
+        let schedule = cron::Schedule::from_str(&normalized)
+            .map_err(|e| AppError::BadRequest(format!("Invalid cron expression: {}", e)))?;
+        let timezone = timezone
+            .parse::<Tz>()
+            .map_err(|_| AppError::BadRequest(format!("Unknown timezone {}", timezone)))?;
+
+        Ok(Self { schedule, timezone })
+    }
+
+    /// The first occurrence strictly after `after`. Wall-clock times are
+    /// read in the schedule's timezone, so `0 3 * * *` stays at 03:00 across
+    /// daylight-saving changes.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.schedule
+            .after(&after.with_timezone(&self.timezone))
+            .next()
+            .map(|next| next.with_timezone(&Utc))
+    }
+}
+
+/// Rewrites a crontab day-of-week field (0-7, both 0 and 7 being Sunday, or
+/// names) as the days the cron parser numbers 1 (Sunday) to 7 (Saturday).
+fn days_of_week(field: &str) -> Result<String> {
+    if field == "*" || field == "?" {
+        return Ok(field.to_string());
+    }
+    let invalid = || AppError::BadRequest(format!("Invalid day of the week: {}", field));
+
+    let mut days = [false; 7];
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<usize>().map_err(|_| invalid())?),
+            None => (item, 1),
+        };
+        if step == 0 {
+            return Err(invalid());
+        }
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (0, 7),
+            Some((first, last)) => (day_number(first).ok_or_else(invalid)?, day_number(last).ok_or_else(invalid)?),
+            // `3/2` runs from day 3 to the end of the week
+            None if item.contains('/') => (day_number(range).ok_or_else(invalid)?, 7),
+            None => {
+                let day = day_number(range).ok_or_else(invalid)?;
+                (day, day)
+            }
+        };
+        if first > last {
+            return Err(invalid());
+        }
+        for day in (first..=last).step_by(step) {
+            days[day % 7] = true;
+        }
+    }
+
+    Ok(days
+        .iter()
+        .enumerate()
+        .filter(|(_, &set)| set)
+        .map(|(day, _)| (day + 1).to_string())
+        .collect::<Vec<_>>()
+        .join(","))
+}
+
+/// A crontab day of the week, 0 (Sunday) to 7 (Sunday again).
+fn day_number(day: &str) -> Option<usize> {
+    let number = match day.to_lowercase().as_str() {
+        "sun" | "sunday" => 0,
+        "mon" | "monday" => 1,
+        "tue" | "tues" | "tuesday" => 2,
+        "wed" | "wednesday" => 3,
+        "thu" | "thurs" | "thursday" => 4,
+        "fri" | "friday" => 5,
+        "sat" | "saturday" => 6,
+        other => other.parse().ok()?,
+    };
+    (number <= 7).then_some(number)
+}
+
+impl ScheduleSpec {
+    /// Checks the spec, including the request against the workspace's
+    /// execution policy, and returns its parsed cron.
+    pub async fn validate(&self, state: &AppState, workspace_id: &str) -> Result<Cron> {
+        if self.name.trim().is_empty() || self.name.len() > MAX_NAME_LEN {
+            return Err(AppError::BadRequest(format!(
+                "Schedule names must be between 1 and {} characters",
+                MAX_NAME_LEN
+            )));
+        }
+        let cron = Cron::parse(&self.cron, &self.timezone)?;
+        if cron.next_after(Utc::now()).is_none() {
+            return Err(AppError::BadRequest("The cron expression never fires".to_string()));
+        }
+
+        executions::validate_tags(&self.tags)?;
+        if let Some(rules) = &self.log_parsing {
+            rules.validate()?;
+        }
+        let policy = policy::effective(state, Some(workspace_id)).await?;
+        policy::validate(
+            &policy,
+            &policy::ExecutionInput {
+                language: &self.request.language,
+                version: self.request.version.as_deref(),
+                code: &self.request.code,
+                environment: &self.request.environment,
+            },
+        )?;
+        Ok(cron)
+    }
+}
+
+/// Anyone who can see the workspace can see its schedules. Changing one
+/// takes its owner or a workspace admin, since runs execute as the owner.
+pub async fn authorize(state: &AppState, claims: &Claims, id: Uuid, manage: bool) -> Result<Schedule> {
+    let schedule = find(state, id).await?.ok_or(AppError::NotFound)?;
+    if claims.role == "admin" {
+        return Ok(schedule);
+    }
+
+    let role = workspace::require_role(state, claims, &schedule.workspace_id, WorkspaceRole::Viewer).await?;
+    if manage && schedule.owner_id != claims.sub && role < WorkspaceRole::Admin {
+        return Err(AppError::Forbidden);
+    }
+    Ok(schedule)
+}
+
+pub async fn list(state: &AppState, workspace_id: &str) -> Result<Vec<Schedule>> {
+    Ok(sqlx::query_as::<_, Schedule>(&format!(
+        "SELECT {} FROM schedules WHERE workspace_id = $1 ORDER BY created_at",
+        SELECT_COLUMNS
+    ))
+    .bind(workspace_id)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+pub async fn find(state: &AppState, id: Uuid) -> Result<Option<Schedule>> {
+    Ok(
+        sqlx::query_as::<_, Schedule>(&format!("SELECT {} FROM schedules WHERE id = $1", SELECT_COLUMNS))
+            .bind(id)
+            .fetch_optional(&state.db)
+            .await?,
+    )
+}
+
+pub async fn create(state: &AppState, claims: &Claims, workspace_id: &str, spec: &ScheduleSpec) -> Result<Schedule> {
+    let cron = spec.validate(state, workspace_id).await?;
+
+    let existing: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM schedules WHERE workspace_id = $1")
+        .bind(workspace_id)
+        .fetch_one(&state.db)
+        .await?;
+    if existing >= MAX_SCHEDULES_PER_WORKSPACE {
+        return Err(AppError::Conflict(format!(
+            "A workspace can have at most {} schedules",
+            MAX_SCHEDULES_PER_WORKSPACE
+        )));
+    }
+
+    let id = Uuid::new_v4();
+    let (request, sealed) = seal_request(state, id, &spec.request)?;
+    Ok(sqlx::query_as::<_, Schedule>(&format!(
+        "INSERT INTO schedules (id, workspace_id, name, cron, timezone, request, sealed_environment, tags, \
+         log_parsing, catch_up, overlap, next_run_at, owner_id, owner_is_service_account) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14) RETURNING {}",
+        SELECT_COLUMNS
+    ))
+    .bind(id)
+    .bind(workspace_id)
+    .bind(spec.name.trim())
+    .bind(spec.cron.trim())
+    .bind(&spec.timezone)
+    .bind(Json(request))
+    .bind(sealed.map(Json))
+    .bind(&spec.tags)
+    .bind(spec.log_parsing.as_ref().map(Json))
+    .bind(spec.catch_up.as_str())
+    .bind(spec.overlap.as_str())
+    .bind(cron.next_after(Utc::now()))
+    .bind(&claims.sub)
+    .bind(claims.is_service_account())
+    .fetch_one(&state.db)
+    .await?)
+}
+
+/// Replaces a schedule's spec. When `take_ownership` is set the caller
+/// becomes the owner, so changed code never runs as someone who did not
+/// write it.
+pub async fn update(
+    state: &AppState,
+    claims: &Claims,
+    schedule: &Schedule,
+    spec: &ScheduleSpec,
+    take_ownership: bool,
+) -> Result<Schedule> {
+    let cron = spec.validate(state, &schedule.workspace_id).await?;
+    let (owner_id, owner_is_service_account) = if take_ownership {
+        (claims.sub.as_str(), claims.is_service_account())
+    } else {
+        (schedule.owner_id.as_str(), schedule.owner_is_service_account)
+    };
+
+    let (request, sealed) = seal_request(state, schedule.id, &spec.request)?;
+
+    Ok(sqlx::query_as::<_, Schedule>(&format!(
+        "UPDATE schedules SET name = $2, cron = $3, timezone = $4, request = $5, sealed_environment = $6, \
+         tags = $7, log_parsing = $8, catch_up = $9, overlap = $10, \
+         next_run_at = CASE WHEN paused THEN NULL ELSE $11 END, \
+         owner_id = $12, owner_is_service_account = $13, updated_at = NOW() WHERE id = $1 RETURNING {}",
+        SELECT_COLUMNS
+    ))
+    .bind(schedule.id)
+    .bind(spec.name.trim())
+    .bind(spec.cron.trim())
+    .bind(&spec.timezone)
+    .bind(Json(request))
+    .bind(sealed.map(Json))
+    .bind(&spec.tags)
+    .bind(spec.log_parsing.as_ref().map(Json))
+    .bind(spec.catch_up.as_str())
+    .bind(spec.overlap.as_str())
+    .bind(cron.next_after(Utc::now()))
+    .bind(owner_id)
+    .bind(owner_is_service_account)
+    .fetch_one(&state.db)
+    .await?)
+}
+
+pub async fn delete(state: &AppState, id: Uuid) -> Result<()> {
+    sqlx::query("DELETE FROM schedules WHERE id = $1")
+        .bind(id)
+        .execute(&state.db)
+        .await?;
+    Ok(())
+}
+
+/// Pausing clears the next run. Resuming starts from now; occurrences missed
+/// while paused are not caught up.
+pub async fn set_paused(state: &AppState, schedule: &Schedule, paused: bool) -> Result<Schedule> {
+    let next_run_at = if paused {
+        None
+    } else {
+        Cron::parse(&schedule.cron, &schedule.timezone)?.next_after(Utc::now())
+    };
+
+    Ok(sqlx::query_as::<_, Schedule>(&format!(
+        "UPDATE schedules SET paused = $2, next_run_at = $3, updated_at = NOW() WHERE id = $1 RETURNING {}",
+        SELECT_COLUMNS
+    ))
+    .bind(schedule.id)
+    .bind(paused)
+    .bind(next_run_at)
+    .fetch_one(&state.db)
+    .await?)
+}
+
+/// A schedule's run history, newest first. `before` is the `scheduled_for`
+/// of the last run of the previous page.
+pub async fn runs(state: &AppState, schedule_id: Uuid, before: Option<DateTime<Utc>>, limit: i64) -> Result<Vec<Run>> {
+    Ok(sqlx::query_as::<_, Run>(&format!(
+        "SELECT {} FROM schedule_runs WHERE schedule_id = $1 \
+         AND ($2::TIMESTAMPTZ IS NULL OR scheduled_for < $2) ORDER BY scheduled_for DESC LIMIT $3",
+        RUN_COLUMNS
+    ))
+    .bind(schedule_id)
+    .bind(before)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+/// Schedules whose next run is due, most overdue first.
+pub(crate) async fn due(state: &AppState, now: DateTime<Utc>, limit: i64) -> Result<Vec<Schedule>> {
+    Ok(sqlx::query_as::<_, Schedule>(&format!(
+        "SELECT {} FROM schedules WHERE NOT paused AND next_run_at <= $1 ORDER BY next_run_at LIMIT $2",
+        SELECT_COLUMNS
+    ))
+    .bind(now)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+/// Records an occurrence as `pending`. Returns `None` if it was already
+/// recorded, so it is never fired twice.
+pub(crate) async fn claim_run(
+    state: &AppState,
+    schedule_id: Uuid,
+    scheduled_for: DateTime<Utc>,
+) -> Result<Option<Uuid>> {
+    Ok(sqlx::query_scalar(
+        "INSERT INTO schedule_runs (id, schedule_id, scheduled_for, status) VALUES ($1, $2, $3, 'pending') \
+         ON CONFLICT (schedule_id, scheduled_for) DO NOTHING RETURNING id",
+    )
+    .bind(Uuid::new_v4())
+    .bind(schedule_id)
+    .bind(scheduled_for)
+    .fetch_optional(&state.db)
+    .await?)
+}
+
+pub(crate) async fn finish_run(
+    state: &AppState,
+    run_id: Uuid,
+    status: &str,
+    execution_id: Option<&str>,
+    reason: Option<&str>,
+) -> Result<()> {
+    sqlx::query("UPDATE schedule_runs SET status = $2, execution_id = $3, reason = $4 WHERE id = $1")
+        .bind(run_id)
+        .bind(status)
+        .bind(execution_id)
+        .bind(reason.map(|reason| reason.chars().take(MAX_REASON_LEN).collect::<String>()))
+        .execute(&state.db)
+        .await?;
+    Ok(())
+}
+
+/// The execution of the schedule's latest run, if it is still going.
+pub(crate) async fn unfinished_execution(state: &AppState, schedule_id: Uuid) -> Result<Option<String>> {
+    Ok(sqlx::query_scalar(
+        "SELECT r.execution_id FROM schedule_runs r JOIN executions e ON e.id = r.execution_id \
+         WHERE r.schedule_id = $1 AND NOT (e.status = ANY($2)) ORDER BY r.scheduled_for DESC LIMIT 1",
+    )
+    .bind(schedule_id)
+    .bind(TERMINAL_STATUSES)
+    .fetch_optional(&state.db)
+    .await?)
+}
+
+/// Moves the schedule on to its next occurrence, unless it was paused or
+/// changed meanwhile.
+pub(crate) async fn advance(
+    state: &AppState,
+    schedule_id: Uuid,
+    expected: DateTime<Utc>,
+    next_run_at: Option<DateTime<Utc>>,
+    last_run_at: Option<DateTime<Utc>>,
+) -> Result<()> {
+    sqlx::query(
+        "UPDATE schedules SET next_run_at = $3, last_run_at = COALESCE($4, last_run_at) \
+         WHERE id = $1 AND next_run_at = $2 AND NOT paused",
+    )
+    .bind(schedule_id)
+    .bind(expected)
+    .bind(next_run_at)
+    .bind(last_run_at)
+    .execute(&state.db)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, TimeZone, Weekday};
+
+    use super::*;
+
+    /// The weekdays `expression` fires on, over one week from a Monday.
+    fn weekdays(expression: &str) -> Vec<Weekday> {
+        let cron = Cron::parse(expression, "UTC").unwrap();
+        let mut at = Utc.with_ymd_and_hms(2024, 6, 3, 0, 0, 0).unwrap();
+        let end = at + chrono::Duration::days(7);
+        let mut days = Vec::new();
+        while let Some(next) = cron.next_after(at).filter(|next| *next < end) {
+            days.push(next.weekday());
+            at = next;
+        }
+        days
+    }
+
+    #[test]
+    fn weekday_ranges_use_crontab_numbering() {
+        use Weekday::*;
+        assert_eq!(weekdays("0 3 * * 1-5"), vec![Mon, Tue, Wed, Thu, Fri]);
+        assert_eq!(weekdays("0 3 * * 0"), vec![Sun]);
+        assert_eq!(weekdays("0 3 * * 7"), vec![Sun]);
+        assert_eq!(weekdays("0 3 * * 5-7"), vec![Fri, Sat, Sun]);
+        assert_eq!(weekdays("0 3 * * 0,6"), vec![Sat, Sun]);
+        assert_eq!(weekdays("0 3 * * */2"), vec![Tue, Thu, Sat, Sun]);
+        assert_eq!(weekdays("0 3 * * 1-5/2"), vec![Mon, Wed, Fri]);
+        assert_eq!(weekdays("0 3 * * *").len(), 7);
+    }
+
+    #[test]
+    fn named_days_are_accepted() {
+        use Weekday::*;
+        assert_eq!(weekdays("0 3 * * mon-fri"), vec![Mon, Tue, Wed, Thu, Fri]);
+        assert_eq!(weekdays("0 3 * * SUN"), vec![Sun]);
+        assert_eq!(weekdays("0 3 * * sat,sun"), vec![Sat, Sun]);
+    }
+
+    #[test]
+    fn invalid_days_are_rejected() {
+        for expression in ["0 3 * * 8", "0 3 * * fri-mon", "0 3 * * 1/0", "0 3 * * funday", "0 3 * * 1-"] {
+            assert!(Cron::parse(expression, "UTC").is_err(), "{}", expression);
+        }
+    }
+}
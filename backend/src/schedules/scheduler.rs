@@ -0,0 +1,302 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use super::{CatchUp, Cron, Overlap, Schedule};
+use crate::{
+    auth::{service_accounts, Claims, SERVICE_ROLE},
+    error::Result,
+    executions::launch::launch,
+    lease, schedules, AppState,
+};
+
+const LEADER_KEY: &str = "scheduler_leader";
+const LEADER_LEASE_SECS: u64 = 30;
+const TICK_INTERVAL: Duration = Duration::from_secs(5);
+const BATCH_SIZE: i64 = 50;
+/// Bounds how far back a long outage is walked, and how many missed
+/// occurrences are fired or recorded as skipped.
+const MAX_MISSED_OCCURRENCES: usize = 100;
+const MAX_OCCURRENCE_SCAN: usize = 10_000;
+/// With `catch_up = none`, an occurrence at most this late still fires.
+const MISFIRE_GRACE_SECS: i64 = 60;
+
+/// Takes or keeps the scheduler lock. Only the replica holding it fires
+/// schedules; if it dies, another takes over once the lease expires.
+async fn hold_leadership(state: &AppState, token: &str, leader: bool) -> Result<bool> {
+    if leader && lease::renew(state, LEADER_KEY, token, LEADER_LEASE_SECS).await? {
+        return Ok(true);
+    }
+    lease::acquire(state, LEADER_KEY, token, LEADER_LEASE_SECS).await
+}
+
+/// Fires due schedules on whichever replica holds the scheduler lock. An
+/// occurrence is claimed in `schedule_runs` before it fires, so a lock
+/// handover mid-tick cannot fire it twice.
+pub async fn run(state: AppState) {
+    let token = Uuid::new_v4().to_string();
+    let mut leader = false;
+    let mut interval = tokio::time::interval(TICK_INTERVAL);
+    info!("Scheduler started");
+
+    loop {
+        interval.tick().await;
+
+        let was_leader = leader;
+        leader = match hold_leadership(&state, &token, leader).await {
+            Ok(leader) => leader,
+            Err(e) => {
+                warn!("Failed to take the scheduler lock: {}", e);
+                false
+            }
+        };
+        if leader != was_leader {
+            info!("Scheduler {} leadership", if leader { "took" } else { "lost" });
+        }
+        if !leader {
+            continue;
+        }
+
+        let now = Utc::now();
+        let due = match schedules::due(&state, now, BATCH_SIZE).await {
+            Ok(due) => due,
+            Err(e) => {
+                error!("Failed to load due schedules: {}", e);
+                continue;
+            }
+        };
+        for schedule in due {
+            if let Err(e) = fire(&state, &schedule, now).await {
+                error!("Failed to fire schedule {}: {}", schedule.id, e);
+            }
+        }
+    }
+}
+
+/// The identity runs execute as. Workspace roles, policy and quotas are
+/// checked against the owner, as if they had started the run themselves.
+/// The owner is looked up for every run, so a disabled account or a changed
+/// role takes effect at once; `None` means the owner can no longer run it.
+async fn owner_claims(state: &AppState, schedule: &Schedule) -> Result<Option<Claims>> {
+    let (email, role, workspace_id) = if schedule.owner_is_service_account {
+        if !service_accounts::is_active(state, &schedule.owner_id).await? {
+            return Ok(None);
+        }
+        (String::new(), SERVICE_ROLE.to_string(), Some(schedule.workspace_id.clone()))
+    } else {
+        match state.iam_client.get_user(&schedule.owner_id).await?.user {
+            Some(user) => (user.email, user.role, None),
+            None => return Ok(None),
+        }
+    };
+
+    let now = Utc::now().timestamp();
+    Ok(Some(Claims {
+        sub: schedule.owner_id.clone(),
+        email,
+        role,
+        exp: now + LEADER_LEASE_SECS as i64,
+        iat: now,
+        jti: Uuid::new_v4().to_string(),
+        sid: None,
+        scope: None,
+        workspace_id,
+        act: None,
+    }))
+}
+
+/// Every occurrence from `first` up to `now`, keeping only the latest
+/// `MAX_MISSED_OCCURRENCES`.
+fn occurrences(cron: &Cron, first: DateTime<Utc>, now: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+    let mut occurrences = vec![first];
+    let mut cursor = first;
+    for _ in 0..MAX_OCCURRENCE_SCAN {
+        match cron.next_after(cursor) {
+            Some(next) if next <= now => {
+                occurrences.push(next);
+                cursor = next;
+            }
+            _ => break,
+        }
+    }
+
+    let excess = occurrences.len().saturating_sub(MAX_MISSED_OCCURRENCES);
+    occurrences.split_off(excess)
+}
+
+/// The occurrences due by `now`, split into those to fire and those to
+/// record as missed.
+#[derive(Debug, PartialEq)]
+struct Plan {
+    fire: Vec<DateTime<Utc>>,
+    missed: Vec<DateTime<Utc>>,
+}
+
+fn plan(cron: &Cron, catch_up: CatchUp, first: DateTime<Utc>, now: DateTime<Utc>) -> Plan {
+    let mut missed = occurrences(cron, first, now);
+    let latest = missed.pop().unwrap_or(first);
+    let mut fire = Vec::new();
+    if catch_up == CatchUp::All {
+        fire.append(&mut missed);
+    }
+    if catch_up == CatchUp::None && now - latest > chrono::Duration::seconds(MISFIRE_GRACE_SECS) {
+        missed.push(latest);
+    } else {
+        fire.push(latest);
+    }
+    Plan { fire, missed }
+}
+
+async fn fire(state: &AppState, schedule: &Schedule, now: DateTime<Utc>) -> Result<()> {
+    let Some(first) = schedule.next_run_at else {
+        return Ok(());
+    };
+    let cron = Cron::parse(&schedule.cron, &schedule.timezone)?;
+    let Plan { fire, missed } = plan(&cron, schedule.catch_up.parse()?, first, now);
+
+    // One occurrence failing must not hold back the others or the schedule
+    for scheduled_for in missed {
+        match schedules::claim_run(state, schedule.id, scheduled_for).await {
+            Ok(Some(run_id)) => {
+                if let Err(e) = schedules::finish_run(state, run_id, "skipped", None, Some("missed")).await {
+                    error!("Failed to record missed run {} of schedule {}: {}", run_id, schedule.id, e);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => error!("Failed to claim missed run of schedule {} at {}: {}", schedule.id, scheduled_for, e),
+        }
+    }
+
+    let mut last_run_at = None;
+    for scheduled_for in fire {
+        match schedules::claim_run(state, schedule.id, scheduled_for).await {
+            Ok(Some(run_id)) => {
+                last_run_at = Some(scheduled_for);
+                if let Err(e) = trigger(state, schedule, run_id).await {
+                    error!("Failed to run schedule {} at {}: {}", schedule.id, scheduled_for, e);
+                    if let Err(e) = schedules::finish_run(state, run_id, "failed", None, Some(&e.to_string())).await {
+                        error!("Failed to record failed run {} of schedule {}: {}", run_id, schedule.id, e);
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(e) => error!("Failed to claim run of schedule {} at {}: {}", schedule.id, scheduled_for, e),
+        }
+    }
+
+    schedules::advance(state, schedule.id, first, cron.next_after(now), last_run_at).await
+}
+
+async fn trigger(state: &AppState, schedule: &Schedule, run_id: Uuid) -> Result<()> {
+    let Some(claims) = owner_claims(state, schedule).await? else {
+        return schedules::finish_run(state, run_id, "skipped", None, Some("Schedule owner is disabled")).await;
+    };
+
+    if let Some(previous) = schedules::unfinished_execution(state, schedule.id).await? {
+        match schedule.overlap.parse()? {
+            Overlap::Skip => {
+                let reason = format!("Execution {} is still running", previous);
+                return schedules::finish_run(state, run_id, "skipped", None, Some(&reason)).await;
+            }
+            Overlap::CancelPrevious => {
+                if let Err(e) = state.control_plane_client.cancel_execution(&previous).await {
+                    warn!("Failed to cancel {} for schedule {}: {}", previous, schedule.id, e);
+                }
+            }
+            Overlap::Allow => {}
+        }
+    }
+
+    let request = schedule.open_request(state)?;
+    let log_parsing = schedule.log_parsing.as_ref().map(|rules| &rules.0);
+    match launch(
+        state,
+        &claims,
+        Some(&schedule.workspace_id),
+        &schedule.tags,
+        None,
+        log_parsing,
+        request,
+    )
+    .await
+    {
+        Ok(execution) => schedules::finish_run(state, run_id, "started", Some(&execution.id), None).await,
+        Err(e) => {
+            warn!("Schedule {} failed to start a run: {}", schedule.id, e);
+            schedules::finish_run(state, run_id, "failed", None, Some(&e.to_string())).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    fn hourly() -> Cron {
+        Cron::parse("0 * * * *", "UTC").unwrap()
+    }
+
+    #[test]
+    fn fires_an_on_time_occurrence() {
+        let first = at(2024, 6, 3, 10, 0);
+        for catch_up in [CatchUp::None, CatchUp::Latest, CatchUp::All] {
+            let plan = plan(&hourly(), catch_up, first, first + chrono::Duration::seconds(5));
+            assert_eq!(plan, Plan { fire: vec![first], missed: vec![] }, "{:?}", catch_up);
+        }
+    }
+
+    #[test]
+    fn catch_up_none_fires_only_within_the_grace() {
+        let first = at(2024, 6, 3, 10, 0);
+        let within = first + chrono::Duration::seconds(MISFIRE_GRACE_SECS);
+        assert_eq!(plan(&hourly(), CatchUp::None, first, within).fire, vec![first]);
+
+        let now = at(2024, 6, 3, 12, 5);
+        let plan = plan(&hourly(), CatchUp::None, first, now);
+        assert!(plan.fire.is_empty());
+        assert_eq!(plan.missed, vec![first, at(2024, 6, 3, 11, 0), at(2024, 6, 3, 12, 0)]);
+    }
+
+    #[test]
+    fn catch_up_latest_fires_the_most_recent() {
+        let plan = plan(&hourly(), CatchUp::Latest, at(2024, 6, 3, 10, 0), at(2024, 6, 3, 12, 5));
+        assert_eq!(plan.fire, vec![at(2024, 6, 3, 12, 0)]);
+        assert_eq!(plan.missed, vec![at(2024, 6, 3, 10, 0), at(2024, 6, 3, 11, 0)]);
+    }
+
+    #[test]
+    fn catch_up_all_fires_each_oldest_first() {
+        let plan = plan(&hourly(), CatchUp::All, at(2024, 6, 3, 10, 0), at(2024, 6, 3, 12, 5));
+        assert_eq!(plan.fire, vec![at(2024, 6, 3, 10, 0), at(2024, 6, 3, 11, 0), at(2024, 6, 3, 12, 0)]);
+        assert!(plan.missed.is_empty());
+    }
+
+    #[test]
+    fn long_outages_keep_only_the_latest_occurrences() {
+        let every_minute = Cron::parse("* * * * *", "UTC").unwrap();
+        let now = at(2024, 6, 3, 12, 0);
+        let first = now - chrono::Duration::minutes(500);
+
+        let plan = plan(&every_minute, CatchUp::All, first, now);
+        assert_eq!(plan.fire.len(), MAX_MISSED_OCCURRENCES);
+        assert_eq!(plan.fire.first(), Some(&(now - chrono::Duration::minutes(MAX_MISSED_OCCURRENCES as i64 - 1))));
+        assert_eq!(plan.fire.last(), Some(&now));
+    }
+
+    #[test]
+    fn wall_clock_times_survive_daylight_saving_changes() {
+        // Berlin moves from UTC+1 to UTC+2 on 2024-03-31 and back on 2024-10-27
+        let cron = Cron::parse("0 3 * * *", "Europe/Berlin").unwrap();
+        assert_eq!(cron.next_after(at(2024, 3, 30, 0, 0)), Some(at(2024, 3, 30, 2, 0)));
+        assert_eq!(cron.next_after(at(2024, 3, 30, 2, 0)), Some(at(2024, 3, 31, 1, 0)));
+        assert_eq!(cron.next_after(at(2024, 10, 26, 12, 0)), Some(at(2024, 10, 27, 2, 0)));
+    }
+}
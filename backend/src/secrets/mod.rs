@@ -0,0 +1,453 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{AppError, FieldError, Result},
+    AppState,
+};
+
+const MAX_NAME_LEN: usize = 128;
+const MAX_VALUE_BYTES: usize = 64 * 1024;
+const NONCE_LEN: usize = 12;
+const REFERENCE_PREFIX: &str = "secrets.";
+pub const REDACTED: &str = "[REDACTED]";
+
+/// A secret's metadata. The value is never read back out through the API.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct Secret {
+    pub workspace_id: String,
+    pub name: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_by: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+const SELECT_COLUMNS: &str = "workspace_id, name, created_by, created_at, updated_by, updated_at";
+
+/// A plaintext secret. Its `Debug` output never shows the value, so it
+/// cannot end up in logs or tracing spans by accident.
+#[derive(Clone, Deserialize)]
+#[serde(transparent)]
+pub struct SecretValue(String);
+
+impl SecretValue {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SecretValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct EncryptedSecret {
+    name: String,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+/// Names follow environment variable conventions: `DB_PASSWORD`.
+pub fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+    if !valid {
+        return Err(AppError::BadRequest(
+            "Secret names may only contain A-Z, 0-9 and _, and may not start with a digit".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn cipher(state: &AppState) -> Result<Aes256Gcm> {
+    let key = state.config.secrets_master_key.as_ref().ok_or(AppError::ServiceUnavailable)?;
+    Ok(Aes256Gcm::new(key.into()))
+}
+
+/// Ciphertexts are bound to their workspace and name, so a row copied
+/// elsewhere fails to decrypt.
+fn associated_data(workspace_id: &str, name: &str) -> String {
+    format!("{}/{}", workspace_id, name)
+}
+
+fn encrypt(cipher: &Aes256Gcm, workspace_id: &str, name: &str, value: &str) -> Result<(Vec<u8>, Vec<u8>)> {
+    encrypt_bytes(cipher, &associated_data(workspace_id, name), value.as_bytes())
+}
+
+fn decrypt(cipher: &Aes256Gcm, workspace_id: &str, secret: &EncryptedSecret) -> Result<SecretValue> {
+    let plaintext = decrypt_bytes(
+        cipher,
+        &associated_data(workspace_id, &secret.name),
+        &secret.nonce,
+        &secret.ciphertext,
+    )?;
+    String::from_utf8(plaintext)
+        .map(SecretValue)
+        .map_err(|_| AppError::InternalServerError)
+}
+
+fn encrypt_bytes(cipher: &Aes256Gcm, aad: &str, plaintext: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad: aad.as_bytes(),
+            },
+        )
+        .map_err(|_| AppError::InternalServerError)?;
+    Ok((nonce.to_vec(), ciphertext))
+}
+
+fn decrypt_bytes(cipher: &Aes256Gcm, aad: &str, nonce: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+    if nonce.len() != NONCE_LEN {
+        return Err(AppError::InternalServerError);
+    }
+
+    cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: aad.as_bytes(),
+            },
+        )
+        .map_err(|_| AppError::InternalServerError)
+}
+
+/// Encrypts a value kept inside another record (not a named secret), bound
+/// to `context`. Returns `None` when no master key is configured.
+pub fn seal(state: &AppState, context: &str, plaintext: &str) -> Result<Option<String>> {
+    let Some(key) = state.config.secrets_master_key.as_ref() else {
+        return Ok(None);
+    };
+    let (mut sealed, ciphertext) = encrypt_bytes(&Aes256Gcm::new(key.into()), context, plaintext.as_bytes())?;
+    sealed.extend(ciphertext);
+    Ok(Some(STANDARD.encode(sealed)))
+}
+
+/// Reverses `seal` for the same `context`.
+pub fn unseal(state: &AppState, context: &str, sealed: &str) -> Result<String> {
+    let sealed = STANDARD.decode(sealed).map_err(|_| AppError::InternalServerError)?;
+    if sealed.len() < NONCE_LEN {
+        return Err(AppError::InternalServerError);
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let plaintext = decrypt_bytes(&cipher(state)?, context, nonce, ciphertext)?;
+    String::from_utf8(plaintext).map_err(|_| AppError::InternalServerError)
+}
+
+/// Splits an environment into values that refer to secrets, which are safe
+/// to store as written, and literal values, which may be credentials.
+pub fn split_literals(environment: &HashMap<String, String>) -> (HashMap<String, String>, HashMap<String, String>) {
+    environment
+        .clone()
+        .into_iter()
+        .partition(|(_, value)| has_references(value))
+}
+
+/// The literal environment values of a long-lived record, such as a
+/// notebook or schedule, sealed together. Their names stay readable.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedEnvironment {
+    pub names: Vec<String>,
+    sealed: String,
+}
+
+impl SealedEnvironment {
+    /// Splits `environment` for storage: references are returned as they
+    /// are and literals are sealed under `context`. Refuses to store
+    /// literals at all when no master key is configured.
+    pub fn seal(
+        state: &AppState,
+        context: &str,
+        environment: &HashMap<String, String>,
+    ) -> Result<(HashMap<String, String>, Option<Self>)> {
+        let (references, literals) = split_literals(environment);
+        if literals.is_empty() {
+            return Ok((references, None));
+        }
+
+        let plaintext = serde_json::to_string(&literals).map_err(|_| AppError::InternalServerError)?;
+        let sealed = seal(state, context, &plaintext)?.ok_or_else(|| {
+            AppError::BadRequest(
+                "Literal environment values cannot be stored without a secrets master key".to_string(),
+            )
+        })?;
+        let mut names: Vec<String> = literals.into_keys().collect();
+        names.sort();
+        Ok((references, Some(Self { names, sealed })))
+    }
+
+    /// The literal values, for the `context` they were sealed under.
+    pub fn open(&self, state: &AppState, context: &str) -> Result<HashMap<String, String>> {
+        serde_json::from_str(&unseal(state, context, &self.sealed)?).map_err(|_| AppError::InternalServerError)
+    }
+}
+
+pub async fn list(state: &AppState, workspace_id: &str) -> Result<Vec<Secret>> {
+    Ok(sqlx::query_as::<_, Secret>(&format!(
+        "SELECT {} FROM workspace_secrets WHERE workspace_id = $1 ORDER BY name",
+        SELECT_COLUMNS
+    ))
+    .bind(workspace_id)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+/// Creates or replaces a secret.
+pub async fn put(state: &AppState, workspace_id: &str, name: &str, value: &SecretValue, user_id: &str) -> Result<Secret> {
+    validate_name(name)?;
+    if value.expose().is_empty() || value.expose().len() > MAX_VALUE_BYTES {
+        return Err(AppError::BadRequest(format!(
+            "Secret values must be between 1 and {} bytes",
+            MAX_VALUE_BYTES
+        )));
+    }
+
+    let (nonce, ciphertext) = encrypt(&cipher(state)?, workspace_id, name, value.expose())?;
+
+    Ok(sqlx::query_as::<_, Secret>(&format!(
+        "INSERT INTO workspace_secrets (workspace_id, name, nonce, ciphertext, created_by, updated_by) \
+         VALUES ($1, $2, $3, $4, $5, $5) \
+         ON CONFLICT (workspace_id, name) DO UPDATE SET \
+         nonce = EXCLUDED.nonce, ciphertext = EXCLUDED.ciphertext, \
+         updated_by = EXCLUDED.updated_by, updated_at = NOW() \
+         RETURNING {}",
+        SELECT_COLUMNS
+    ))
+    .bind(workspace_id)
+    .bind(name)
+    .bind(nonce)
+    .bind(ciphertext)
+    .bind(user_id)
+    .fetch_one(&state.db)
+    .await?)
+}
+
+pub async fn delete(state: &AppState, workspace_id: &str, name: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM workspace_secrets WHERE workspace_id = $1 AND name = $2")
+        .bind(workspace_id)
+        .bind(name)
+        .execute(&state.db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Whether a value refers to at least one secret.
+pub fn has_references(value: &str) -> bool {
+    !references(value).is_empty()
+}
+
+/// Finds `{{secrets.NAME}}` references in a value, returning the byte range
+/// of each and the name it refers to. Other `{{...}}` text is left alone.
+fn references(value: &str) -> Vec<(std::ops::Range<usize>, &str)> {
+    let mut found = Vec::new();
+    let mut offset = 0;
+
+    while let Some(start) = value[offset..].find("{{").map(|index| offset + index) {
+        let Some(end) = value[start + 2..].find("}}").map(|index| start + 2 + index) else {
+            break;
+        };
+        match value[start + 2..end].trim().strip_prefix(REFERENCE_PREFIX) {
+            Some(name) => {
+                found.push((start..end + 2, name));
+                offset = end + 2;
+            }
+            None => offset = start + 2,
+        }
+    }
+    found
+}
+
+/// Replaces secret references in environment values with the workspace's
+/// secrets, just before the request leaves for the control plane. Returns
+/// the substituted values so they can be scrubbed from anything echoed back.
+pub async fn resolve(
+    state: &AppState,
+    workspace_id: Option<&str>,
+    environment: &mut HashMap<String, String>,
+) -> Result<Vec<SecretValue>> {
+    let names: BTreeSet<&str> = environment
+        .values()
+        .flat_map(|value| references(value))
+        .map(|(_, name)| name)
+        .collect();
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let Some(workspace_id) = workspace_id else {
+        return Err(AppError::Validation(vec![FieldError::new(
+            "environment",
+            "Secrets can only be used by executions in a workspace",
+        )]));
+    };
+
+    let names: Vec<String> = names.into_iter().map(str::to_string).collect();
+    let rows = sqlx::query_as::<_, EncryptedSecret>(
+        "SELECT name, nonce, ciphertext FROM workspace_secrets WHERE workspace_id = $1 AND name = ANY($2)",
+    )
+    .bind(workspace_id)
+    .bind(&names)
+    .fetch_all(&state.db)
+    .await?;
+
+    let missing: Vec<FieldError> = environment
+        .iter()
+        .flat_map(|(key, value)| {
+            references(value)
+                .into_iter()
+                .filter(|(_, name)| !rows.iter().any(|row| row.name == *name))
+                .map(move |(_, name)| FieldError::new(format!("environment.{}", key), format!("Unknown secret {}", name)))
+        })
+        .collect();
+    if !missing.is_empty() {
+        return Err(AppError::Validation(missing));
+    }
+
+    let cipher = cipher(state)?;
+    let mut values: HashMap<String, SecretValue> = HashMap::with_capacity(rows.len());
+    for row in &rows {
+        values.insert(row.name.clone(), decrypt(&cipher, workspace_id, row)?);
+    }
+
+    for value in environment.values_mut() {
+        if let Some(resolved) = substitute(value, &values) {
+            *value = resolved;
+        }
+    }
+
+    Ok(values.into_values().collect())
+}
+
+/// Returns `value` with its references replaced, or `None` if it has none.
+/// Every referenced name must be in `values`.
+fn substitute(value: &str, values: &HashMap<String, SecretValue>) -> Option<String> {
+    let refs = references(value);
+    if refs.is_empty() {
+        return None;
+    }
+
+    let mut resolved = String::with_capacity(value.len());
+    let mut last = 0;
+    for (range, name) in refs {
+        resolved.push_str(&value[last..range.start]);
+        resolved.push_str(values[name].expose());
+        last = range.end;
+    }
+    resolved.push_str(&value[last..]);
+    Some(resolved)
+}
+
+/// Masks every occurrence of the given secret values in `text`.
+pub fn redact(text: &str, values: &[SecretValue]) -> String {
+    let mut values: Vec<&str> = values.iter().map(SecretValue::expose).filter(|value| !value.is_empty()).collect();
+    // Longest first, so a secret containing another is masked whole
+    values.sort_by_key(|value| std::cmp::Reverse(value.len()));
+
+    values
+        .into_iter()
+        .fold(text.to_string(), |text, value| text.replace(value, REDACTED))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secret(value: &str) -> SecretValue {
+        SecretValue(value.to_string())
+    }
+
+    #[test]
+    fn validates_names() {
+        for name in ["DB_PASSWORD", "_TOKEN", "API_KEY_2"] {
+            assert!(validate_name(name).is_ok(), "{}", name);
+        }
+        for name in ["", "db_password", "2FA_KEY", "DB-PASSWORD", &"A".repeat(MAX_NAME_LEN + 1)] {
+            assert!(validate_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn debug_never_shows_the_value() {
+        assert_eq!(format!("{:?}", secret("hunter2")), REDACTED);
+    }
+
+    #[test]
+    fn finds_references() {
+        let value = "postgres://app:{{secrets.DB_PASSWORD}}@db/{{ secrets.DB_NAME }}?x={{other}}";
+        let names: Vec<&str> = references(value).into_iter().map(|(_, name)| name).collect();
+        assert_eq!(names, vec!["DB_PASSWORD", "DB_NAME"]);
+        assert!(references("{{secrets.UNCLOSED").is_empty());
+        assert!(references("plain").is_empty());
+    }
+
+    #[test]
+    fn substitutes_references() {
+        let values = HashMap::from([
+            ("USER".to_string(), secret("app")),
+            ("PASSWORD".to_string(), secret("hunter2")),
+        ]);
+        assert_eq!(
+            substitute("{{secrets.USER}}:{{secrets.PASSWORD}}@db", &values).as_deref(),
+            Some("app:hunter2@db")
+        );
+        assert_eq!(substitute("no references", &values), None);
+    }
+
+    #[test]
+    fn round_trips_through_encryption() {
+        let cipher = Aes256Gcm::new(&[7u8; 32].into());
+        let (nonce, ciphertext) = encrypt(&cipher, "ws-1", "TOKEN", "hunter2").unwrap();
+        let stored = EncryptedSecret {
+            name: "TOKEN".to_string(),
+            nonce,
+            ciphertext,
+        };
+        assert_eq!(decrypt(&cipher, "ws-1", &stored).unwrap().expose(), "hunter2");
+
+        // Bound to the workspace and name it was written for
+        assert!(decrypt(&cipher, "ws-2", &stored).is_err());
+        let renamed = EncryptedSecret {
+            name: "OTHER".to_string(),
+            ..stored
+        };
+        assert!(decrypt(&cipher, "ws-1", &renamed).is_err());
+    }
+
+    #[test]
+    fn splits_references_from_literals() {
+        let environment = HashMap::from([
+            ("DATABASE_URL".to_string(), "postgres://app:{{secrets.DB_PASSWORD}}@db".to_string()),
+            ("API_TOKEN".to_string(), "hunter2".to_string()),
+        ]);
+        let (references, literals) = split_literals(&environment);
+        assert_eq!(references.keys().collect::<Vec<_>>(), vec!["DATABASE_URL"]);
+        assert_eq!(literals.keys().collect::<Vec<_>>(), vec!["API_TOKEN"]);
+    }
+
+    #[test]
+    fn redacts_longest_values_first() {
+        let values = [secret("abc"), secret("abcdef"), secret("")];
+        assert_eq!(redact("token=abcdef, short=abc", &values), "token=[REDACTED], short=[REDACTED]");
+        assert_eq!(redact("nothing here", &values), "nothing here");
+    }
+}
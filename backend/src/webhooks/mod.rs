@@ -0,0 +1,377 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::Serialize;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    executions::Execution,
+    AppState,
+};
+
+pub mod worker;
+
+pub const EVENT_EXECUTION_FINISHED: &str = "execution.finished";
+pub const SIGNATURE_HEADER: &str = "x-hermes-signature";
+pub const EVENT_HEADER: &str = "x-hermes-event";
+pub const DELIVERY_HEADER: &str = "x-hermes-delivery";
+
+const SECRET_PREFIX: &str = "whsec_";
+const SECRET_LEN: usize = 32;
+const MAX_URL_LEN: usize = 2048;
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub workspace_id: Option<String>,
+    pub execution_id: Option<String>,
+    pub url: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub disabled_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Delivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub execution_id: String,
+    pub event: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub dead_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Attempt {
+    pub attempt: i32,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+    pub attempted_at: DateTime<Utc>,
+}
+
+/// Where a webhook is registered.
+pub enum Target<'a> {
+    Workspace(&'a str),
+    Execution(&'a str),
+}
+
+const WEBHOOK_COLUMNS: &str = "id, workspace_id, execution_id, url, created_by, created_at, disabled_at";
+const DELIVERY_COLUMNS: &str = "id, webhook_id, execution_id, event, payload, status, attempts, next_attempt_at, \
+     last_status_code, last_error, created_at, delivered_at, dead_at";
+
+/// Rejects malformed URLs and literal internal addresses up front. Host
+/// names are checked again when each delivery is made.
+pub fn validate_url(url: &str, allow_private: bool) -> Result<()> {
+    let parsed = reqwest::Url::parse(url).map_err(|_| AppError::BadRequest("Invalid webhook URL".to_string()))?;
+    if url.len() > MAX_URL_LEN || !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err(AppError::BadRequest("Webhook URL must be an http(s) URL".to_string()));
+    }
+
+    let host = parsed.host_str().unwrap_or_default();
+    let literal = host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>().ok();
+    if !allow_private && (literal.is_some_and(|ip| !is_public_ip(ip)) || host == "localhost") {
+        return Err(AppError::BadRequest("Webhook URL must point at a public address".to_string()));
+    }
+    Ok(())
+}
+
+/// Whether deliveries may be sent to `ip`. Loopback, private, link-local,
+/// unique-local and other non-routable ranges are refused so webhooks
+/// cannot reach internal services.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [first, second, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || first == 0
+        // Carrier-grade NAT
+        || (first == 100 && (64..128).contains(&second))
+        // Reserved
+        || first >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local (fc00::/7)
+        || (first & 0xfe00) == 0xfc00
+        // Link local (fe80::/10) and the deprecated site local (fec0::/10)
+        || (first & 0xffc0) == 0xfe80
+        || (first & 0xffc0) == 0xfec0)
+}
+
+/// Signs `{timestamp}.{body}` so receivers can reject replayed deliveries.
+/// The header value is `t=<unix seconds>,v1=<hex HMAC-SHA256>`.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("t={},v1={:x}", timestamp, mac.finalize().into_bytes())
+}
+
+/// Registers a webhook and returns it with its signing secret, which is
+/// never shown again.
+pub async fn create(state: &AppState, target: Target<'_>, url: &str, created_by: &str) -> Result<(Webhook, String)> {
+    validate_url(url, state.config.webhook_allow_private_targets)?;
+
+    let secret = format!(
+        "{}{}",
+        SECRET_PREFIX,
+        rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(SECRET_LEN)
+            .map(char::from)
+            .collect::<String>()
+    );
+    let (workspace_id, execution_id) = match target {
+        Target::Workspace(id) => (Some(id), None),
+        Target::Execution(id) => (None, Some(id)),
+    };
+
+    let webhook = sqlx::query_as::<_, Webhook>(&format!(
+        "INSERT INTO webhooks (id, workspace_id, execution_id, url, secret, created_by) \
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING {}",
+        WEBHOOK_COLUMNS
+    ))
+    .bind(Uuid::new_v4())
+    .bind(workspace_id)
+    .bind(execution_id)
+    .bind(url)
+    .bind(&secret)
+    .bind(created_by)
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok((webhook, secret))
+}
+
+pub async fn list(state: &AppState, target: Target<'_>) -> Result<Vec<Webhook>> {
+    let (column, id) = match target {
+        Target::Workspace(id) => ("workspace_id", id),
+        Target::Execution(id) => ("execution_id", id),
+    };
+
+    Ok(sqlx::query_as::<_, Webhook>(&format!(
+        "SELECT {} FROM webhooks WHERE {} = $1 ORDER BY created_at",
+        WEBHOOK_COLUMNS, column
+    ))
+    .bind(id)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+pub async fn find(state: &AppState, id: Uuid) -> Result<Option<Webhook>> {
+    Ok(sqlx::query_as::<_, Webhook>(&format!(
+        "SELECT {} FROM webhooks WHERE id = $1",
+        WEBHOOK_COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?)
+}
+
+/// Stops future deliveries. Deliveries already queued are still attempted.
+pub async fn disable(state: &AppState, id: Uuid) -> Result<()> {
+    sqlx::query("UPDATE webhooks SET disabled_at = COALESCE(disabled_at, NOW()) WHERE id = $1")
+        .bind(id)
+        .execute(&state.db)
+        .await?;
+    Ok(())
+}
+
+/// Queues a delivery to every active webhook interested in a finished
+/// execution. Safe to call twice; each webhook is notified once. Runs on the
+/// caller's transaction so deliveries commit with the status change.
+pub async fn enqueue_finished(tx: &mut sqlx::PgConnection, execution: &Execution) -> Result<()> {
+    let payload = serde_json::json!({
+        "event": EVENT_EXECUTION_FINISHED,
+        "execution": {
+            "id": execution.id,
+            "status": execution.status,
+            "language": execution.language,
+            "version": execution.language_version,
+            "workspace_id": execution.workspace_id,
+            "owner_id": execution.owner_id,
+            "tags": execution.tags,
+            "created_at": execution.created_at,
+            "completed_at": execution.completed_at,
+        },
+    });
+
+    sqlx::query(
+        "INSERT INTO webhook_deliveries (id, webhook_id, execution_id, event, payload) \
+         SELECT gen_random_uuid(), id, $1, $2, $3 FROM webhooks \
+         WHERE disabled_at IS NULL AND (execution_id = $1 OR workspace_id = $4) \
+         ON CONFLICT (webhook_id, execution_id, event) DO NOTHING",
+    )
+    .bind(&execution.id)
+    .bind(EVENT_EXECUTION_FINISHED)
+    .bind(payload)
+    .bind(&execution.workspace_id)
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+/// Lists a webhook's deliveries, newest first, optionally by status.
+pub async fn deliveries(state: &AppState, webhook_id: Uuid, status: Option<&str>, limit: i64) -> Result<Vec<Delivery>> {
+    Ok(sqlx::query_as::<_, Delivery>(&format!(
+        "SELECT {} FROM webhook_deliveries WHERE webhook_id = $1 AND ($2::TEXT IS NULL OR status = $2) \
+         ORDER BY created_at DESC LIMIT $3",
+        DELIVERY_COLUMNS
+    ))
+    .bind(webhook_id)
+    .bind(status)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+pub async fn delivery(state: &AppState, webhook_id: Uuid, delivery_id: Uuid) -> Result<Option<Delivery>> {
+    Ok(sqlx::query_as::<_, Delivery>(&format!(
+        "SELECT {} FROM webhook_deliveries WHERE id = $1 AND webhook_id = $2",
+        DELIVERY_COLUMNS
+    ))
+    .bind(delivery_id)
+    .bind(webhook_id)
+    .fetch_optional(&state.db)
+    .await?)
+}
+
+pub async fn attempts(state: &AppState, delivery_id: Uuid) -> Result<Vec<Attempt>> {
+    Ok(sqlx::query_as::<_, Attempt>(
+        "SELECT attempt, status_code, error, duration_ms, attempted_at FROM webhook_delivery_attempts \
+         WHERE delivery_id = $1 ORDER BY attempt",
+    )
+    .bind(delivery_id)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+/// Puts a dead-lettered delivery back on the queue with a fresh set of attempts.
+pub async fn redeliver(state: &AppState, webhook_id: Uuid, delivery_id: Uuid) -> Result<()> {
+    let updated = sqlx::query(
+        "UPDATE webhook_deliveries SET status = 'pending', attempts = 0, next_attempt_at = NOW(), dead_at = NULL \
+         WHERE id = $1 AND webhook_id = $2 AND status = 'dead'",
+    )
+    .bind(delivery_id)
+    .bind(webhook_id)
+    .execute(&state.db)
+    .await?;
+
+    if updated.rows_affected() == 0 {
+        return Err(AppError::NotFound);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_internal_addresses() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{} should be refused", ip);
+        }
+        for ip in ["93.184.216.34", "1.1.1.1", "2606:4700::1111"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{} should be allowed", ip);
+        }
+    }
+
+    #[test]
+    fn validates_urls() {
+        assert!(validate_url("https://hooks.example.com/hermes", false).is_ok());
+        assert!(validate_url("ftp://hooks.example.com/", false).is_err());
+        assert!(validate_url("not a url", false).is_err());
+        assert!(validate_url("http://127.0.0.1:8080/hook", false).is_err());
+        assert!(validate_url("http://[::1]/hook", false).is_err());
+        assert!(validate_url("http://localhost/hook", false).is_err());
+        assert!(validate_url("http://127.0.0.1:8080/hook", true).is_ok());
+    }
+
+    fn decode_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn signature_has_timestamp_and_hex_mac() {
+        let signature = sign("whsec_test", 1_700_000_000, b"{}");
+        let (timestamp, mac) = signature.split_once(',').unwrap();
+        assert_eq!(timestamp, "t=1700000000");
+        let mac = mac.strip_prefix("v1=").unwrap();
+        assert_eq!(mac.len(), 64);
+        assert!(mac.chars().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()));
+    }
+
+    #[test]
+    fn signature_verifies_over_timestamp_and_body() {
+        let body = br#"{"event":"execution.finished"}"#;
+        let signature = sign("whsec_test", 1_700_000_000, body);
+        let mac = decode_hex(signature.split_once(",v1=").unwrap().1);
+
+        // What a receiver does: HMAC `<t>.<body>` and compare in constant time
+        let mut expected = Hmac::<Sha256>::new_from_slice(b"whsec_test").unwrap();
+        expected.update(b"1700000000.");
+        expected.update(body);
+        assert!(expected.verify_slice(&mac).is_ok());
+
+        let mut wrong_secret = Hmac::<Sha256>::new_from_slice(b"whsec_other").unwrap();
+        wrong_secret.update(b"1700000000.");
+        wrong_secret.update(body);
+        assert!(wrong_secret.verify_slice(&mac).is_err());
+    }
+
+    #[test]
+    fn signature_changes_with_timestamp_and_body() {
+        let signature = sign("whsec_test", 1_700_000_000, b"{}");
+        assert_ne!(signature, sign("whsec_test", 1_700_000_001, b"{}"));
+        assert_ne!(signature, sign("whsec_test", 1_700_000_000, b"[]"));
+        assert_eq!(signature, sign("whsec_test", 1_700_000_000, b"{}"));
+    }
+}
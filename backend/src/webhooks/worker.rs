@@ -0,0 +1,333 @@
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    error::Result,
+    webhooks::{self, DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER},
+    AppState,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const BATCH_SIZE: i64 = 20;
+const MAX_ERROR_LEN: usize = 500;
+
+#[derive(Debug, sqlx::FromRow)]
+struct DueDelivery {
+    id: Uuid,
+    event: String,
+    payload: serde_json::Value,
+    attempts: i32,
+    url: String,
+    secret: String,
+}
+
+/// Drains the delivery queue. Every replica runs one; rows are leased with
+/// `SKIP LOCKED` so a delivery is only attempted by one of them at a time.
+pub async fn run(state: AppState) {
+    info!("Webhook delivery worker started");
+    loop {
+        match claim(&state).await {
+            Ok(batch) if !batch.is_empty() => {
+                for delivery in batch {
+                    if let Err(e) = attempt(&state, delivery).await {
+                        error!("Failed to record webhook delivery attempt: {}", e);
+                    }
+                }
+                continue;
+            }
+            Ok(_) => {}
+            Err(e) => error!("Failed to claim webhook deliveries: {}", e),
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Takes due deliveries and pushes their next attempt past the HTTP timeout,
+/// so a replica that dies mid-delivery only delays the retry.
+async fn claim(state: &AppState) -> Result<Vec<DueDelivery>> {
+    let lease_secs = (state.config.webhook_timeout_secs * 2) as f64;
+
+    Ok(sqlx::query_as::<_, DueDelivery>(
+        "WITH due AS ( \
+             SELECT id FROM webhook_deliveries \
+             WHERE status = 'pending' AND next_attempt_at <= NOW() \
+             ORDER BY next_attempt_at LIMIT $1 FOR UPDATE SKIP LOCKED \
+         ) \
+         UPDATE webhook_deliveries d SET next_attempt_at = NOW() + make_interval(secs => $2) \
+         FROM due, webhooks w \
+         WHERE d.id = due.id AND w.id = d.webhook_id \
+         RETURNING d.id, d.event, d.payload, d.attempts, w.url, w.secret",
+    )
+    .bind(BATCH_SIZE)
+    .bind(lease_secs)
+    .fetch_all(&state.db)
+    .await?)
+}
+
+/// Seconds to wait after the `attempts`th failure: the base doubled per
+/// earlier failure, capped at `max_secs`.
+fn backoff_secs(base_secs: u64, max_secs: u64, attempts: i32) -> u64 {
+    let exponent = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    base_secs.saturating_mul(1 << exponent).min(max_secs)
+}
+
+#[derive(Debug, PartialEq)]
+enum Next {
+    Delivered,
+    Dead,
+    Retry,
+}
+
+/// What happens to a delivery after its `attempts`th attempt.
+fn next(failed: bool, attempts: i32, max_attempts: i32) -> Next {
+    match (failed, attempts >= max_attempts) {
+        (false, _) => Next::Delivered,
+        (true, true) => Next::Dead,
+        (true, false) => Next::Retry,
+    }
+}
+
+/// Resolves the delivery host and returns the address to connect to. Every
+/// address the name resolves to must be public, so a record that mixes in an
+/// internal address is refused as well.
+async fn resolve_target(url: &reqwest::Url, allow_private: bool) -> std::result::Result<SocketAddr, String> {
+    let host = url.host_str().ok_or("Webhook URL has no host")?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = url.port_or_known_default().ok_or("Webhook URL has no port")?;
+
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("Failed to resolve {}: {}", host, e))?
+        .collect();
+    if !allow_private {
+        if let Some(address) = addresses.iter().find(|address| !webhooks::is_public_ip(address.ip())) {
+            return Err(format!("{} resolves to {}, which is not a public address", host, address.ip()));
+        }
+    }
+    addresses
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("{} did not resolve to any address", host))
+}
+
+/// Posts one signed delivery. The connection goes to the address that was
+/// checked, so a second DNS answer cannot redirect it.
+async fn send(
+    delivery: &DueDelivery,
+    timeout: Duration,
+    allow_private: bool,
+) -> std::result::Result<reqwest::StatusCode, String> {
+    let url = reqwest::Url::parse(&delivery.url).map_err(|e| format!("Invalid webhook URL: {}", e))?;
+    let address = resolve_target(&url, allow_private).await?;
+
+    let mut builder = reqwest::Client::builder()
+        .timeout(timeout)
+        .redirect(reqwest::redirect::Policy::none());
+    if let Some(domain) = url.domain() {
+        builder = builder.resolve(domain, address);
+    }
+    let http = builder.build().map_err(|e| e.to_string())?;
+
+    let mut payload = delivery.payload.clone();
+    payload["delivery_id"] = serde_json::json!(delivery.id);
+    let body = serde_json::to_vec(&payload).unwrap_or_default();
+    let signature = webhooks::sign(&delivery.secret, Utc::now().timestamp(), &body);
+
+    http.post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, signature)
+        .header(EVENT_HEADER, &delivery.event)
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .body(body)
+        .send()
+        .await
+        .map(|response| response.status())
+        .map_err(|e| e.to_string())
+}
+
+async fn attempt(state: &AppState, delivery: DueDelivery) -> Result<()> {
+    let started = Instant::now();
+    let result = send(
+        &delivery,
+        Duration::from_secs(state.config.webhook_timeout_secs),
+        state.config.webhook_allow_private_targets,
+    )
+    .await;
+    let duration_ms = started.elapsed().as_millis().min(i32::MAX as u128) as i32;
+
+    let (status_code, error) = match result {
+        Ok(status) if status.is_success() => (Some(status.as_u16() as i32), None),
+        Ok(status) => (Some(status.as_u16() as i32), Some(format!("Receiver responded with {}", status))),
+        Err(e) => (None, Some(e.chars().take(MAX_ERROR_LEN).collect())),
+    };
+    let attempts = delivery.attempts + 1;
+
+    let mut tx = state.db.begin().await?;
+    sqlx::query(
+        "INSERT INTO webhook_delivery_attempts (delivery_id, attempt, status_code, error, duration_ms) \
+         VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(delivery.id)
+    .bind(attempts)
+    .bind(status_code)
+    .bind(&error)
+    .bind(duration_ms)
+    .execute(&mut *tx)
+    .await?;
+
+    match next(error.is_some(), attempts, state.config.webhook_max_attempts) {
+        Next::Delivered => {
+            sqlx::query(
+                "UPDATE webhook_deliveries SET status = 'delivered', attempts = $2, last_status_code = $3, \
+                 last_error = NULL, delivered_at = NOW() WHERE id = $1",
+            )
+            .bind(delivery.id)
+            .bind(attempts)
+            .bind(status_code)
+            .execute(&mut *tx)
+            .await?;
+        }
+        Next::Dead => {
+            warn!("Webhook delivery {} dead-lettered after {} attempts", delivery.id, attempts);
+            sqlx::query(
+                "UPDATE webhook_deliveries SET status = 'dead', attempts = $2, last_status_code = $3, \
+                 last_error = $4, dead_at = NOW() WHERE id = $1",
+            )
+            .bind(delivery.id)
+            .bind(attempts)
+            .bind(status_code)
+            .bind(&error)
+            .execute(&mut *tx)
+            .await?;
+        }
+        Next::Retry => {
+            sqlx::query(
+                "UPDATE webhook_deliveries SET attempts = $2, last_status_code = $3, last_error = $4, \
+                 next_attempt_at = NOW() + make_interval(secs => $5) WHERE id = $1",
+            )
+            .bind(delivery.id)
+            .bind(attempts)
+            .bind(status_code)
+            .bind(&error)
+            .bind(backoff_secs(
+                state.config.webhook_backoff_base_secs,
+                state.config.webhook_backoff_max_secs,
+                attempts,
+            ) as f64)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+
+    use axum::{http::HeaderMap, http::StatusCode, routing::post, Router};
+
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let schedule: Vec<u64> = (1..=10).map(|attempts| backoff_secs(10, 3600, attempts)).collect();
+        assert_eq!(schedule, vec![10, 20, 40, 80, 160, 320, 640, 1280, 2560, 3600]);
+        assert_eq!(backoff_secs(10, 3600, 0), 10);
+        assert_eq!(backoff_secs(10, 3600, i32::MAX), 3600);
+    }
+
+    #[test]
+    fn gives_up_after_max_attempts() {
+        assert_eq!(next(false, 1, 3), Next::Delivered);
+        assert_eq!(next(false, 3, 3), Next::Delivered);
+        assert_eq!(next(true, 1, 3), Next::Retry);
+        assert_eq!(next(true, 2, 3), Next::Retry);
+        assert_eq!(next(true, 3, 3), Next::Dead);
+    }
+
+    fn delivery(url: String) -> DueDelivery {
+        DueDelivery {
+            id: Uuid::new_v4(),
+            event: webhooks::EVENT_EXECUTION_FINISHED.to_string(),
+            payload: serde_json::json!({ "event": webhooks::EVENT_EXECUTION_FINISHED }),
+            attempts: 0,
+            url,
+            secret: "whsec_test".to_string(),
+        }
+    }
+
+    /// Serves a receiver that fails the first request and accepts the rest,
+    /// keeping the headers and body of the last one.
+    async fn receiver() -> (String, Arc<AtomicUsize>, Arc<Mutex<Option<(HeaderMap, Vec<u8>)>>>) {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let last = Arc::new(Mutex::new(None));
+        let app = Router::new().route(
+            "/hook",
+            post({
+                let hits = hits.clone();
+                let last = last.clone();
+                move |headers: HeaderMap, body: axum::body::Bytes| async move {
+                    *last.lock().unwrap() = Some((headers, body.to_vec()));
+                    match hits.fetch_add(1, Ordering::SeqCst) {
+                        0 => StatusCode::INTERNAL_SERVER_ERROR,
+                        _ => StatusCode::OK,
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, hits, last)
+    }
+
+    #[tokio::test]
+    async fn retries_until_the_receiver_accepts() {
+        let (url, hits, last) = receiver().await;
+        let delivery = delivery(url);
+        let timeout = Duration::from_secs(5);
+
+        let first = send(&delivery, timeout, true).await.unwrap();
+        assert_eq!(first, reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(next(!first.is_success(), 1, 3), Next::Retry);
+
+        let second = send(&delivery, timeout, true).await.unwrap();
+        assert_eq!(second, reqwest::StatusCode::OK);
+        assert_eq!(next(!second.is_success(), 2, 3), Next::Delivered);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        let (headers, body) = last.lock().unwrap().take().unwrap();
+        assert_eq!(headers[EVENT_HEADER], webhooks::EVENT_EXECUTION_FINISHED);
+        assert_eq!(headers[DELIVERY_HEADER], delivery.id.to_string().as_str());
+        let payload: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(payload["delivery_id"], serde_json::json!(delivery.id));
+
+        let signature = headers[SIGNATURE_HEADER].to_str().unwrap();
+        let timestamp: i64 = signature
+            .strip_prefix("t=")
+            .and_then(|rest| rest.split_once(','))
+            .map(|(timestamp, _)| timestamp.parse().unwrap())
+            .unwrap();
+        assert_eq!(signature, webhooks::sign("whsec_test", timestamp, &body));
+    }
+
+    #[tokio::test]
+    async fn refuses_internal_receivers() {
+        let (url, hits, _) = receiver().await;
+        let error = send(&delivery(url), Duration::from_secs(5), false).await.unwrap_err();
+        assert!(error.contains("not a public address"), "{}", error);
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+    }
+}
@@ -1,15 +1,50 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
 use axum::{
     extract::{
-        ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        Query, State,
     },
-    response::IntoResponse,
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+    Extension, Json,
 };
+use chrono::Utc;
 use futures::{sink::SinkExt, stream::StreamExt};
+use rand::{distributions::Alphanumeric, Rng};
 use serde::{Deserialize, Serialize};
-use tracing::{error, info};
+use tokio::{sync::mpsc, task::JoinHandle};
+use tracing::{error, info, warn};
 
-use crate::AppState;
+use crate::{
+    alerts,
+    auth::{
+        validation::{authenticate, verify_claims},
+        workspace::{self, WorkspaceRole},
+        Claims,
+    },
+    error::{AppError, Result},
+    executions,
+    logs::{self, ingest, LineFilter},
+    AppState,
+};
+
+const BEARER_PROTOCOL: &str = "bearer";
+const ACCESS_TOKEN_COOKIE: &str = "access_token";
+const TICKET_LEN: usize = 48;
+const TOKEN_EXPIRED_CLOSE_CODE: u16 = 4001;
+const MAX_STDIN_FRAME_BYTES: usize = 16 * 1024;
+/// Unacknowledged stdin frames allowed per execution before the client is
+/// told to back off.
+const STDIN_QUEUE_FRAMES: usize = 8;
+const OUTPUT_BATCH_LINES: i64 = 500;
+/// How often a subscriber checks for output ingested by another instance.
+const OUTPUT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// How often a notification subscriber checks for notifications stored by
+/// another instance.
+const NOTIFICATION_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const ALLOWED_SIGNALS: &[&str] = &["SIGINT", "SIGTERM", "SIGKILL", "SIGHUP", "SIGQUIT", "SIGUSR1", "SIGUSR2"];
 
 #[derive(Debug, Serialize, Deserialize)]
 struct WsMessage {
@@ -19,39 +54,575 @@ struct WsMessage {
     data: Option<serde_json::Value>,
 }
 
+impl WsMessage {
+    fn new(msg_type: &str, execution_id: Option<String>, data: Option<serde_json::Value>) -> Self {
+        Self {
+            msg_type: msg_type.to_string(),
+            execution_id,
+            data,
+        }
+    }
+
+    fn error(execution_id: Option<String>, message: &str) -> Self {
+        Self::new(
+            "error",
+            execution_id,
+            Some(serde_json::json!({ "message": message })),
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WsAuthQuery {
+    pub ticket: Option<String>,
+}
+
+fn ticket_key(ticket: &str) -> String {
+    format!("ws_ticket:{}", ticket)
+}
+
+/// Issues a short-lived, single-use ticket for clients that can neither set
+/// a subprotocol nor send cookies on the upgrade request.
+pub async fn issue_ticket(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<impl IntoResponse> {
+    if claims.is_impersonation() {
+        return Err(AppError::Forbidden);
+    }
+    let ticket: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(TICKET_LEN)
+        .map(char::from)
+        .collect();
+    let data = serde_json::to_string(&claims).map_err(|_| AppError::InternalServerError)?;
+
+    let mut conn = state.redis_client.as_ref().clone();
+    redis::cmd("SETEX")
+        .arg(ticket_key(&ticket))
+        .arg(state.config.ws_ticket_ttl_secs)
+        .arg(data)
+        .query_async::<_, ()>(&mut conn)
+        .await?;
+
+    Ok(Json(serde_json::json!({
+        "ticket": ticket,
+        "expires_in": state.config.ws_ticket_ttl_secs,
+    })))
+}
+
+async fn redeem_ticket(state: &AppState, ticket: &str) -> Result<Claims> {
+    let mut conn = state.redis_client.as_ref().clone();
+    let data: Option<String> = redis::cmd("GETDEL")
+        .arg(ticket_key(ticket))
+        .query_async(&mut conn)
+        .await?;
+
+    let claims: Claims = data
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .ok_or(AppError::Unauthorized)?;
+    if claims.exp <= Utc::now().timestamp() {
+        return Err(AppError::Unauthorized);
+    }
+    // The session may have been revoked since the ticket was issued
+    verify_claims(state, &claims).await?;
+    Ok(claims)
+}
+
+/// Browsers cannot set `Authorization` on an upgrade, so the token may ride
+/// in `Sec-WebSocket-Protocol` as `bearer, <token>`.
+fn token_from_protocols(headers: &HeaderMap) -> Option<String> {
+    let value = headers
+        .get(header::SEC_WEBSOCKET_PROTOCOL)?
+        .to_str()
+        .ok()?;
+
+    let mut protocols = value.split(',').map(str::trim);
+    protocols
+        .by_ref()
+        .find(|protocol| protocol.eq_ignore_ascii_case(BEARER_PROTOCOL))?;
+    protocols.next().map(str::to_string)
+}
+
+fn token_from_cookie(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == ACCESS_TOKEN_COOKIE)
+        .map(|(_, value)| value.to_string())
+}
+
+/// Browsers always send `Origin` on an upgrade, and other pages must not be
+/// able to open sockets with the user's credentials. Clients that send no
+/// `Origin` are not browsers.
+fn check_origin(state: &AppState, headers: &HeaderMap) -> Result<bool> {
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        return Ok(false);
+    };
+    let origin = origin.to_str().map_err(|_| AppError::Forbidden)?;
+    let origin = origin.trim_end_matches('/').to_lowercase();
+    if !state.config.ws_allowed_origins.contains(&origin) {
+        return Err(AppError::Forbidden);
+    }
+    Ok(true)
+}
+
+async fn authenticate_upgrade(state: &AppState, headers: &HeaderMap, query: &WsAuthQuery) -> Result<Claims> {
+    let from_browser = check_origin(state, headers)?;
+    let claims = match query.ticket.as_deref() {
+        Some(ticket) => redeem_ticket(state, ticket).await?,
+        None => {
+            // The cookie is ambient, so it only counts from an allowed page
+            let token = token_from_protocols(headers)
+                .or_else(|| from_browser.then(|| token_from_cookie(headers)).flatten())
+                .ok_or(AppError::Unauthorized)?;
+            authenticate(state, &token).await?
+        }
+    };
+
+    // Socket traffic never passes the impersonation guard or audit log
+    if claims.is_impersonation() {
+        return Err(AppError::Forbidden);
+    }
+    Ok(claims)
+}
+
 pub async fn handle_websocket(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
-) -> impl IntoResponse {
-    ws.on_upgrade(|socket| websocket_handler(socket, state))
+    Query(query): Query<WsAuthQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let claims = match authenticate_upgrade(&state, &headers, &query).await {
+        Ok(claims) => claims,
+        Err(e @ (AppError::ServiceUnavailable | AppError::Forbidden)) => return e.into_response(),
+        Err(_) => return AppError::Unauthorized.into_response(),
+    };
+
+    ws.protocols([BEARER_PROTOCOL])
+        .on_upgrade(move |socket| websocket_handler(socket, state, claims))
 }
 
-async fn websocket_handler(socket: WebSocket, state: AppState) {
+/// Tasks started on behalf of one socket. Dropping it (when the socket
+/// closes) stops them all.
+#[derive(Default)]
+struct Connection {
+    subscriptions: HashMap<String, JoinHandle<()>>,
+    stdin: HashMap<String, (mpsc::Sender<StdinChunk>, JoinHandle<()>)>,
+    /// Notification feeds, by workspace
+    notifications: HashMap<String, JoinHandle<()>>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        for task in self.subscriptions.values() {
+            task.abort();
+        }
+        for (_, task) in self.stdin.values() {
+            task.abort();
+        }
+        for task in self.notifications.values() {
+            task.abort();
+        }
+    }
+}
+
+#[derive(Debug)]
+struct StdinChunk {
+    data: Vec<u8>,
+    eof: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubscribeData {
+    #[serde(default)]
+    after: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct NotificationsData {
+    workspace_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct StdinData {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    eof: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct SignalData {
+    signal: String,
+}
+
+/// Follows an execution's stored output and forwards new lines as typed
+/// frames, starting after `after`. Lines were redacted when ingested.
+fn spawn_output_stream(state: AppState, execution_id: String, after: i64, tx: mpsc::Sender<WsMessage>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut last_seq = after;
+
+        loop {
+            let lines = match logs::after(&state, &execution_id, last_seq, &LineFilter::default(), OUTPUT_BATCH_LINES).await {
+                Ok(lines) => lines,
+                Err(e) => {
+                    warn!("Failed to read output for {}: {}", execution_id, e);
+                    let _ = tx
+                        .send(WsMessage::error(Some(execution_id), "Output stream unavailable"))
+                        .await;
+                    return;
+                }
+            };
+            let caught_up = (lines.len() as i64) < OUTPUT_BATCH_LINES;
+
+            for line in lines {
+                let msg_type = if line.stream == "stderr" { "stderr" } else { "stdout" };
+                last_seq = line.seq;
+                let message = WsMessage::new(
+                    msg_type,
+                    Some(execution_id.clone()),
+                    Some(serde_json::json!({
+                        "seq": line.seq,
+                        "text": line.message,
+                        "timestamp": line.timestamp,
+                        "format": line.format,
+                        "level": line.level,
+                        "parsed_message": line.parsed_message,
+                        "attributes": line.attributes,
+                    })),
+                );
+                // Awaiting here holds back reads when the client is slow
+                if tx.send(message).await.is_err() {
+                    return;
+                }
+            }
+            if !caught_up {
+                continue;
+            }
+
+            match state.execution_logs.subscribe(&execution_id) {
+                // Ingested here: wake as soon as more lines are stored
+                Some(mut progress) => {
+                    let _ = tokio::time::timeout(OUTPUT_POLL_INTERVAL, progress.wait_for(|seq| *seq > last_seq)).await;
+                }
+                None => {
+                    if output_finished(&state, &execution_id).await {
+                        // Lines stored since the last read are picked up first
+                        if logs::last_seq(&state, &execution_id).await.unwrap_or(last_seq) <= last_seq {
+                            break;
+                        }
+                        continue;
+                    }
+                    tokio::time::sleep(OUTPUT_POLL_INTERVAL).await;
+                }
+            }
+        }
+
+        let _ = tx
+            .send(WsMessage::new("output_end", Some(execution_id), None))
+            .await;
+    })
+}
+
+/// Forwards a workspace's alert notifications created from now on.
+fn spawn_notification_stream(state: AppState, workspace_id: String, tx: mpsc::Sender<WsMessage>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut stored = state.notifications.subscribe();
+        let mut last = match alerts::latest(&state, &workspace_id).await {
+            Ok(last) => last,
+            Err(e) => {
+                warn!("Failed to read notifications for workspace {}: {}", workspace_id, e);
+                let _ = tx.send(WsMessage::error(None, "Notifications unavailable")).await;
+                return;
+            }
+        };
+
+        loop {
+            // Stored here: wake at once; stored elsewhere: found by polling
+            let _ = tokio::time::timeout(NOTIFICATION_POLL_INTERVAL, stored.changed()).await;
+
+            let notifications = match alerts::notifications_after(&state, &workspace_id, last).await {
+                Ok(notifications) => notifications,
+                Err(e) => {
+                    warn!("Failed to read notifications for workspace {}: {}", workspace_id, e);
+                    continue;
+                }
+            };
+            for notification in notifications {
+                last = Some(notification.id);
+                let message = WsMessage::new(
+                    "notification",
+                    Some(notification.execution_id.clone()),
+                    serde_json::to_value(&notification).ok(),
+                );
+                if tx.send(message).await.is_err() {
+                    return;
+                }
+            }
+        }
+    })
+}
+
+/// Whether the execution has finished and no instance is still ingesting it.
+async fn output_finished(state: &AppState, execution_id: &str) -> bool {
+    let finished = matches!(
+        executions::find(state, execution_id).await,
+        Ok(Some(execution)) if executions::is_terminal(&execution.status)
+    );
+    finished && !ingest::is_active(state, execution_id).await.unwrap_or(true)
+}
+
+/// Writes stdin to the control plane one chunk at a time and acknowledges
+/// each, so the client knows when it may send more.
+fn spawn_stdin_writer(
+    state: AppState,
+    execution_id: String,
+    tx: mpsc::Sender<WsMessage>,
+) -> (mpsc::Sender<StdinChunk>, JoinHandle<()>) {
+    let (stdin_tx, mut stdin_rx) = mpsc::channel::<StdinChunk>(STDIN_QUEUE_FRAMES);
+
+    let task = tokio::spawn(async move {
+        while let Some(chunk) = stdin_rx.recv().await {
+            let bytes = chunk.data.len();
+            let reply = match state.control_plane_client.write_stdin(&execution_id, chunk.data, chunk.eof).await {
+                Ok(_) => WsMessage::new(
+                    "stdin_ack",
+                    Some(execution_id.clone()),
+                    Some(serde_json::json!({ "bytes": bytes, "eof": chunk.eof })),
+                ),
+                Err(e) => {
+                    warn!("Failed to write stdin for {}: {}", execution_id, e);
+                    WsMessage::error(Some(execution_id.clone()), "Failed to write stdin")
+                }
+            };
+            if tx.send(reply).await.is_err() || chunk.eof {
+                break;
+            }
+        }
+    });
+
+    (stdin_tx, task)
+}
+
+impl Connection {
+    async fn subscribe_notifications(
+        &mut self,
+        state: &AppState,
+        claims: &Claims,
+        data: Option<serde_json::Value>,
+        tx: &mpsc::Sender<WsMessage>,
+    ) -> WsMessage {
+        let Some(NotificationsData { workspace_id }) =
+            data.and_then(|data| serde_json::from_value::<NotificationsData>(data).ok())
+        else {
+            return WsMessage::error(None, "subscribe_notifications needs data.workspace_id");
+        };
+
+        if let Err(e) = workspace::require_role(state, claims, &workspace_id, WorkspaceRole::Viewer).await {
+            warn!("User {} denied notifications for workspace {}: {}", claims.sub, workspace_id, e);
+            return WsMessage::error(None, "Not authorized for this workspace");
+        }
+
+        let streaming = self.notifications.get(&workspace_id).is_some_and(|task| !task.is_finished());
+        if !streaming {
+            let task = spawn_notification_stream(state.clone(), workspace_id.clone(), tx.clone());
+            self.notifications.insert(workspace_id.clone(), task);
+        }
+        WsMessage::new(
+            "notifications_subscribed",
+            None,
+            Some(serde_json::json!({ "workspace_id": workspace_id })),
+        )
+    }
+
+    fn unsubscribe_notifications(&mut self, data: Option<serde_json::Value>) {
+        let workspace_id = data.and_then(|data| serde_json::from_value::<NotificationsData>(data).ok());
+        if let Some(task) = workspace_id.and_then(|data| self.notifications.remove(&data.workspace_id)) {
+            task.abort();
+        }
+    }
+
+    async fn subscribe(
+        &mut self,
+        state: &AppState,
+        claims: &Claims,
+        execution_id: String,
+        data: Option<serde_json::Value>,
+        tx: &mpsc::Sender<WsMessage>,
+    ) -> WsMessage {
+        // A valid socket does not grant access to every execution; each
+        // subscription is checked on its own
+        match executions::authorize(state, claims, &execution_id).await {
+            Ok(_) => {
+                info!("Client subscribed to execution: {}", execution_id);
+                let streaming = self.subscriptions.get(&execution_id).is_some_and(|task| !task.is_finished());
+                if !streaming {
+                    // Resuming clients pass the last `seq` they saw
+                    let after = data
+                        .and_then(|data| serde_json::from_value::<SubscribeData>(data).ok())
+                        .map_or(0, |data| data.after);
+                    let task = spawn_output_stream(state.clone(), execution_id.clone(), after, tx.clone());
+                    self.subscriptions.insert(execution_id.clone(), task);
+                }
+                WsMessage::new("subscribed", Some(execution_id), None)
+            }
+            Err(e) => {
+                warn!("User {} denied subscription to {}: {}", claims.sub, execution_id, e);
+                WsMessage::error(Some(execution_id), "Not authorized for this execution")
+            }
+        }
+    }
+
+    fn unsubscribe(&mut self, execution_id: &str) {
+        if let Some(task) = self.subscriptions.remove(execution_id) {
+            info!("Client unsubscribed from execution: {}", execution_id);
+            task.abort();
+        }
+    }
+
+    async fn stdin(
+        &mut self,
+        state: &AppState,
+        claims: &Claims,
+        execution_id: String,
+        data: Option<serde_json::Value>,
+        tx: &mpsc::Sender<WsMessage>,
+    ) -> Option<WsMessage> {
+        let Some(input) = data.and_then(|data| serde_json::from_value::<StdinData>(data).ok()) else {
+            return Some(WsMessage::error(Some(execution_id), "stdin needs data.text"));
+        };
+        if input.text.len() > MAX_STDIN_FRAME_BYTES {
+            return Some(WsMessage::error(
+                Some(execution_id),
+                &format!("stdin frames are limited to {} bytes", MAX_STDIN_FRAME_BYTES),
+            ));
+        }
+
+        if !self.stdin.contains_key(&execution_id) {
+            if let Err(e) = executions::authorize_interactive(state, claims, &execution_id).await {
+                warn!("User {} denied stdin for {}: {}", claims.sub, execution_id, e);
+                return Some(WsMessage::error(Some(execution_id), "Not allowed to write to this execution"));
+            }
+            let writer = spawn_stdin_writer(state.clone(), execution_id.clone(), tx.clone());
+            self.stdin.insert(execution_id.clone(), writer);
+        }
+
+        let (stdin_tx, _) = &self.stdin[&execution_id];
+        let chunk = StdinChunk {
+            data: input.text.into_bytes(),
+            eof: input.eof,
+        };
+        // Refuse rather than queue without bound; the client should wait
+        // for stdin_ack before sending more
+        match stdin_tx.try_send(chunk) {
+            Ok(()) => None,
+            Err(mpsc::error::TrySendError::Full(_)) => Some(WsMessage::new(
+                "stdin_rejected",
+                Some(execution_id),
+                Some(serde_json::json!({ "reason": "backpressure" })),
+            )),
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                self.stdin.remove(&execution_id);
+                Some(WsMessage::error(Some(execution_id), "stdin is closed"))
+            }
+        }
+    }
+
+    async fn signal(
+        &mut self,
+        state: &AppState,
+        claims: &Claims,
+        execution_id: String,
+        data: Option<serde_json::Value>,
+    ) -> WsMessage {
+        let Some(signal) = data
+            .and_then(|data| serde_json::from_value::<SignalData>(data).ok())
+            .map(|data| data.signal.to_uppercase())
+            .map(|signal| if signal.starts_with("SIG") { signal } else { format!("SIG{}", signal) })
+        else {
+            return WsMessage::error(Some(execution_id), "signal needs data.signal");
+        };
+        if !ALLOWED_SIGNALS.contains(&signal.as_str()) {
+            return WsMessage::error(
+                Some(execution_id),
+                &format!("Unsupported signal; allowed: {}", ALLOWED_SIGNALS.join(", ")),
+            );
+        }
+
+        if let Err(e) = executions::authorize_interactive(state, claims, &execution_id).await {
+            warn!("User {} denied signal for {}: {}", claims.sub, execution_id, e);
+            return WsMessage::error(Some(execution_id), "Not allowed to signal this execution");
+        }
+
+        match state.control_plane_client.send_signal(&execution_id, &signal).await {
+            Ok(response) => WsMessage::new(
+                "signal_sent",
+                Some(execution_id),
+                Some(serde_json::json!({ "signal": signal, "delivered": response.delivered })),
+            ),
+            Err(e) => {
+                warn!("Failed to signal {}: {}", execution_id, e);
+                WsMessage::error(Some(execution_id), "Failed to send signal")
+            }
+        }
+    }
+}
+
+async fn websocket_handler(socket: WebSocket, state: AppState, claims: Claims) {
     let (mut sender, mut receiver) = socket.split();
+    let (tx, mut rx) = mpsc::channel::<WsMessage>(32);
+
+    let user_id = claims.sub.clone();
+    let expires_in = Duration::from_secs((claims.exp - Utc::now().timestamp()).max(0) as u64);
 
     // Spawn a task to handle incoming messages
     let state_clone = state.clone();
     let mut recv_task = tokio::spawn(async move {
+        let mut connection = Connection::default();
+
         while let Some(msg) = receiver.next().await {
             match msg {
                 Ok(Message::Text(text)) => {
-                    if let Ok(ws_msg) = serde_json::from_str::<WsMessage>(&text) {
-                        match ws_msg.msg_type.as_str() {
-                            "subscribe" => {
-                                if let Some(execution_id) = ws_msg.execution_id {
-                                    info!("Client subscribed to execution: {}", execution_id);
-                                    // TODO: Subscribe to execution logs via Redis pub/sub
-                                }
-                            }
-                            "unsubscribe" => {
-                                if let Some(execution_id) = ws_msg.execution_id {
-                                    info!("Client unsubscribed from execution: {}", execution_id);
-                                    // TODO: Unsubscribe from execution logs
-                                }
-                            }
-                            _ => {
-                                error!("Unknown message type: {}", ws_msg.msg_type);
-                            }
+                    let Ok(ws_msg) = serde_json::from_str::<WsMessage>(&text) else {
+                        continue;
+                    };
+
+                    let reply = match (ws_msg.msg_type.as_str(), ws_msg.execution_id) {
+                        ("subscribe_notifications", _) => {
+                            Some(connection.subscribe_notifications(&state_clone, &claims, ws_msg.data, &tx).await)
+                        }
+                        ("unsubscribe_notifications", _) => {
+                            connection.unsubscribe_notifications(ws_msg.data);
+                            None
+                        }
+                        // Everything else is about one execution
+                        (_, None) => continue,
+                        ("subscribe", Some(execution_id)) => {
+                            Some(connection.subscribe(&state_clone, &claims, execution_id, ws_msg.data, &tx).await)
+                        }
+                        ("unsubscribe", Some(execution_id)) => {
+                            connection.unsubscribe(&execution_id);
+                            None
+                        }
+                        ("stdin", Some(execution_id)) => {
+                            connection.stdin(&state_clone, &claims, execution_id, ws_msg.data, &tx).await
+                        }
+                        ("signal", Some(execution_id)) => {
+                            Some(connection.signal(&state_clone, &claims, execution_id, ws_msg.data).await)
+                        }
+                        (msg_type, Some(_)) => {
+                            error!("Unknown message type: {}", msg_type);
+                            None
+                        }
+                    };
+
+                    if let Some(reply) = reply {
+                        if tx.send(reply).await.is_err() {
+                            break;
                         }
                     }
                 }
@@ -68,24 +639,35 @@ async fn websocket_handler(socket: WebSocket, state: AppState) {
         }
     });
 
-    // Spawn a task to send execution logs
+    // Spawn a task to send replies, heartbeats and execution output
     let mut send_task = tokio::spawn(async move {
-        // TODO: Subscribe to Redis pub/sub for execution logs
-        // For now, send a heartbeat every 30 seconds
         let mut interval = tokio::time::interval(std::time::Duration::from_secs(30));
-        
+        let expiry = tokio::time::sleep(expires_in);
+        tokio::pin!(expiry);
+
         loop {
-            interval.tick().await;
-            
-            let heartbeat = WsMessage {
-                msg_type: "heartbeat".to_string(),
-                execution_id: None,
-                data: Some(serde_json::json!({
-                    "timestamp": chrono::Utc::now().to_rfc3339(),
-                })),
+            let message = tokio::select! {
+                _ = interval.tick() => WsMessage::new(
+                    "heartbeat",
+                    None,
+                    Some(serde_json::json!({
+                        "timestamp": chrono::Utc::now().to_rfc3339(),
+                    })),
+                ),
+                Some(message) = rx.recv() => message,
+                _ = &mut expiry => {
+                    info!("Closing WebSocket for user {}: token expired", user_id);
+                    let _ = sender
+                        .send(Message::Close(Some(CloseFrame {
+                            code: TOKEN_EXPIRED_CLOSE_CODE,
+                            reason: "Token expired".into(),
+                        })))
+                        .await;
+                    break;
+                }
             };
-            
-            if let Ok(json) = serde_json::to_string(&heartbeat) {
+
+            if let Ok(json) = serde_json::to_string(&message) {
                 if sender.send(Message::Text(json)).await.is_err() {
                     break;
                 }
@@ -100,4 +682,4 @@ async fn websocket_handler(socket: WebSocket, state: AppState) {
     }
 
     info!("WebSocket connection closed");
-}
\ No newline at end of file
+}
@@ -1,65 +1,51 @@
 import { z } from "zod";
-import { eq, and, desc, sql } from "drizzle-orm";
+import { eq, and } from "drizzle-orm";
 import { TRPCError } from "@trpc/server";
 import { createTRPCRouter, protectedProcedure } from "~/server/api/trpc";
 import { executions, workspaceMembers, activityLogs } from "~/server/db/schema";
-import { hermesClient } from "~/server/lib/hermes-client";
+import { HermesError, hermesClient } from "~/server/lib/hermes-client";
 
 export const executionRouter = createTRPCRouter({
-  // List executions in a workspace
+  // List executions in a workspace (filtered and paginated by the backend)
   list: protectedProcedure
     .input(
       z.object({
         workspaceId: z.string().uuid(),
         limit: z.number().min(1).max(100).default(20),
-        offset: z.number().min(0).default(0),
-        status: z.enum(["pending", "running", "completed", "failed", "cancelled"]).optional(),
+        cursor: z.string().optional(),
+        status: z.array(z.enum(["pending", "running", "completed", "failed", "cancelled", "timeout", "lost"])).optional(),
+        language: z.array(z.string()).optional(),
+        createdBy: z.string().optional(),
+        createdAfter: z.date().optional(),
+        createdBefore: z.date().optional(),
+        tags: z.array(z.string()).optional(),
       })
     )
     .query(async ({ ctx, input }) => {
-      // Check access
-      const hasAccess = await ctx.db
-        .select()
-        .from(workspaceMembers)
-        .where(
-          and(
-            eq(workspaceMembers.workspaceId, input.workspaceId),
-            eq(workspaceMembers.userId, ctx.session.user.id)
-          )
-        )
-        .limit(1);
-
-      if (hasAccess.length === 0) {
+      try {
+        const result = await hermesClient.listExecutions(input, ctx.session.token);
+        return {
+          items: result.executions,
+          nextCursor: result.next_cursor,
+        };
+      } catch (error) {
+        if (error instanceof HermesError && error.status === 403) {
+          throw new TRPCError({
+            code: "FORBIDDEN",
+            message: "Access denied",
+          });
+        }
+        if (error instanceof HermesError && error.status === 400) {
+          throw new TRPCError({
+            code: "BAD_REQUEST",
+            message: "Invalid execution filter",
+          });
+        }
         throw new TRPCError({
-          code: "FORBIDDEN",
-          message: "Access denied",
+          code: "INTERNAL_SERVER_ERROR",
+          message: "Failed to list executions",
         });
       }
-
-      const where = input.status
-        ? and(eq(executions.workspaceId, input.workspaceId), eq(executions.status, input.status))
-        : eq(executions.workspaceId, input.workspaceId);
-
-      const results = await ctx.db
-        .select()
-        .from(executions)
-        .where(where)
-        .orderBy(desc(executions.createdAt))
-        .limit(input.limit)
-        .offset(input.offset);
-
-      const [countResult] = await ctx.db
-        .select({ count: sql<number>`count(*)::int` })
-        .from(executions)
-        .where(where);
-
-      const totalCount = countResult?.count ?? 0;
-
-      return {
-        items: results,
-        total: totalCount,
-        hasMore: input.offset + results.length < totalCount,
-      };
     }),
 
   // Get single execution
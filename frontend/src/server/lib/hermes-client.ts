@@ -11,6 +11,38 @@ interface ExecutionResponse {
   executionId: string;
 }
 
+interface ListExecutionsQuery {
+  workspaceId?: string;
+  status?: string[];
+  language?: string[];
+  createdBy?: string;
+  createdAfter?: Date;
+  createdBefore?: Date;
+  tags?: string[];
+  cursor?: string;
+  limit?: number;
+}
+
+interface ExecutionSummary {
+  id: string;
+  owner_id: string;
+  workspace_id: string | null;
+  language: string;
+  language_version: string | null;
+  status: string;
+  code_hash: string;
+  tags: string[];
+  parent_execution_id: string | null;
+  created_at: string;
+  updated_at: string;
+  completed_at: string | null;
+}
+
+interface ListExecutionsResponse {
+  executions: ExecutionSummary[];
+  next_cursor: string | null;
+}
+
 interface MemorySearchRequest {
   namespace: string;
   query: string;
@@ -48,11 +80,33 @@ interface MemoryQueryResponse {
 }
 
 class HermesClient {
+  private readonly baseUrl: string;
+
   constructor() {
-    // Base URL would be used in production for actual HTTP calls
-    // For now, using mock implementations
-    const _baseUrl = process.env.HERMES_BACKEND_URL ?? "http://localhost:8090";
-    void _baseUrl; // Acknowledge but not use in mocks
+    // Only listing calls the backend so far; the rest are mock implementations
+    this.baseUrl = process.env.HERMES_BACKEND_URL ?? "http://localhost:8090";
+  }
+
+  // Filtered, cursor-paginated history, as seen by the caller's token
+  async listExecutions(query: ListExecutionsQuery, token: string): Promise<ListExecutionsResponse> {
+    const params = new URLSearchParams();
+    if (query.workspaceId) params.set("workspace_id", query.workspaceId);
+    if (query.status?.length) params.set("status", query.status.join(","));
+    if (query.language?.length) params.set("language", query.language.join(","));
+    if (query.createdBy) params.set("created_by", query.createdBy);
+    if (query.createdAfter) params.set("created_after", query.createdAfter.toISOString());
+    if (query.createdBefore) params.set("created_before", query.createdBefore.toISOString());
+    if (query.tags?.length) params.set("tags", query.tags.join(","));
+    if (query.cursor) params.set("cursor", query.cursor);
+    if (query.limit) params.set("limit", String(query.limit));
+
+    const response = await fetch(`${this.baseUrl}/api/executions?${params.toString()}`, {
+      headers: { Authorization: `Bearer ${token}` },
+    });
+    if (!response.ok) {
+      throw new HermesError(response.status, await response.text());
+    }
+    return (await response.json()) as ListExecutionsResponse;
   }
 
   async createExecution(_request: ExecutionRequest): Promise<ExecutionResponse> {
@@ -119,4 +173,13 @@ class HermesClient {
   }
 }
 
+export class HermesError extends Error {
+  constructor(
+    readonly status: number,
+    body: string
+  ) {
+    super(`Hermes backend returned ${status}: ${body}`);
+  }
+}
+
 export const hermesClient = new HermesClient();